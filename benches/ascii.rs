@@ -0,0 +1,43 @@
+// ASCII判定について、1バイトずつ調べる素朴な実装と8バイト・16バイトずつまとめて調べる実装を比べる
+// 結果はtarget/criterion/に残るので、変更の前後で比べれば性能の後退に気づける
+extern crate alloc;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+// src/main.rsはバイナリクレートなので、asciiモジュールのソースをそのまま取り込む
+#[allow(dead_code, unused_macros)]
+#[path = "../src/ascii.rs"]
+mod ascii;
+
+use ascii::{is_ascii_fast, is_ascii_scalar, is_ascii_word};
+
+// 数MBの入力では、まとめて調べる効果がはっきり出る
+const SIZES: [usize; 3] = [1024, 64 * 1024, 8 * 1024 * 1024];
+
+type Check = fn(&[u8]) -> bool;
+
+// 全体がASCIIの入力では、どの実装も最後まで読まなければならない
+fn all_ascii(c: &mut Criterion) {
+    let mut group = c.benchmark_group("is_ascii");
+    for &len in SIZES.iter() {
+        let bytes = vec![b'x'; len];
+        group.throughput(Throughput::Bytes(len as u64));
+        let mut checks: Vec<(&str, Check)> = vec![
+            ("scalar", is_ascii_scalar),
+            ("word", is_ascii_word),
+            ("fast", is_ascii_fast),
+            ("std", <[u8]>::is_ascii)
+        ];
+        #[cfg(target_arch = "x86_64")]
+        checks.push(("sse2", ascii::is_ascii_sse2));
+        for (name, check) in checks {
+            group.bench_with_input(BenchmarkId::new(name, len), &bytes, |b, bytes| {
+                b.iter(|| assert!(check(black_box(bytes))));
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, all_ascii);
+criterion_main!(benches);
@@ -0,0 +1,166 @@
+// エディタでよくある編集のパターンについて、GapBuffer<char>とVec<char>、VecDeque<char>を比べる
+// 結果はtarget/criterion/に残るので、変更の前後で比べれば性能の後退に気づける
+extern crate alloc;
+
+use std::collections::VecDeque;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+// src/main.rsはバイナリクレートなので、GapBufferのソースをそのまま取り込む
+#[allow(dead_code)]
+#[path = "../src/gap.rs"]
+mod gap;
+
+use gap::GapBuffer;
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+// 1回の計測で挿入・削除する文字数
+const EDITS: usize = 100;
+
+fn text(len: usize) -> impl Iterator<Item=char> + Clone {
+    "the quick brown fox jumps over the lazy dog\n".chars().cycle().take(len)
+}
+
+// 再現できるよう、固定の種から編集する位置を作る
+fn positions(len: usize) -> Vec<usize> {
+    let mut seed = 12345u32;
+    (0..EDITS).map(|i| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 8) as usize % (len + i)
+    }).collect()
+}
+
+// 文書の真ん中にカーソルを置き、続けて文字を打ち込む
+fn cursor_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cursor_insert");
+    for &len in SIZES.iter() {
+        group.bench_with_input(BenchmarkId::new("GapBuffer", len), &len, |b, &len| {
+            b.iter_batched_ref(|| text(len).collect::<GapBuffer<char>>(), |buf| {
+                buf.set_position(len / 2);
+                for ch in text(EDITS) {
+                    buf.insert(ch);
+                }
+            }, BatchSize::LargeInput);
+        });
+        group.bench_with_input(BenchmarkId::new("Vec", len), &len, |b, &len| {
+            b.iter_batched_ref(|| text(len).collect::<Vec<char>>(), |vec| {
+                for (i, ch) in text(EDITS).enumerate() {
+                    vec.insert(len / 2 + i, ch);
+                }
+            }, BatchSize::LargeInput);
+        });
+        group.bench_with_input(BenchmarkId::new("VecDeque", len), &len, |b, &len| {
+            b.iter_batched_ref(|| text(len).collect::<VecDeque<char>>(), |deque| {
+                for (i, ch) in text(EDITS).enumerate() {
+                    deque.insert(len / 2 + i, ch);
+                }
+            }, BatchSize::LargeInput);
+        });
+    }
+    group.finish();
+}
+
+// 文書の真ん中にカーソルを置き、続けてカーソルの後ろの文字を消す
+fn cursor_delete(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cursor_delete");
+    for &len in SIZES.iter() {
+        group.bench_with_input(BenchmarkId::new("GapBuffer", len), &len, |b, &len| {
+            b.iter_batched_ref(|| text(len).collect::<GapBuffer<char>>(), |buf| {
+                buf.set_position(len / 2);
+                for _ in 0..EDITS {
+                    buf.remove();
+                }
+            }, BatchSize::LargeInput);
+        });
+        group.bench_with_input(BenchmarkId::new("Vec", len), &len, |b, &len| {
+            b.iter_batched_ref(|| text(len).collect::<Vec<char>>(), |vec| {
+                for _ in 0..EDITS {
+                    vec.remove(len / 2);
+                }
+            }, BatchSize::LargeInput);
+        });
+        group.bench_with_input(BenchmarkId::new("VecDeque", len), &len, |b, &len| {
+            b.iter_batched_ref(|| text(len).collect::<VecDeque<char>>(), |deque| {
+                for _ in 0..EDITS {
+                    deque.remove(len / 2);
+                }
+            }, BatchSize::LargeInput);
+        });
+    }
+    group.finish();
+}
+
+// 文書のあちこちに1文字ずつ挿入する。GapBufferにとっては毎回ギャップを動かす最悪に近い使い方
+fn random_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("random_insert");
+    for &len in SIZES.iter() {
+        let positions = positions(len);
+        group.bench_with_input(BenchmarkId::new("GapBuffer", len), &len, |b, &len| {
+            b.iter_batched_ref(|| text(len).collect::<GapBuffer<char>>(), |buf| {
+                for &pos in positions.iter() {
+                    buf.set_position(pos);
+                    buf.insert('x');
+                }
+            }, BatchSize::LargeInput);
+        });
+        group.bench_with_input(BenchmarkId::new("Vec", len), &len, |b, &len| {
+            b.iter_batched_ref(|| text(len).collect::<Vec<char>>(), |vec| {
+                for &pos in positions.iter() {
+                    vec.insert(pos, 'x');
+                }
+            }, BatchSize::LargeInput);
+        });
+        group.bench_with_input(BenchmarkId::new("VecDeque", len), &len, |b, &len| {
+            b.iter_batched_ref(|| text(len).collect::<VecDeque<char>>(), |deque| {
+                for &pos in positions.iter() {
+                    deque.insert(pos, 'x');
+                }
+            }, BatchSize::LargeInput);
+        });
+    }
+    group.finish();
+}
+
+// ファイルを開いた時のように、文書全体を一度に読み込む
+fn bulk_load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bulk_load");
+    for &len in SIZES.iter() {
+        group.bench_with_input(BenchmarkId::new("GapBuffer", len), &len, |b, &len| {
+            b.iter(|| text(len).collect::<GapBuffer<char>>());
+        });
+        group.bench_with_input(BenchmarkId::new("Vec", len), &len, |b, &len| {
+            b.iter(|| text(len).collect::<Vec<char>>());
+        });
+        group.bench_with_input(BenchmarkId::new("VecDeque", len), &len, |b, &len| {
+            b.iter(|| text(len).collect::<VecDeque<char>>());
+        });
+    }
+    group.finish();
+}
+
+// 空のGapBufferに文書全体を挿入する。insert_sliceはギャップへ一度に写すので、1要素ずつ挿入するより速いはず
+fn insert_slice(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_slice");
+    for &len in SIZES.iter() {
+        let chars: Vec<char> = text(len).collect();
+        group.bench_with_input(BenchmarkId::new("insert_iter", len), &chars, |b, chars| {
+            b.iter(|| {
+                let mut buf = GapBuffer::new();
+                buf.insert_iter(chars.iter().cloned());
+                buf
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("insert_slice", len), &chars, |b, chars| {
+            b.iter(|| {
+                let mut buf = GapBuffer::new();
+                buf.insert_slice(chars);
+                buf
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cursor_insert, cursor_delete, random_insert, bulk_load, insert_slice);
+criterion_main!(benches);
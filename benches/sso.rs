@@ -0,0 +1,99 @@
+// 短いキーを大量に扱う処理について、SsoStringとStringを比べる
+// 結果はtarget/criterion/に残るので、変更の前後で比べれば性能の後退に気づける
+extern crate alloc;
+
+use std::collections::HashMap;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+// src/main.rsはバイナリクレートなので、SsoStringのソースをそのまま取り込む
+#[allow(dead_code)]
+#[path = "../src/sso.rs"]
+mod sso;
+
+use sso::SsoString;
+
+// キーの長さ。SsoStringが自分の中に置ける長さと、ヒープに置く長さの両方を測る
+const KEY_LENS: [usize; 3] = [8, 20, 40];
+
+const KEYS: usize = 10_000;
+
+// 再現できるよう、固定の種からキーを作る
+fn keys(len: usize) -> Vec<String> {
+    let mut seed = 12345u32;
+    (0..KEYS).map(|_| {
+        (0..len).map(|_| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (b'a' + (seed >> 16) as u8 % 26) as char
+        }).collect()
+    }).collect()
+}
+
+// &strからキーを作ってすぐに捨てる
+fn create(c: &mut Criterion) {
+    let mut group = c.benchmark_group("create");
+    for &len in KEY_LENS.iter() {
+        let keys = keys(len);
+        group.bench_with_input(BenchmarkId::new("SsoString", len), &keys, |b, keys| {
+            b.iter(|| keys.iter().map(|k| SsoString::from(k.as_str())).filter(|k| k.ends_with('a')).count());
+        });
+        group.bench_with_input(BenchmarkId::new("String", len), &keys, |b, keys| {
+            b.iter(|| keys.iter().map(|k| String::from(k.as_str())).filter(|k| k.ends_with('a')).count());
+        });
+    }
+    group.finish();
+}
+
+// キーを数えるHashMapを作り、&strで引く
+fn count_keys(c: &mut Criterion) {
+    let mut group = c.benchmark_group("count_keys");
+    for &len in KEY_LENS.iter() {
+        let keys = keys(len);
+        group.bench_with_input(BenchmarkId::new("SsoString", len), &keys, |b, keys| {
+            b.iter(|| {
+                let mut counts: HashMap<SsoString, usize> = HashMap::new();
+                for k in keys {
+                    *counts.entry(SsoString::from(k.as_str())).or_insert(0) += 1;
+                }
+                keys.iter().filter(|k| counts.contains_key(k.as_str())).count()
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("String", len), &keys, |b, keys| {
+            b.iter(|| {
+                let mut counts: HashMap<String, usize> = HashMap::new();
+                for k in keys {
+                    *counts.entry(String::from(k.as_str())).or_insert(0) += 1;
+                }
+                keys.iter().filter(|k| counts.contains_key(k.as_str())).count()
+            });
+        });
+    }
+    group.finish();
+}
+
+// キーを並べ替える。比較のたびに文字列を読むので、ヒープへの間接参照の有無が効く
+fn sort_keys(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sort_keys");
+    for &len in KEY_LENS.iter() {
+        let keys = keys(len);
+        let sso: Vec<SsoString> = keys.iter().map(|k| SsoString::from(k.as_str())).collect();
+        group.bench_with_input(BenchmarkId::new("SsoString", len), &sso, |b, sso| {
+            b.iter(|| {
+                let mut sorted = sso.clone();
+                sorted.sort_unstable();
+                sorted
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("String", len), &keys, |b, keys| {
+            b.iter(|| {
+                let mut sorted = keys.clone();
+                sorted.sort_unstable();
+                sorted
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, create, count_keys, sort_keys);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+#![no_main]
+extern crate alloc;
+
+use libfuzzer_sys::fuzz_target;
+
+// src/main.rsはバイナリクレートでライブラリとして参照できないので、GapBufferのソースをそのまま取り込む
+#[allow(dead_code)]
+#[path = "../../src/gap.rs"]
+mod gap;
+
+fuzz_target!(|data: &[u8]| {
+    gap::testing::check_against_vec(data);
+});
@@ -0,0 +1,41 @@
+// gapモジュールとasciiモジュールがstdに頼らずビルドできることを確かめるためのクレート
+// stdを持たないターゲット向けにビルドする
+//
+// $ cargo build --target thumbv7em-none-eabihf
+#![no_std]
+
+extern crate alloc;
+
+#[path = "../../src/gap.rs"]
+pub mod gap;
+
+#[macro_use]
+#[path = "../../src/ascii.rs"]
+pub mod ascii;
+
+use alloc::borrow::ToOwned;
+use ascii::{Ascii, AsciiStr, NotAsciiError};
+use gap::{GapBuffer, GapString};
+
+// 組み込みのテキストUIでの使い方のように、カーソル位置で挿入と削除を行う
+pub fn type_and_backspace(text: &mut GapString, typed: &str, backspaces: usize) {
+    text.insert_str(typed);
+    for _ in 0..backspaces {
+        text.remove_char();
+    }
+}
+
+pub fn collect_lines(bytes: &[u8]) -> GapBuffer<usize> {
+    bytes.iter().enumerate().filter(|&(_, &b)| b == b'\n').map(|(i, _)| i).collect()
+}
+
+// ASCIIだけのプロトコルのフレームを組み立てる。コマンドは検証済みなので、そのまま送れる
+pub fn frame(command: &AsciiStr, payload: &[u8]) -> Result<Ascii, NotAsciiError> {
+    let payload = Ascii::from_bytes(payload.into())?;
+    let mut frame = ascii!("$").to_owned();
+    frame += command;
+    frame += ascii!(",");
+    frame += &payload;
+    frame += ascii!("\r\n");
+    Ok(frame)
+}
@@ -0,0 +1,1504 @@
+// ASCIIテキストを扱う型
+// coreとallocだけに依存するので、stdフィーチャーを無効にしたno_std環境でも使える
+// ビルドできることはno_std_check/で組み込み向けのターゲットに対して確かめる
+use alloc::borrow::{Cow, ToOwned};
+use alloc::ffi::{CString, NulError};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ffi::CStr;
+
+// バイト列の大小比較はそのままASCIIの辞書順比較と一致する
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord)]
+pub struct Ascii(
+    Vec<u8> // ASCIIテキストだけを保持する 0 - 0x7f までのバイト列
+    );
+
+impl Ascii {
+    // 引数 bytes 内のASCIIテキストから型 Ascii を作る
+    // ASCIIでない文字列が入っていたらNotAsciiErrorを返す
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Ascii, NotAsciiError> {
+        if !is_ascii_fast(&bytes) {
+            return Err(NotAsciiError::new(bytes));
+        }
+
+        Ok(Ascii(bytes))
+    }
+
+    // 引数をチェックしないコンストラクタ
+    // 呼び出し元は0x7f以下のバイトのみ引数に渡さないと未定義動作となるためunsafeキーワードでマーク
+    pub unsafe fn from_bytes_unchecked(bytes: Vec<u8>) -> Ascii {
+        Ascii(bytes)
+    }
+
+    // ASCIIでないバイトを'?'に置き換えてAsciiを作る
+    // バッファを持っていない時は、全てASCIIなら借用で済むAsciiStr::from_bytes_lossyを使う
+    pub fn from_bytes_lossy(bytes: Vec<u8>) -> Ascii {
+        Ascii::from_bytes_lossy_with(bytes, AsciiChar(b'?'))
+    }
+
+    // ASCIIでないバイトを引数replacementに置き換えてAsciiを作る
+    // 置き換えは受け取ったバッファ上で行うので、全てASCIIの入力ならコピーも書き込みも発生しない
+    pub fn from_bytes_lossy_with(mut bytes: Vec<u8>, replacement: AsciiChar) -> Ascii {
+        if !is_ascii_fast(&bytes) {
+            for byte in bytes.iter_mut().filter(|byte| !byte.is_ascii()) {
+                *byte = replacement.as_byte();
+            }
+        }
+        Ascii(bytes)
+    }
+
+    // 大文字を全て小文字に変換する
+    // ASCII同士の変換なので不変条件は保たれる
+    pub fn make_ascii_lowercase(&mut self) {
+        self.0.make_ascii_lowercase();
+    }
+
+    // 小文字を全て大文字に変換する
+    pub fn make_ascii_uppercase(&mut self) {
+        self.0.make_ascii_uppercase();
+    }
+
+    // 末尾にASCII文字を1つ追加する
+    pub fn push(&mut self, ch: AsciiChar) {
+        self.0.push(ch.as_byte());
+    }
+
+    // 末尾にASCIIテキストを追加する
+    // どちらもASCIIなので連結結果を検証し直す必要はない
+    pub fn push_str(&mut self, s: &AsciiStr) {
+        self.0.extend_from_slice(s.as_bytes());
+    }
+}
+
+// 文字列リテラルをコンパイル時に検証して&'static AsciiStrを作る
+// 定数として評価させるので、呼び出し側にunsafeも実行時の検証も要らない
+macro_rules! ascii {
+    ($s:expr) => {{
+        const ASCII: &'static $crate::ascii::AsciiStr =
+            $crate::ascii::AsciiStr::from_static($s.as_bytes());
+        ASCII
+    }};
+}
+
+impl core::fmt::Debug for Ascii {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        core::fmt::Debug::fmt(&**self, f)
+    }
+}
+
+// Asciiから借用したASCIIテキスト
+// Stringに対するstrと同じ関係で、[u8]と同じメモリ表現を持つ
+#[derive(Eq, PartialEq, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct AsciiStr([u8]);
+
+impl AsciiStr {
+    // 引数bytesが全てASCIIならAsciiStrとして借用する
+    pub fn from_bytes(bytes: &[u8]) -> Option<&AsciiStr> {
+        if is_ascii_fast(bytes) {
+            Some(unsafe { AsciiStr::from_bytes_unchecked(bytes) })
+        } else {
+            None
+        }
+    }
+
+    // ASCIIでないバイトを'?'に置き換えたテキストを返す
+    // 全てASCIIならbytesをそのまま借用し、コピーもメモリ確保もしない
+    pub fn from_bytes_lossy(bytes: &[u8]) -> Cow<'_, AsciiStr> {
+        AsciiStr::from_bytes_lossy_with(bytes, AsciiChar(b'?'))
+    }
+
+    // ASCIIでないバイトを引数replacementに置き換えたテキストを返す
+    // 置き換えが必要な時だけコピーを作る
+    pub fn from_bytes_lossy_with(bytes: &[u8], replacement: AsciiChar) -> Cow<'_, AsciiStr> {
+        match AsciiStr::from_bytes(bytes) {
+            Some(ascii) => Cow::Borrowed(ascii),
+            None => Cow::Owned(Ascii::from_bytes_lossy_with(bytes.to_vec(), replacement))
+        }
+    }
+
+    // コンパイル時に評価できる検証付きコンストラクタ
+    // ASCIIでないバイトが含まれていればpanicし、const文脈で呼べばコンパイルエラーになる
+    pub const fn from_static(bytes: &'static [u8]) -> &'static AsciiStr {
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] >= 0x80 {
+                panic!("non-ASCII byte in AsciiStr::from_static");
+            }
+            i += 1;
+        }
+        // 直前のループで全てASCIIであることを確かめた
+        unsafe { &*(bytes as *const [u8] as *const AsciiStr) }
+    }
+
+    // 引数をチェックせずに借用する
+    // 呼び出し元は0x7f以下のバイトのみ渡さなければならない
+    unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &AsciiStr {
+        // repr(transparent)なので&[u8]と&AsciiStrはポインタも長さもそのまま読み替えられる
+        &*(bytes as *const [u8] as *const AsciiStr)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    // ASCIIはそのままUTF8としても正しいので検証せずに&strへ変換できる
+    pub fn as_str(&self) -> &str {
+        unsafe { core::str::from_utf8_unchecked(&self.0) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // index番目の文字を返す
+    // indexが範囲外ならNoneを返す
+    pub fn get(&self, index: usize) -> Option<AsciiChar> {
+        self.0.get(index).map(|&byte| AsciiChar(byte))
+    }
+
+    // 制御文字などをエスケープした文字を順に返すイテレータを作る
+    pub fn escape_default(&self) -> EscapeDefault<'_> {
+        EscapeDefault {
+            bytes: self.0.iter(),
+            current: None
+        }
+    }
+
+    // 大文字・小文字の違いを無視して比較する
+    pub fn eq_ignore_ascii_case(&self, other: &AsciiStr) -> bool {
+        self.0.eq_ignore_ascii_case(&other.0)
+    }
+
+    // 引数patが含まれているか
+    pub fn contains<P: AsciiPattern>(&self, pat: P) -> bool {
+        self.find(pat).is_some()
+    }
+
+    // 最初にpatが現れる位置を返す
+    pub fn find<P: AsciiPattern>(&self, pat: P) -> Option<usize> {
+        let needle = pat.as_bytes();
+        let (&first, rest) = match needle.split_first() {
+            Some(split) => split,
+            None => return Some(0)
+        };
+        // 先頭の1バイトをmemchrで探し、見つかった候補の位置で残りを比較する
+        let mut offset = 0;
+        while let Some(i) = memchr(first, &self.0[offset..]) {
+            let candidate = offset + i;
+            if self.0[candidate + 1..].starts_with(rest) {
+                return Some(candidate);
+            }
+            offset = candidate + 1;
+        }
+        None
+    }
+
+    pub fn starts_with<P: AsciiPattern>(&self, pat: P) -> bool {
+        self.0.starts_with(pat.as_bytes())
+    }
+
+    pub fn ends_with<P: AsciiPattern>(&self, pat: P) -> bool {
+        self.0.ends_with(pat.as_bytes())
+    }
+
+    // 区切り文字sepで分割した部分列を順に返すイテレータを作る
+    pub fn split(&self, sep: AsciiChar) -> Split<'_> {
+        Split {
+            rest: Some(&self.0),
+            sep: sep.as_byte()
+        }
+    }
+
+    // str::linesと同じく\nまたは\r\nで分割した行を順に返すイテレータを作る
+    pub fn lines(&self) -> Lines<'_> {
+        Lines {
+            rest: &self.0
+        }
+    }
+
+    // 空白文字の並びで分割した空でない部分列を順に返すイテレータを作る
+    pub fn split_whitespace(&self) -> SplitWhitespace<'_> {
+        SplitWhitespace {
+            rest: &self.0
+        }
+    }
+}
+
+// AsciiStrの検索メソッドに渡せるパターン
+// ASCIIテキストにはASCIIでないバイトが無いので、ASCIIでない文字を含む&strはどこにもマッチしない
+pub trait AsciiPattern {
+    fn as_bytes(&self) -> &[u8];
+}
+
+impl AsciiPattern for &str {
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+}
+
+impl AsciiPattern for &AsciiStr {
+    fn as_bytes(&self) -> &[u8] {
+        AsciiStr::as_bytes(self)
+    }
+}
+
+impl AsciiPattern for &Ascii {
+    fn as_bytes(&self) -> &[u8] {
+        AsciiStr::as_bytes(self)
+    }
+}
+
+impl AsciiPattern for AsciiChar {
+    fn as_bytes(&self) -> &[u8] {
+        // AsciiCharはrepr(transparent)なのでu8の1要素スライスとして読み替えられる
+        core::slice::from_ref(&self.0)
+    }
+}
+
+// haystackの中から最初にneedleが現れる位置を返す
+// 8バイトずつまとめて、needleと一致するバイトを含むかをビット演算で判定する
+// AsciiStr::findの中で使う内部の関数なので、クレートの外には公開しない
+pub(crate) fn memchr(needle: u8, haystack: &[u8]) -> Option<usize> {
+    const LO: u64 = 0x0101_0101_0101_0101;
+    const HI: u64 = 0x8080_8080_8080_8080;
+    let repeated = LO * needle as u64;
+
+    // u64はどんなビットパターンでも有効な値なのでalign_toによる読み替えは安全
+    let (head, words, _) = unsafe { haystack.align_to::<u64>() };
+    if let Some(i) = head.iter().position(|&byte| byte == needle) {
+        return Some(i);
+    }
+    let mut offset = head.len();
+    for &word in words {
+        // 一致したバイトが0になるので、0のバイトを含むwordを探す
+        let x = word ^ repeated;
+        if x.wrapping_sub(LO) & !x & HI != 0 {
+            break;
+        }
+        offset += 8;
+    }
+    // 一致を含むword以降（あるいは末尾の半端なバイト列）を1バイトずつ調べる
+    haystack[offset..].iter().position(|&byte| byte == needle).map(|i| offset + i)
+}
+
+// 以下の分割イテレータは全てASCIIテキストの部分列を借用して返す
+// ASCIIテキストをどこで区切ってもASCIIテキストのままなので検証し直す必要はない
+pub struct Split<'a> {
+    // 最後の部分列を返した後はNoneになる
+    rest: Option<&'a [u8]>,
+    sep: u8
+}
+
+impl<'a> Iterator for Split<'a> {
+    type Item = &'a AsciiStr;
+
+    fn next(&mut self) -> Option<&'a AsciiStr> {
+        let rest = self.rest?;
+        let field = match rest.iter().position(|&byte| byte == self.sep) {
+            Some(i) => {
+                self.rest = Some(&rest[i + 1..]);
+                &rest[..i]
+            }
+            None => {
+                self.rest = None;
+                rest
+            }
+        };
+        Some(unsafe { AsciiStr::from_bytes_unchecked(field) })
+    }
+}
+
+pub struct Lines<'a> {
+    rest: &'a [u8]
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a AsciiStr;
+
+    fn next(&mut self) -> Option<&'a AsciiStr> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let mut line = match self.rest.iter().position(|&byte| byte == b'\n') {
+            Some(i) => {
+                let line = &self.rest[..i];
+                self.rest = &self.rest[i + 1..];
+                line
+            }
+            None => {
+                let line = self.rest;
+                self.rest = &[];
+                line
+            }
+        };
+        if let Some((&b'\r', without_cr)) = line.split_last() {
+            line = without_cr;
+        }
+        Some(unsafe { AsciiStr::from_bytes_unchecked(line) })
+    }
+}
+
+pub struct SplitWhitespace<'a> {
+    rest: &'a [u8]
+}
+
+impl<'a> Iterator for SplitWhitespace<'a> {
+    type Item = &'a AsciiStr;
+
+    fn next(&mut self) -> Option<&'a AsciiStr> {
+        let start = self.rest.iter().position(|byte| !byte.is_ascii_whitespace())?;
+        let rest = &self.rest[start..];
+        let end = rest.iter().position(u8::is_ascii_whitespace).unwrap_or(rest.len());
+        self.rest = &rest[end..];
+        Some(unsafe { AsciiStr::from_bytes_unchecked(&rest[..end]) })
+    }
+}
+
+// 印字可能な文字はそのまま、制御文字は \n や \x07 のようにエスケープして表示する
+impl core::fmt::Debug for AsciiStr {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "\"{}\"", self.escape_default())
+    }
+}
+
+// 範囲外のindexを渡すと[u8]の添字アクセスと同じくpanicを起こす
+impl core::ops::Index<usize> for AsciiStr {
+    type Output = AsciiChar;
+
+    fn index(&self, index: usize) -> &AsciiChar {
+        let byte: &u8 = &self.0[index];
+        // AsciiCharはrepr(transparent)で、AsciiStrの要素は全てASCIIなので読み替えてよい
+        unsafe { &*(byte as *const u8 as *const AsciiChar) }
+    }
+}
+
+// ASCIIテキストのどこで区切ってもASCIIテキストのままなので部分列をそのまま借用できる
+macro_rules! impl_index_range_for_ascii_str {
+    ($($range:ty),*) => {
+        $(
+            impl core::ops::Index<$range> for AsciiStr {
+                type Output = AsciiStr;
+
+                fn index(&self, range: $range) -> &AsciiStr {
+                    unsafe { AsciiStr::from_bytes_unchecked(&self.0[range]) }
+                }
+            }
+        )*
+    }
+}
+
+impl_index_range_for_ascii_str!(
+    core::ops::Range<usize>,
+    core::ops::RangeFrom<usize>,
+    core::ops::RangeTo<usize>,
+    core::ops::RangeInclusive<usize>,
+    core::ops::RangeToInclusive<usize>,
+    core::ops::RangeFull
+);
+
+impl core::ops::Deref for Ascii {
+    type Target = AsciiStr;
+
+    fn deref(&self) -> &AsciiStr {
+        unsafe { AsciiStr::from_bytes_unchecked(&self.0) }
+    }
+}
+
+// ハッシュ値は同じ内容のstrと必ず一致させる
+// [u8]のハッシュは長さも書き込むのでstrとは一致しない。strのHash実装にそのまま委ねる
+impl core::hash::Hash for AsciiStr {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl core::hash::Hash for Ascii {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+// 比較・ハッシュは同じ内容のstrと一致するのでHashMap<Ascii, V>を&strで引ける
+impl core::borrow::Borrow<str> for Ascii {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+// String/strと同じくCow<AsciiStr>で必要になるまでメモリ確保を遅らせられる
+// 比較・ハッシュはAsciiとAsciiStrで一致するのでHashMap<Ascii, V>を&AsciiStrで引ける
+impl core::borrow::Borrow<AsciiStr> for Ascii {
+    fn borrow(&self) -> &AsciiStr {
+        self
+    }
+}
+
+impl ToOwned for AsciiStr {
+    type Owned = Ascii;
+
+    fn to_owned(&self) -> Ascii {
+        Ascii(self.0.to_vec())
+    }
+}
+
+// Stringと同じ演算子でASCIIテキストを組み立てられるようにする
+impl<'a> core::ops::Add<&'a AsciiStr> for Ascii {
+    type Output = Ascii;
+
+    fn add(mut self, other: &'a AsciiStr) -> Ascii {
+        self.push_str(other);
+        self
+    }
+}
+
+impl<'a> core::ops::AddAssign<&'a AsciiStr> for Ascii {
+    fn add_assign(&mut self, other: &'a AsciiStr) {
+        self.push_str(other);
+    }
+}
+
+impl Extend<AsciiChar> for Ascii {
+    fn extend<I: IntoIterator<Item=AsciiChar>>(&mut self, iter: I) {
+        self.0.extend(iter.into_iter().map(AsciiChar::as_byte));
+    }
+}
+
+impl<'a> Extend<&'a AsciiStr> for Ascii {
+    fn extend<I: IntoIterator<Item=&'a AsciiStr>>(&mut self, iter: I) {
+        for s in iter {
+            self.push_str(s);
+        }
+    }
+}
+
+impl core::iter::FromIterator<AsciiChar> for Ascii {
+    fn from_iter<I: IntoIterator<Item=AsciiChar>>(iter: I) -> Ascii {
+        let mut ascii = Ascii(Vec::new());
+        ascii.extend(iter);
+        ascii
+    }
+}
+
+// ソケットやファイルから少しずつ届く入力を、届いた分から順に検証する
+// 非ASCIIのバイトが来た時点で失敗するので、全体を読み終えるまで待つ必要がない
+#[derive(Default)]
+pub struct AsciiValidator {
+    // ここまでに検証済みのバイト列
+    validated: Vec<u8>
+}
+
+impl AsciiValidator {
+    pub fn new() -> AsciiValidator {
+        AsciiValidator::default()
+    }
+
+    // これまでに検証したバイト数
+    pub fn len(&self) -> usize {
+        self.validated.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.validated.is_empty()
+    }
+
+    // 引数chunkを検証して、ASCIIであれば検証済みのバイト列に追加する
+    // 失敗した時はそれまでの入力全体とその中での位置を持ったNotAsciiErrorを返し、状態は空に戻る
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), NotAsciiError> {
+        if !is_ascii_fast(chunk) {
+            let valid_up_to = self.validated.len() + chunk.iter()
+                .position(|&byte| byte >= 0x80)
+                .unwrap_or(chunk.len());
+            let mut bytes = core::mem::take(&mut self.validated);
+            bytes.extend_from_slice(chunk);
+            return Err(NotAsciiError::with_valid_up_to(bytes, valid_up_to));
+        }
+        self.validated.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    // 検証済みのバイト列からAsciiを作る
+    pub fn finish(self) -> Ascii {
+        Ascii(self.validated)
+    }
+}
+
+// Iterator<Item = u8>を包み、ASCIIのバイトが続く間だけAsciiCharを返すアダプタ
+// 非ASCIIのバイトに出会ったら終わり、その位置とバイトをinvalid()で返す
+pub struct AsciiChars<I> {
+    iter: I,
+    position: usize,
+    invalid: Option<u8>
+}
+
+impl<I: Iterator<Item=u8>> AsciiChars<I> {
+    pub fn new<T: IntoIterator<IntoIter=I>>(iter: T) -> AsciiChars<I> {
+        AsciiChars {
+            iter: iter.into_iter(),
+            position: 0,
+            invalid: None
+        }
+    }
+
+    // 最初に見つかった非ASCIIバイトの位置とその値
+    pub fn invalid(&self) -> Option<(usize, u8)> {
+        self.invalid.map(|byte| (self.position, byte))
+    }
+}
+
+impl<I: Iterator<Item=u8>> Iterator for AsciiChars<I> {
+    type Item = AsciiChar;
+
+    fn next(&mut self) -> Option<AsciiChar> {
+        if self.invalid.is_some() {
+            return None;
+        }
+        let byte = self.iter.next()?;
+        match AsciiChar::new(byte) {
+            Some(ch) => {
+                self.position += 1;
+                Some(ch)
+            }
+            None => {
+                self.invalid = Some(byte);
+                None
+            }
+        }
+    }
+}
+
+// str::escape_defaultにならったAsciiStr::escape_defaultの戻り値
+pub struct EscapeDefault<'a> {
+    bytes: core::slice::Iter<'a, u8>,
+    // エスケープ途中のバイト
+    current: Option<core::ascii::EscapeDefault>
+}
+
+impl<'a> Iterator for EscapeDefault<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        loop {
+            if let Some(escaped) = self.current.as_mut().and_then(|e| e.next()) {
+                return Some(escaped as char);
+            }
+            self.current = Some(core::ascii::escape_default(*self.bytes.next()?));
+        }
+    }
+}
+
+impl<'a> core::fmt::Display for EscapeDefault<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        // エスケープ途中の残りから書き出す
+        for escaped in self.current.clone().into_iter().flatten() {
+            write!(f, "{}", escaped as char)?;
+        }
+        for byte in self.bytes.clone() {
+            write!(f, "{}", core::ascii::escape_default(*byte))?;
+        }
+        Ok(())
+    }
+}
+
+// 0 - 0x7f までの1バイトだけを保持するASCII文字
+// u8と同じメモリ表現を持つので&u8を&AsciiCharとして読み替えられる
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct AsciiChar(u8);
+
+impl AsciiChar {
+    // 引数byteがASCIIでなければNoneを返す
+    pub fn new(byte: u8) -> Option<AsciiChar> {
+        if byte.is_ascii() {
+            Some(AsciiChar(byte))
+        } else {
+            None
+        }
+    }
+
+    pub fn as_byte(self) -> u8 {
+        self.0
+    }
+
+    pub fn as_char(self) -> char {
+        self.0 as char
+    }
+}
+
+// 文字列リテラルとStringへ変換せずに直接比較できるようにする
+impl PartialEq<str> for Ascii {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl<'a> PartialEq<&'a str> for Ascii {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+// 1バイトずつ最上位ビットを検査する素朴なASCII判定
+pub fn is_ascii_scalar(bytes: &[u8]) -> bool {
+    bytes.iter().all(|&byte| byte < 0x80)
+}
+
+// u64単位で8バイトずつまとめて最上位ビットを検査するASCII判定
+pub fn is_ascii_word(bytes: &[u8]) -> bool {
+    const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+    // u64として読めるのはアラインメントが揃った中央部分だけなので前後の半端は1バイトずつ検査する
+    // u64はどんなビットパターンでも有効な値なのでalign_toによる読み替えは安全
+    let (head, words, tail) = unsafe { bytes.align_to::<u64>() };
+    is_ascii_scalar(head)
+        && words.chunks(4).all(|chunk| chunk.iter().fold(0, |acc, &word| acc | word) & HIGH_BITS == 0)
+        && is_ascii_scalar(tail)
+}
+
+// SSE2で16バイトずつ最上位ビットを集めて検査するASCII判定
+// x86_64ではSSE2が必ず使えるので実行時の機能検出は不要
+#[cfg(target_arch = "x86_64")]
+pub fn is_ascii_sse2(bytes: &[u8]) -> bool {
+    use core::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_movemask_epi8};
+
+    let mut chunks = bytes.chunks_exact(16);
+    for chunk in &mut chunks {
+        // chunkは16バイトあるのでアラインメントを問わないロードで読み出せる
+        // 各バイトの最上位ビットを16ビットのマスクに集める
+        let mask = unsafe {
+            _mm_movemask_epi8(_mm_loadu_si128(chunk.as_ptr() as *const __m128i))
+        };
+        if mask != 0 {
+            return false;
+        }
+    }
+    is_ascii_word(chunks.remainder())
+}
+
+// 使える中で最も速いASCII判定を選ぶ
+pub fn is_ascii_fast(bytes: &[u8]) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_ascii_sse2(bytes)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        is_ascii_word(bytes)
+    }
+}
+
+// std::string::FromUtf8Errorにならい、元のバイト列と最初の非ASCIIバイトの位置を保持する
+#[derive(Debug, Eq, PartialEq)]
+pub struct NotAsciiError {
+    bytes: Vec<u8>,
+    valid_up_to: usize
+}
+
+impl NotAsciiError {
+    // 検証に失敗したバイト列から最初の非ASCIIバイトの位置を探してエラーを作る
+    fn new(bytes: Vec<u8>) -> NotAsciiError {
+        let valid_up_to = bytes.iter()
+            .position(|&byte| byte >= 0x80)
+            .unwrap_or(bytes.len());
+        NotAsciiError { bytes, valid_up_to }
+    }
+
+    // 最初の不正なバイトの位置が分かっている時のコンストラクタ
+    fn with_valid_up_to(bytes: Vec<u8>, valid_up_to: usize) -> NotAsciiError {
+        NotAsciiError { bytes, valid_up_to }
+    }
+
+    // 先頭からこの位置の手前まではASCIIであることが保証される
+    pub fn valid_up_to(&self) -> usize {
+        self.valid_up_to
+    }
+
+    // 最初に見つかった非ASCIIバイトを返す
+    pub fn invalid_byte(&self) -> u8 {
+        self.bytes[self.valid_up_to]
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    // 検証に失敗したバイト列をコピーせずに取り戻す
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl core::fmt::Display for NotAsciiError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "invalid ASCII byte 0x{:02x} at index {}",
+               self.invalid_byte(), self.valid_up_to)
+    }
+}
+
+// std::error::Errorはstdにしか無いのでstdフィーチャーが有効な時だけ実装する
+#[cfg(feature = "std")]
+impl std::error::Error for NotAsciiError {}
+
+impl From<Ascii> for String {
+    fn from(ascii: Ascii) -> String {
+        unsafe {
+            // unsafeだが安全で効率的な変換
+            // well-formedなASCIIテキストはwell-formedなUTF8テキストであるのは自明なため
+            String::from_utf8_unchecked(ascii.0)
+        }
+    }
+}
+
+// ASCIIを共通の部分集合として持つレガシーなエンコーディングとの変換
+pub mod encodings {
+    use alloc::vec::Vec;
+    use super::{Ascii, AsciiStr};
+
+    // CP037のバイト列の復号に失敗した理由
+    // 入力はEBCDICなので、ASCIIの検証に失敗したことを表すNotAsciiErrorとは別の型にする
+    #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+    pub struct Cp037Error {
+        // ASCIIに対応する文字が無いバイトの位置とその値
+        pub index: usize,
+        pub byte: u8
+    }
+
+    impl core::fmt::Display for Cp037Error {
+        fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            write!(f, "CP037 byte 0x{:02x} at index {} has no ASCII equivalent", self.byte, self.index)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl std::error::Error for Cp037Error {}
+
+    // Latin-1(ISO-8859-1)の0x80以上の文字を'?'に置き換えてAsciiを作る
+    pub fn from_latin1_lossy(latin1: &[u8]) -> Ascii {
+        AsciiStr::from_bytes_lossy(latin1).into_owned()
+    }
+
+    // Latin-1の0x00 - 0x7fはASCIIと同じなのでバイト列はそのまま使える
+    pub fn to_latin1(ascii: &AsciiStr) -> Vec<u8> {
+        ascii.as_bytes().to_vec()
+    }
+
+    // ASCIIのバイト値を添字としてCP037(EBCDIC)のバイト値を引く表
+    const ASCII_TO_CP037: [u8; 128] = [
+        0x00, 0x01, 0x02, 0x03, 0x37, 0x2d, 0x2e, 0x2f, 0x16, 0x05, 0x25, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+        0x10, 0x11, 0x12, 0x13, 0x3c, 0x3d, 0x32, 0x26, 0x18, 0x19, 0x3f, 0x27, 0x1c, 0x1d, 0x1e, 0x1f,
+        0x40, 0x5a, 0x7f, 0x7b, 0x5b, 0x6c, 0x50, 0x7d, 0x4d, 0x5d, 0x5c, 0x4e, 0x6b, 0x60, 0x4b, 0x61,
+        0xf0, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0x7a, 0x5e, 0x4c, 0x7e, 0x6e, 0x6f,
+        0x7c, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xd1, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6,
+        0xd7, 0xd8, 0xd9, 0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xba, 0xe0, 0xbb, 0xb0, 0x6d,
+        0x79, 0x81, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x91, 0x92, 0x93, 0x94, 0x95, 0x96,
+        0x97, 0x98, 0x99, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xc0, 0x4f, 0xd0, 0xa1, 0x07,
+    ];
+
+    // ASCII_TO_CP037を逆引きした表をコンパイル時に作る
+    // ASCIIに対応する文字が無いバイトには0xffを入れておく
+    const CP037_TO_ASCII: [u8; 256] = {
+        let mut table = [0xff; 256];
+        let mut ascii = 0;
+        while ascii < ASCII_TO_CP037.len() {
+            table[ASCII_TO_CP037[ascii] as usize] = ascii as u8;
+            ascii += 1;
+        }
+        table
+    };
+
+    // ASCIIテキストをCP037に変換する
+    // ASCIIの文字は全てCP037に対応する文字があるので失敗しない
+    pub fn encode_cp037(ascii: &AsciiStr) -> Vec<u8> {
+        ascii.as_bytes().iter().map(|&byte| ASCII_TO_CP037[byte as usize]).collect()
+    }
+
+    // CP037のバイト列をASCIIテキストに変換する
+    // ASCIIに対応する文字が無いバイトがあれば、その位置と値を持ったCp037Errorを返す
+    pub fn decode_cp037(ebcdic: &[u8]) -> Result<Ascii, Cp037Error> {
+        let mut ascii = Vec::with_capacity(ebcdic.len());
+        for (index, &byte) in ebcdic.iter().enumerate() {
+            match CP037_TO_ASCII[byte as usize] {
+                0xff => return Err(Cp037Error { index, byte }),
+                decoded => ascii.push(decoded)
+            }
+        }
+        // 表にはASCIIの値しか入っていない
+        Ok(Ascii(ascii))
+    }
+}
+
+// 比較・順序・ハッシュで大文字と小文字を区別しないAscii
+// HTTPのヘッダー名のようなトークンを、小文字に変換したコピーを作らずにそのままマップのキーにできる
+#[derive(Clone)]
+pub struct AsciiCaseless(Ascii);
+
+impl AsciiCaseless {
+    pub fn new(ascii: Ascii) -> AsciiCaseless {
+        AsciiCaseless(ascii)
+    }
+
+    // 元の大文字・小文字のまま取り出す
+    pub fn into_inner(self) -> Ascii {
+        self.0
+    }
+
+    fn folded(&self) -> impl Iterator<Item=u8> + '_ {
+        self.0.as_bytes().iter().map(u8::to_ascii_lowercase)
+    }
+}
+
+impl core::ops::Deref for AsciiCaseless {
+    type Target = AsciiStr;
+
+    fn deref(&self) -> &AsciiStr {
+        &self.0
+    }
+}
+
+impl core::fmt::Debug for AsciiCaseless {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        core::fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl PartialEq for AsciiCaseless {
+    fn eq(&self, other: &AsciiCaseless) -> bool {
+        self.0.eq_ignore_ascii_case(&other.0)
+    }
+}
+
+impl Eq for AsciiCaseless {}
+
+// 小文字に揃えたバイト列同士の辞書順で比べる
+impl Ord for AsciiCaseless {
+    fn cmp(&self, other: &AsciiCaseless) -> core::cmp::Ordering {
+        self.folded().cmp(other.folded())
+    }
+}
+
+impl PartialOrd for AsciiCaseless {
+    fn partial_cmp(&self, other: &AsciiCaseless) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// 等しいと判定される値のハッシュ値が一致するよう、小文字に揃えてから書き込む
+impl core::hash::Hash for AsciiCaseless {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        for byte in self.folded() {
+            state.write_u8(byte);
+        }
+        // strと同じく終端を書き込んで、連続したキーの区切りを曖昧にしない
+        state.write_u8(0xff);
+    }
+}
+
+// ヒープを使わずに最大Nバイトまでを構造体の中に持つASCIIテキスト
+// 使っていない領域は初期化しないままにしておく
+#[derive(Clone, Copy)]
+pub struct AsciiArray<const N: usize> {
+    // 先頭len個だけが初期化済みで、全てASCII
+    bytes: [core::mem::MaybeUninit<u8>; N],
+    len: usize
+}
+
+// 容量を超える書き込みをしようとした
+#[derive(Debug, Eq, PartialEq)]
+pub struct CapacityError;
+
+impl core::fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str("insufficient capacity")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CapacityError {}
+
+impl<const N: usize> AsciiArray<N> {
+    pub const fn new() -> AsciiArray<N> {
+        AsciiArray {
+            // MaybeUninitの配列は初期化しなくてよい
+            bytes: [core::mem::MaybeUninit::uninit(); N],
+            len: 0
+        }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    // 末尾にASCII文字を1つ追加する
+    // 容量が足りなければ何もせずにCapacityErrorを返す
+    pub fn try_push(&mut self, ch: AsciiChar) -> Result<(), CapacityError> {
+        if self.len == N {
+            return Err(CapacityError);
+        }
+        self.bytes[self.len].write(ch.as_byte());
+        self.len += 1;
+        Ok(())
+    }
+
+    // 末尾にASCIIテキストを追加する
+    // 容量が足りなければ何もせずにCapacityErrorを返す
+    pub fn try_push_str(&mut self, s: &AsciiStr) -> Result<(), CapacityError> {
+        if s.len() > N - self.len {
+            return Err(CapacityError);
+        }
+        unsafe {
+            // 書き込み先は未初期化の領域で、sとは重ならない
+            core::ptr::copy_nonoverlapping(s.as_bytes().as_ptr(),
+                                           self.bytes.as_mut_ptr().add(self.len) as *mut u8,
+                                           s.len());
+        }
+        self.len += s.len();
+        Ok(())
+    }
+
+    pub fn to_ascii(self) -> Ascii {
+        (*self).to_owned()
+    }
+}
+
+impl<const N: usize> Default for AsciiArray<N> {
+    fn default() -> AsciiArray<N> {
+        AsciiArray::new()
+    }
+}
+
+impl<const N: usize> core::ops::Deref for AsciiArray<N> {
+    type Target = AsciiStr;
+
+    fn deref(&self) -> &AsciiStr {
+        unsafe {
+            // 先頭len個は初期化済みのASCII
+            let bytes = core::slice::from_raw_parts(self.bytes.as_ptr() as *const u8, self.len);
+            AsciiStr::from_bytes_unchecked(bytes)
+        }
+    }
+}
+
+impl<const N: usize> core::fmt::Debug for AsciiArray<N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        core::fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, const N: usize> core::convert::TryFrom<&'a AsciiStr> for AsciiArray<N> {
+    type Error = CapacityError;
+
+    fn try_from(s: &'a AsciiStr) -> Result<AsciiArray<N>, CapacityError> {
+        let mut array = AsciiArray::new();
+        array.try_push_str(s)?;
+        Ok(array)
+    }
+}
+
+impl<const N: usize> From<AsciiArray<N>> for Ascii {
+    fn from(array: AsciiArray<N>) -> Ascii {
+        array.to_ascii()
+    }
+}
+
+// C言語の文字列との相互変換
+// ASCIIでNULを含まないテキストなら、そのままNUL終端してFFIに渡せる
+impl Ascii {
+    // 途中にNULがあればNulErrorを返す
+    pub fn to_cstring(&self) -> Result<CString, NulError> {
+        CString::new(self.0.clone())
+    }
+
+    // C言語の文字列がASCIIだけで構成されていればAsciiを作る
+    pub fn from_cstr(s: &CStr) -> Result<Ascii, NotAsciiError> {
+        Ascii::from_bytes(s.to_bytes().to_vec())
+    }
+}
+
+// 途中にNULを含まないASCIIテキスト
+// 末尾にNULを1つだけ持つので、コピーせずにそのままC言語の関数に渡せる
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AsciiNulTerminated(CString);
+
+impl AsciiNulTerminated {
+    // 受け取ったバッファの末尾にNULを足して使う
+    pub fn new(ascii: Ascii) -> Result<AsciiNulTerminated, NulError> {
+        CString::new(ascii.0).map(AsciiNulTerminated)
+    }
+
+    // C言語の文字列は途中にNULを含まないので、ASCIIかどうかだけ検証する
+    pub fn from_cstr(s: &CStr) -> Result<AsciiNulTerminated, NotAsciiError> {
+        if !is_ascii_fast(s.to_bytes()) {
+            return Err(NotAsciiError::new(s.to_bytes().to_vec()));
+        }
+        Ok(AsciiNulTerminated(s.to_owned()))
+    }
+
+    pub fn as_ptr(&self) -> *const core::ffi::c_char {
+        self.0.as_ptr()
+    }
+
+    pub fn as_c_str(&self) -> &CStr {
+        &self.0
+    }
+
+    // 末尾のNULを除いたASCIIテキスト
+    pub fn as_ascii_str(&self) -> &AsciiStr {
+        // 構築時に全てASCIIであることを確かめた
+        unsafe { AsciiStr::from_bytes_unchecked(self.0.as_bytes()) }
+    }
+
+    // 末尾のNULを取り除いてAsciiに戻す
+    pub fn into_ascii(self) -> Ascii {
+        Ascii(self.0.into_bytes())
+    }
+}
+
+// ASCIIのレコードを並べたバイナリ形式
+// mmapしたファイルなどのバイト列を一度だけ検証すれば、後はコピーせずにAsciiStrとして借用できる
+//
+// 形式: レコード数(u32 LE)に続いて、各レコードの長さ(u32 LE)とASCIIのバイト列を繰り返す
+pub mod archive {
+    use alloc::vec::Vec;
+    use core::convert::{TryFrom, TryInto};
+    use super::AsciiStr;
+
+    // バイト列が形式に合わなかった位置
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum ArchiveError {
+        // 長さやレコードの途中でバイト列が終わっている
+        Truncated(usize),
+        // レコードに非ASCIIのバイトが含まれている
+        NotAscii(usize),
+        // 最後のレコードの後ろに余分なバイトがある
+        TrailingBytes(usize)
+    }
+
+    // archiveに渡したレコードが、形式のu32に収まらなかった
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum TooLargeError {
+        // この番目のレコードが4GiB以上ある
+        RecordTooLong(usize),
+        // レコードがu32::MAX個より多い
+        TooManyRecords
+    }
+
+    // レコードを並べてバイト列にする
+    // 長さや個数をu32に切り詰めると読み出す側が壊れたバイト列を読むことになるので、収まらなければエラーにする
+    pub fn archive<'a, I: IntoIterator<Item=&'a AsciiStr>>(records: I) -> Result<Vec<u8>, TooLargeError> {
+        let mut bytes = alloc::vec![0; 4];
+        let mut count: u32 = 0;
+        for (index, record) in records.into_iter().enumerate() {
+            let len = u32::try_from(record.len()).map_err(|_| TooLargeError::RecordTooLong(index))?;
+            count = count.checked_add(1).ok_or(TooLargeError::TooManyRecords)?;
+            bytes.extend_from_slice(&len.to_le_bytes());
+            bytes.extend_from_slice(record.as_bytes());
+        }
+        bytes[..4].copy_from_slice(&count.to_le_bytes());
+        Ok(bytes)
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> Option<usize> {
+        let word = bytes.get(offset..offset.checked_add(4)?)?;
+        Some(u32::from_le_bytes(word.try_into().unwrap()) as usize)
+    }
+
+    // 検証済みのバイト列を借用するビュー
+    #[derive(Clone, Copy, Debug)]
+    pub struct ArchivedAscii<'a> {
+        // 先頭のレコード数を除いたレコードの並び
+        records: &'a [u8],
+        len: usize
+    }
+
+    impl<'a> ArchivedAscii<'a> {
+        // バイト列全体を一度だけ検証する
+        pub fn new(bytes: &'a [u8]) -> Result<ArchivedAscii<'a>, ArchiveError> {
+            let len = read_u32(bytes, 0).ok_or(ArchiveError::Truncated(0))?;
+            let mut offset = 4;
+            for _ in 0..len {
+                let record_len = read_u32(bytes, offset).ok_or(ArchiveError::Truncated(offset))?;
+                offset += 4;
+                let record = offset.checked_add(record_len)
+                    .and_then(|end| bytes.get(offset..end))
+                    .ok_or(ArchiveError::Truncated(offset))?;
+                if let Some(i) = record.iter().position(|&byte| byte >= 0x80) {
+                    return Err(ArchiveError::NotAscii(offset + i));
+                }
+                offset += record_len;
+            }
+            if offset != bytes.len() {
+                return Err(ArchiveError::TrailingBytes(offset));
+            }
+            Ok(ArchivedAscii { records: &bytes[4..], len })
+        }
+
+        pub fn len(&self) -> usize {
+            self.len
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        pub fn iter(&self) -> Iter<'a> {
+            Iter {
+                rest: self.records
+            }
+        }
+    }
+
+    impl<'a> IntoIterator for ArchivedAscii<'a> {
+        type Item = &'a AsciiStr;
+        type IntoIter = Iter<'a>;
+
+        fn into_iter(self) -> Iter<'a> {
+            self.iter()
+        }
+    }
+
+    // レコードを先頭から順に借用して返すイテレータ
+    pub struct Iter<'a> {
+        rest: &'a [u8]
+    }
+
+    impl<'a> Iterator for Iter<'a> {
+        type Item = &'a AsciiStr;
+
+        fn next(&mut self) -> Option<&'a AsciiStr> {
+            // ArchivedAscii::newで長さの並びとASCIIであることは確かめてある
+            let len = read_u32(self.rest, 0)?;
+            let (record, rest) = self.rest[4..].split_at(len);
+            self.rest = rest;
+            Some(unsafe { AsciiStr::from_bytes_unchecked(record) })
+        }
+    }
+}
+
+// 16進数とBase64の符号化
+// どちらも出力はASCIIの文字だけで構成されるので、検証を省いたAsciiのコンストラクタを安全に使える
+pub mod codec {
+    use alloc::vec::Vec;
+    use super::{Ascii, AsciiStr};
+
+    // 復号に失敗した理由
+    #[derive(Debug, Eq, PartialEq)]
+    pub enum DecodeError {
+        // 入力の長さが符号化の単位の倍数になっていない
+        InvalidLength(usize),
+        // 符号化に使われない文字が現れた位置とその文字
+        InvalidByte(usize, u8)
+    }
+
+    impl core::fmt::Display for DecodeError {
+        fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            match *self {
+                DecodeError::InvalidLength(len) => write!(f, "invalid input length {}", len),
+                DecodeError::InvalidByte(index, byte) =>
+                    write!(f, "invalid byte {:?} at index {}", byte as char, index)
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl std::error::Error for DecodeError {}
+
+    const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+    const BASE64_ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    // バイト列を小文字の16進数に符号化する
+    pub fn encode_hex(bytes: &[u8]) -> Ascii {
+        let mut hex = Vec::with_capacity(bytes.len() * 2);
+        for &byte in bytes {
+            hex.push(HEX_DIGITS[(byte >> 4) as usize]);
+            hex.push(HEX_DIGITS[(byte & 0xf) as usize]);
+        }
+        // HEX_DIGITSの要素は全てASCII
+        unsafe { Ascii::from_bytes_unchecked(hex) }
+    }
+
+    fn hex_value(index: usize, digit: u8) -> Result<u8, DecodeError> {
+        match digit {
+            b'0'..=b'9' => Ok(digit - b'0'),
+            b'a'..=b'f' => Ok(digit - b'a' + 10),
+            b'A'..=b'F' => Ok(digit - b'A' + 10),
+            _ => Err(DecodeError::InvalidByte(index, digit))
+        }
+    }
+
+    // 16進数を復号する（大文字・小文字を問わない）
+    pub fn decode_hex(hex: &AsciiStr) -> Result<Vec<u8>, DecodeError> {
+        let hex = hex.as_bytes();
+        if !hex.len().is_multiple_of(2) {
+            return Err(DecodeError::InvalidLength(hex.len()));
+        }
+        hex.chunks(2).enumerate().map(|(i, pair)| {
+            Ok(hex_value(i * 2, pair[0])? << 4 | hex_value(i * 2 + 1, pair[1])?)
+        }).collect()
+    }
+
+    // バイト列をパディング付きの標準的なBase64に符号化する
+    pub fn encode_base64(bytes: &[u8]) -> Ascii {
+        let mut encoded = Vec::with_capacity(bytes.len().div_ceil(3) * 4);
+        for chunk in bytes.chunks(3) {
+            let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+            let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+            // 3バイトを6ビットずつ4文字に分け、足りない分は'='で埋める
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    encoded.push(BASE64_ALPHABET[(n >> (18 - 6 * i)) & 0x3f]);
+                } else {
+                    encoded.push(b'=');
+                }
+            }
+        }
+        // BASE64_ALPHABETの要素と'='は全てASCII
+        unsafe { Ascii::from_bytes_unchecked(encoded) }
+    }
+
+    fn base64_value(index: usize, byte: u8) -> Result<u32, DecodeError> {
+        BASE64_ALPHABET.iter()
+            .position(|&c| c == byte)
+            .map(|value| value as u32)
+            .ok_or(DecodeError::InvalidByte(index, byte))
+    }
+
+    // パディング付きの標準的なBase64を復号する
+    pub fn decode_base64(encoded: &AsciiStr) -> Result<Vec<u8>, DecodeError> {
+        let encoded = encoded.as_bytes();
+        if !encoded.len().is_multiple_of(4) {
+            return Err(DecodeError::InvalidLength(encoded.len()));
+        }
+        let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+        let last = encoded.len() / 4;
+        for (i, chunk) in encoded.chunks(4).enumerate() {
+            // '='は最後の4文字の末尾2文字までにしか現れない
+            let padding = if i + 1 == last {
+                chunk.iter().rev().take(2).take_while(|&&c| c == b'=').count()
+            } else {
+                0
+            };
+            let mut n = 0;
+            for (j, &c) in chunk[..4 - padding].iter().enumerate() {
+                n |= base64_value(i * 4 + j, c)? << (18 - 6 * j);
+            }
+            let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+            decoded.extend_from_slice(&bytes[..3 - padding]);
+        }
+        Ok(decoded)
+    }
+}
+
+// プロセスの境界でASCIIであることを強制するstd::ioのラッパー
+// std::ioはstdにしか無いのでstdフィーチャーが有効な時だけ使える
+#[cfg(feature = "std")]
+pub mod io {
+    use std::io::{self, Read, Write};
+    use super::{AsciiChar, AsciiStr, NotAsciiError};
+
+    // 読み出したバイト列を検証するReader
+    // 非ASCIIのバイトはエラーにするか、指定した文字に置き換える
+    pub struct AsciiReader<R> {
+        inner: R,
+        replacement: Option<AsciiChar>
+    }
+
+    impl<R: Read> AsciiReader<R> {
+        // 非ASCIIのバイトを読んだらErrorKind::InvalidDataのエラーを返すReaderを作る
+        // エラーを返した後の読み出し位置は不定になる
+        pub fn new(inner: R) -> AsciiReader<R> {
+            AsciiReader { inner, replacement: None }
+        }
+
+        // 非ASCIIのバイトを引数replacementに置き換えるReaderを作る
+        pub fn lossy(inner: R, replacement: AsciiChar) -> AsciiReader<R> {
+            AsciiReader { inner, replacement: Some(replacement) }
+        }
+
+        pub fn into_inner(self) -> R {
+            self.inner
+        }
+    }
+
+    impl<R: Read> Read for AsciiReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            let chunk = &mut buf[..n];
+            if let Some(valid_up_to) = chunk.iter().position(|&byte| byte >= 0x80) {
+                match self.replacement {
+                    Some(replacement) => {
+                        for byte in chunk[valid_up_to..].iter_mut().filter(|byte| !byte.is_ascii()) {
+                            *byte = replacement.as_byte();
+                        }
+                    }
+                    None => {
+                        let error = NotAsciiError::with_valid_up_to(chunk.to_vec(), valid_up_to);
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+                    }
+                }
+            }
+            Ok(n)
+        }
+    }
+
+    // ASCIIのバイトしか書き込ませないWriter
+    pub struct AsciiWriter<W> {
+        inner: W
+    }
+
+    impl<W: Write> AsciiWriter<W> {
+        pub fn new(inner: W) -> AsciiWriter<W> {
+            AsciiWriter { inner }
+        }
+
+        // 検証済みのテキストはそのまま書き込む
+        pub fn write_ascii(&mut self, s: &AsciiStr) -> io::Result<()> {
+            self.inner.write_all(s.as_bytes())
+        }
+
+        pub fn into_inner(self) -> W {
+            self.inner
+        }
+    }
+
+    impl<W: Write> Write for AsciiWriter<W> {
+        // 途中に非ASCIIのバイトがあればその手前までだけ書き込む
+        // 先頭が非ASCIIのバイトならErrorKind::InvalidDataのエラーを返す
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match buf.iter().position(|&byte| byte >= 0x80) {
+                Some(0) => {
+                    let error = NotAsciiError::with_valid_up_to(buf.to_vec(), 0);
+                    Err(io::Error::new(io::ErrorKind::InvalidData, error))
+                }
+                Some(valid_up_to) => self.inner.write(&buf[..valid_up_to]),
+                None => self.inner.write(buf)
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+}
+
+// Asciiを受け取るパーサーをファジングやプロパティテストにかけるための入力生成器
+// testingフィーチャーが有効な時だけ使える
+#[cfg(feature = "testing")]
+pub mod testing {
+    use alloc::vec::Vec;
+    use arbitrary::{Arbitrary, Unstructured};
+    use proptest::prelude::*;
+    use proptest::sample::Index;
+    use super::Ascii;
+
+    // 任意のバイト列の最上位ビットを落としてASCIIにする
+    impl<'a> Arbitrary<'a> for Ascii {
+        fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Ascii> {
+            let mut bytes = Vec::<u8>::arbitrary(u)?;
+            for byte in bytes.iter_mut() {
+                *byte &= 0x7f;
+            }
+            Ok(Ascii(bytes))
+        }
+
+        fn size_hint(depth: usize) -> (usize, Option<usize>) {
+            Vec::<u8>::size_hint(depth)
+        }
+    }
+
+    // 正しいASCIIテキスト
+    pub fn ascii() -> impl Strategy<Value = Ascii> {
+        proptest::collection::vec(0u8..0x80, 0..64).prop_map(Ascii)
+    }
+
+    // ASCIIテキストのどこか1箇所にだけ0x80以上のバイトを差し込んだバイト列
+    pub fn almost_ascii() -> impl Strategy<Value = Vec<u8>> {
+        (proptest::collection::vec(0u8..0x80, 0..64), any::<Index>(), 0x80u8..=0xff)
+            .prop_map(|(mut bytes, index, invalid)| {
+                let at = index.index(bytes.len() + 1);
+                bytes.insert(at, invalid);
+                bytes
+            })
+    }
+
+    // ASCIIと複数バイトの文字が混ざった、UTF8としては正しいバイト列
+    // 非ASCIIの文字を必ず1つは含む
+    pub fn multibyte_utf8() -> impl Strategy<Value = Vec<u8>> {
+        let ch = prop_oneof![
+            proptest::char::range('\0', '\x7f'),
+            proptest::char::range('\u{80}', char::MAX)
+        ];
+        (proptest::collection::vec(ch, 0..32), proptest::char::range('\u{80}', char::MAX), any::<Index>())
+            .prop_map(|(mut chars, multibyte, index)| {
+                let at = index.index(chars.len() + 1);
+                chars.insert(at, multibyte);
+                chars.into_iter().collect::<alloc::string::String>().into_bytes()
+            })
+    }
+}
+
+// serdeフィーチャーが有効な時だけシリアライズ・デシリアライズを実装する
+// シリアライズは文字列として書き出し、デシリアライズ時にASCIIかを検証する
+#[cfg(feature = "serde")]
+mod ascii_serde {
+    use core::fmt;
+    use serde::de::{self, Deserialize, Deserializer, Unexpected, Visitor};
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use serde::ser::{Serialize, Serializer};
+    use super::Ascii;
+
+    impl Serialize for Ascii {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(self.as_str())
+        }
+    }
+
+    struct AsciiVisitor;
+
+    impl<'de> Visitor<'de> for AsciiVisitor {
+        type Value = Ascii;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an ASCII string")
+        }
+
+        // 文字列として渡された入力は、エラーでも文字列として報告する
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Ascii, E> {
+            if !v.is_ascii() {
+                return Err(E::invalid_value(Unexpected::Str(v), &self));
+            }
+            Ok(Ascii(v.as_bytes().to_vec()))
+        }
+
+        fn visit_string<E: de::Error>(self, v: String) -> Result<Ascii, E> {
+            if !v.is_ascii() {
+                return Err(E::invalid_value(Unexpected::Str(&v), &self));
+            }
+            Ok(Ascii(v.into_bytes()))
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Ascii, E> {
+            if !v.is_ascii() {
+                return Err(E::invalid_value(Unexpected::Bytes(v), &self));
+            }
+            Ok(Ascii(v.to_vec()))
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Ascii, E> {
+            Ascii::from_bytes(v)
+                .map_err(|e| E::invalid_value(Unexpected::Bytes(e.as_bytes()), &self))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Ascii {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Ascii, D::Error> {
+            deserializer.deserialize_string(AsciiVisitor)
+        }
+    }
+}
@@ -0,0 +1,2327 @@
+// coreとallocだけに依存するので、stdフィーチャーを無効にしたno_std環境でも使える
+// ビルドできることはno_std_check/で組み込み向けのターゲットに対して確かめる
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::ops::Range;
+use core::ptr::NonNull;
+// Allocatorトレイトはまだstableではないので、同じAPIをstableで使えるallocator-api2を使う
+// Globalはstd::alloc::allocで確保するので、その領域はstdのVecとやり取りできる
+use allocator_api2::alloc::{Allocator, Global};
+use allocator_api2::collections::{TryReserveError, TryReserveErrorKind};
+
+// GapBufferの格納場所となる、初期化されていないかもしれないTの並び
+// 確保と解放、確保し直す時の移し替えだけを受け持ち、どこに要素があるかは知らない
+// 要素をドロップすることもないので、初期化済みの要素の管理は持ち主（GapBuffer）の責任になる
+// unsafeな確保と解放はこの型に集め、GapBufferはこの型が返すポインタの範囲だけを読み書きする
+pub struct RawGapBuf<T, A: Allocator = Global> {
+    // 格納場所の先頭
+    // allocから確保した領域で、どの要素が初期化済みかは型に表れないのでMaybeUninitで持つ
+    ptr: NonNull<core::mem::MaybeUninit<T>>,
+    // 格納場所の要素数。ゼロサイズの型ではVecと同じくusize::MAXになる
+    cap: usize,
+    // ptrを確保したアロケータ。解放も同じアロケータで行う
+    alloc: A
+}
+
+impl<T, A: Allocator> RawGapBuf<T, A> {
+    // allocから何も確保しない空の格納場所を作る
+    pub fn new_in(alloc: A) -> RawGapBuf<T, A> {
+        // 容量ゼロの確保は失敗しない
+        RawGapBuf::try_with_capacity_in(0, alloc).unwrap()
+    }
+
+    // allocから要素capacity個分の格納場所を確保する
+    // 容量の計算があふれればpanicを起こし、確保に失敗すればhandle_alloc_errorで中断する
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> RawGapBuf<T, A> {
+        RawGapBuf::try_with_capacity_in(capacity, alloc).unwrap_or_else(|e| handle_reserve_error(e))
+    }
+
+    // with_capacity_inの、確保に失敗した時にエラーを返す版
+    pub fn try_with_capacity_in(capacity: usize, alloc: A) -> Result<RawGapBuf<T, A>, TryReserveError> {
+        let (ptr, cap) = RawGapBuf::<T, A>::allocate(capacity, &alloc)?;
+        Ok(RawGapBuf { ptr, cap, alloc })
+    }
+
+    // allocから要素capacity個分の領域を確保し、先頭へのポインタと容量を返す
+    // ゼロサイズの型と容量ゼロでは確保せず、アラインされたダングリングポインタを返す
+    fn allocate(capacity: usize, alloc: &A) -> Result<(NonNull<core::mem::MaybeUninit<T>>, usize), TryReserveError> {
+        if core::mem::size_of::<T>() == 0 {
+            return Ok((NonNull::dangling(), usize::MAX));
+        }
+        if capacity == 0 {
+            return Ok((NonNull::dangling(), 0));
+        }
+        // Layout::arrayはバイト数がisize::MAXを超える時もエラーにする
+        let layout = Layout::array::<T>(capacity).map_err(|_| capacity_overflow())?;
+        match alloc.allocate(layout) {
+            Ok(ptr) => Ok((ptr.cast(), capacity)),
+            Err(_) => Err(TryReserveErrorKind::AllocError { layout, non_exhaustive: () }.into())
+        }
+    }
+
+    // allocateで確保した領域を解放する。中身は何もドロップしない
+    unsafe fn deallocate(ptr: NonNull<core::mem::MaybeUninit<T>>, capacity: usize, alloc: &A) {
+        if core::mem::size_of::<T>() != 0 && capacity != 0 {
+            alloc.deallocate(ptr.cast(), Layout::array::<T>(capacity).unwrap());
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    // 格納場所の先頭へのポインタを返す
+    // 参照を経由せずptrのコピーから作るので、何度呼んでも前に返したポインタが無効になることはない
+    // 読み書きしてよいのは0..capacityの範囲だけで、読むのは持ち主が初期化した要素だけにしなければならない
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr.as_ptr().cast()
+    }
+
+    // 容量new_capacityの領域を確保し、relocateに古い領域の先頭、新しい領域の先頭、新しい容量を渡して
+    // 中身を移させてから、古い領域を解放する
+    // 確保に失敗した時はrelocateを呼ばず、何も変えずにエラーを返す
+    //
+    // relocateは古い領域の初期化済みの要素を、新しい容量の範囲に収まるようにコピーしなければならない
+    // 古い領域は中身をドロップせずに解放するので、移さなかった要素はリークする
+    pub unsafe fn try_relocate<F>(&mut self, new_capacity: usize, relocate: F) -> Result<(), TryReserveError>
+        where F: FnOnce(*const T, *mut T, usize)
+    {
+        let (new, new_cap) = RawGapBuf::<T, A>::allocate(new_capacity, &self.alloc)?;
+        relocate(self.as_ptr(), new.as_ptr().cast(), new_cap);
+        RawGapBuf::<T, A>::deallocate(self.ptr, self.cap, &self.alloc);
+        self.ptr = new;
+        self.cap = new_cap;
+        Ok(())
+    }
+
+    // 領域を解放せずに、先頭へのポインタと容量、アロケータに分解する
+    pub fn into_raw_parts(self) -> (NonNull<T>, usize, A) {
+        let raw = core::mem::ManuallyDrop::new(self);
+        // rawのDropは呼ばれないので、allocを読み出して持ち主を移してよい
+        let alloc = unsafe { core::ptr::read(&raw.alloc) };
+        (raw.ptr.cast(), raw.cap, alloc)
+    }
+
+    // into_raw_partsで分解した値から作り直す
+    // ptrとcapacityはallocから確保したものか、ゼロサイズの型や容量ゼロならダングリングポインタでなければならない
+    pub unsafe fn from_raw_parts_in(ptr: NonNull<T>, capacity: usize, alloc: A) -> RawGapBuf<T, A> {
+        RawGapBuf { ptr: ptr.cast(), cap: capacity, alloc }
+    }
+}
+
+// 領域を解放するだけで、中身はドロップしない
+impl<T, A: Allocator> Drop for RawGapBuf<T, A> {
+    fn drop(&mut self) {
+        unsafe {
+            RawGapBuf::<T, A>::deallocate(self.ptr, self.cap, &self.alloc);
+        }
+    }
+}
+
+// 初期化されていない領域とアロケータを持つだけなので、Vec<T>と同じ境界でSendやSyncになる
+unsafe impl<T: Send, A: Allocator + Send> Send for RawGapBuf<T, A> {}
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for RawGapBuf<T, A> {}
+
+// charの値を予備領域と一緒に保持する型
+//
+// 要素へのポインタは全てraw.as_ptr()から作り、確保した領域全体を指す&[T]や&mut [T]は決して作らない
+// ギャップの中は初期化されていないので、全体への参照を作るとそれだけで未定義動作になるうえ、
+// Stacked Borrowsではその参照を作った時点でそれまでに作ったポインタが無効になってしまう
+// 参照を作るのは初期化済みでお互いに重ならない範囲（as_slicesの2つのスライスなど）だけにする
+//
+// 格納場所はアロケータAから確保する。指定しなければstdのVecと同じGlobalになる
+pub struct GapBuffer<T, A: Allocator = Global> {
+    // 格納場所
+    // 確保と解放はRawGapBufに任せ、GapBufferはgapの外側が初期化済みであることだけを管理する
+    raw: RawGapBuf<T, A>,
+
+    // raw内で初期化されていない範囲
+    gap: Range<usize>,
+
+    // set_markで付けた印と、add_cursorで置いたカーソル
+    marks: Vec<Mark>,
+
+    // RawGapBufは要素をドロップしないので、Tの値を所有していることをドロップチェッカーに教える
+    owns: PhantomData<T>
+}
+
+// set_markが返す印のID。印を付けたGapBufferとその複製の中でだけ意味を持つ
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MarkId(usize);
+
+// add_cursorが返すカーソルのID。MarkIdと同じく、カーソルを置いたGapBufferとその複製の中でだけ意味を持つ
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CursorId(usize);
+
+// カーソルは名前の無い印として持つので、印と同じく挿入・削除に合わせて位置がずれる
+#[derive(Clone)]
+struct Mark {
+    name: Option<String>,
+    at: MarkAt
+}
+
+// 印の位置の持ち方
+// LineIndexの改行と同じく、ギャップの前の印は先頭からの位置、後ろの印は末尾までの要素数で持つ
+// 挿入はいつもギャップの先頭で起きるので、どちらの持ち方も挿入では変わらず、印を1つも書き換えなくてよい
+// 挿入点と同じ位置の印はギャップの前として持つので、そこへ挿入しても印は挿入した要素の前に留まる
+#[derive(Clone, Copy)]
+enum MarkAt {
+    Front(usize),
+    Back(usize)
+}
+
+impl MarkAt {
+    // 論理的な位置posを、ギャップの先頭がgap_start、長さがlenの時の持ち方にする
+    fn encode(pos: usize, gap_start: usize, len: usize) -> MarkAt {
+        if pos <= gap_start {
+            MarkAt::Front(pos)
+        } else {
+            MarkAt::Back(len - pos)
+        }
+    }
+
+    // 長さがlenの時の論理的な位置に戻す
+    fn decode(self, len: usize) -> usize {
+        match self {
+            MarkAt::Front(pos) => pos,
+            MarkAt::Back(from_end) => len.saturating_sub(from_end)
+        }
+    }
+}
+
+// 長さlenの並びに対するrangeを、論理的な位置の範囲にする。rangeが範囲外ならpanicを起こす
+fn logical_range<R: core::ops::RangeBounds<usize>>(range: R, len: usize) -> Range<usize> {
+    use core::ops::Bound;
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len
+    };
+    if start > end || end > len {
+        panic!("range {}..{} out of range for GapBuffer of length {}", start, end, len);
+    }
+    start .. end
+}
+
+// Vecと同じく、容量の計算があふれたらpanicを起こし、確保に失敗したらhandle_alloc_errorで中断する
+fn handle_reserve_error(error: TryReserveError) -> ! {
+    match error.kind() {
+        TryReserveErrorKind::CapacityOverflow => panic!("capacity overflow"),
+        TryReserveErrorKind::AllocError { layout, .. } => alloc::alloc::handle_alloc_error(layout)
+    }
+}
+
+impl<T> GapBuffer<T> {
+    pub fn new() -> GapBuffer<T> {
+        GapBuffer::new_in(Global)
+    }
+
+    // 少なくともcapacity個の要素を再確保せずに保持できるGapBufferを作る
+    pub fn with_capacity(capacity: usize) -> GapBuffer<T> {
+        GapBuffer::with_capacity_in(capacity, Global)
+    }
+
+    // 要素を詰めてstorageの確保領域をそのままVecとして返す
+    // ギャップがすでに末尾にあれば要素のコピーは起きない
+    // Vecに渡せるのはGlobalから確保した領域だけなので、Globalの時だけ使える
+    pub fn into_vec(mut self) -> Vec<T> {
+        let len = self.len();
+        self.set_position(len);
+        // selfのDropが要素をドロップしたり領域を解放したりしないよう、空の格納場所と入れ替える
+        let raw = core::mem::replace(&mut self.raw, RawGapBuf::new_in(Global));
+        self.gap = 0..self.capacity();
+        let (ptr, cap, Global) = raw.into_raw_parts();
+        unsafe {
+            Vec::from_raw_parts(ptr.as_ptr(), len, cap)
+        }
+    }
+}
+
+impl<T, A: Allocator> GapBuffer<T, A> {
+    // allocから格納場所を確保する空のGapBufferを作る
+    pub fn new_in(alloc: A) -> GapBuffer<T, A> {
+        GapBuffer::with_capacity_in(0, alloc)
+    }
+
+    // with_capacityのアロケータを指定する版
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> GapBuffer<T, A> {
+        // ゼロサイズの型では確保しなくても容量がusize::MAXになるので、ギャップも容量に合わせる
+        let raw = RawGapBuf::with_capacity_in(capacity, alloc);
+        GapBuffer { gap: 0..raw.capacity(), raw, marks: Vec::new(), owns: PhantomData }
+    }
+
+    // 格納場所を確保したアロケータを返す
+    pub fn allocator(&self) -> &A {
+        self.raw.allocator()
+    }
+
+    // 余分な容量を手放す
+    // 要素を詰めて確保し直すので、挿入点はそのままでギャップの大きさはゼロになる
+    pub fn shrink_to_fit(&mut self) {
+        if !self.gap.is_empty() {
+            let len = self.len();
+            self.reallocate(len);
+        }
+    }
+
+    // GapBufferが再確保せず保持できる要素数を返す
+    pub fn capacity(&self) -> usize {
+        self.raw.capacity()
+    }
+
+    // 現在このGapBufferが保持している要素数を返す
+    pub fn len(&self) -> usize {
+        self.capacity() - self.gap.len()
+    }
+
+    // 現在の挿入点を返す
+    pub fn position(&self) -> usize {
+        self.gap.start
+    }
+
+    // ギャップを気にせず内部ストレージindex番目要素へのポインタを返す
+    // どちらもRawGapBuf::as_ptrから作るだけで参照を経由しないので、返したポインタ同士が無効にし合うことはない
+    unsafe fn space(&self, index: usize) -> *const T {
+        self.raw.as_ptr().add(index)
+    }
+
+    // ギャップを気にせず内部ストレージindex番目要素への可変ポインタを返す
+    unsafe fn space_mut(&mut self, index: usize) -> *mut T {
+        self.raw.as_ptr().add(index)
+    }
+
+    // VecDeque::as_slicesと同じく、ギャップの前と後ろの初期化済みの領域をそれぞれスライスとして返す
+    // 2つを連結したものが論理的な内容になる
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        unsafe {
+            (core::slice::from_raw_parts(self.space(0), self.gap.start),
+             core::slice::from_raw_parts(self.space(self.gap.end), self.capacity() - self.gap.end))
+        }
+    }
+
+    // ギャップをstorageの末尾に動かし、全ての要素を1つのスライスとして返す
+    // 挿入点は末尾に移る
+    pub fn make_contiguous(&mut self) -> &mut [T] {
+        let len = self.len();
+        self.set_position(len);
+        self.as_mut_slices().0
+    }
+
+    // as_slicesの可変参照版
+    pub fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]) {
+        let (gap, capacity) = (self.gap.clone(), self.capacity());
+        unsafe {
+            // 2つの領域はギャップを挟んで重ならないので、可変参照を同時に作ってよい
+            (core::slice::from_raw_parts_mut(self.space_mut(0), gap.start),
+             core::slice::from_raw_parts_mut(self.space_mut(gap.end), capacity - gap.end))
+        }
+    }
+
+    // ギャップを計算に入れて内部ストレージのindex番目要素へのポインタを返す
+    fn index_to_raw(&self, index: usize) -> usize {
+        if index < self.gap.start {
+            index
+        } else {
+            index + self.gap.len()
+        }
+    }
+
+    // index番目の要素への参照を返す
+    // indexが範囲外ならNoneを返す
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let raw = self.index_to_raw(index);
+        // rawをself.capacity()に対してチェックした
+        // index_to_rawはギャップをスキップするので安全
+        if raw < self.capacity() {
+            unsafe {
+                Some(&*self.space(raw))
+            }
+        } else {
+            None
+        }
+    }
+
+    // index番目の要素への可変参照を返す
+    // 要素を取り出して挿入し直す必要がないので、ギャップは動かない
+    // indexが範囲外ならNoneを返す
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let raw = self.index_to_raw(index);
+        // getと同じくrawをself.capacity()に対してチェックすれば、
+        // 初期化済みの要素だけを指していることが保証される
+        if raw < self.capacity() {
+            unsafe {
+                Some(&mut *self.space_mut(raw))
+            }
+        } else {
+            None
+        }
+    }
+
+    // 現在の挿入点を引数posに動かす
+    // もしposが範囲外であればpanicを起こす
+    pub fn set_position(&mut self, pos: usize) {
+        if pos > self.len() {
+            panic!("index {} out of range for GapBuffer", pos);
+        }
+        let gap_start = self.gap.start;
+
+        unsafe {
+            let gap = self.gap.clone();
+            if pos > gap.start {
+                // posはギャップの後ろにある
+                // ギャップの後ろの要素をギャップの前に動かして、ギャップを右にずらす
+                let distance = pos - gap.start;
+                core::ptr::copy(self.space(gap.end),
+                               self.space_mut(gap.start),
+                               distance);
+            } else if pos < gap.start {
+                // posはギャップの前にある
+                // ギャップの前の要素をギャップの後ろに動かして、ギャップを左にずらす
+                let distance = gap.start - pos;
+                core::ptr::copy(self.space(pos),
+                               self.space_mut(gap.end - distance),
+                               distance);
+            }
+
+        self.gap = pos .. pos + gap.len();
+        }
+
+        // ギャップをまたいだ印だけ持ち方が変わる
+        if pos != gap_start {
+            let len = self.len();
+            self.remap_marks(len, |pos| pos);
+        }
+    }
+
+    pub fn remove(&mut self) -> Option<T> {
+        if self.gap.end == self.capacity() {
+            return None;
+        }
+
+        // ギャップ直後の値をバッファから取り出す
+        let element = unsafe {
+            core::ptr::read(self.space(self.gap.end))
+        };
+        self.gap.end += 1;
+        let start = self.gap.start;
+        self.remap_marks_removed(self.len() + 1, start .. start + 1);
+        Some(element)
+    }
+
+    // 挿入点の直前の要素を取り除いて返す。先頭にいればNoneを返す
+    // ギャップを左に広げるだけなので、removeと同じく要素は動かない
+    pub fn remove_backward(&mut self) -> Option<T> {
+        if self.gap.start == 0 {
+            return None;
+        }
+
+        // ギャップ直前の値をバッファから取り出す
+        self.gap.start -= 1;
+        let element = unsafe {
+            core::ptr::read(self.space(self.gap.start))
+        };
+        let start = self.gap.start;
+        self.remap_marks_removed(self.len() + 1, start .. start + 1);
+        Some(element)
+    }
+
+    // 論理的な位置indexの要素を取り除いて返し、空いた場所にはギャップに隣接する要素を移す
+    // Vec::swap_removeと同じく要素の順序は保たれないが、間の要素を動かさずにO(1)で済む
+    // indexが挿入点より前なら挿入点の直前の要素が、後ろなら直後の要素がindexに移り、
+    // 前の場合は挿入点が1つ前にずれる。indexが範囲外ならpanicを起こす
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        if index >= self.len() {
+            panic!("swap_remove index (is {}) should be < len (is {})", index, self.len());
+        }
+
+        let raw = self.index_to_raw(index);
+        unsafe {
+            let element = core::ptr::read(self.space(raw));
+            // ギャップに隣接する要素を取り出したことにしてギャップを広げ、その要素を空いた場所に移す
+            let adjacent = if index < self.gap.start {
+                self.gap.start -= 1;
+                self.gap.start
+            } else {
+                let end = self.gap.end;
+                self.gap.end += 1;
+                end
+            };
+            if adjacent != raw {
+                core::ptr::copy_nonoverlapping(self.space(adjacent), self.space_mut(raw), 1);
+            }
+            // 印から見れば、ギャップに隣接する位置の要素が取り除かれたのと同じになる
+            let start = self.gap.start;
+            self.remap_marks_removed(self.len() + 1, start .. start + 1);
+            element
+        }
+    }
+
+    // 引数eltを現在の挿入点に挿入し、挿入点を1つ後ろにずらす
+    pub fn insert(&mut self, elt: T) {
+        if self.gap.is_empty() {
+            self.enlarge_gap();
+        }
+
+        unsafe {
+            let index = self.gap.start;
+            core::ptr::write(self.space_mut(index), elt);
+        }
+
+        self.gap.start += 1;
+    }
+
+    // 先頭から順に要素への参照を返すイテレータを作る
+    pub fn iter(&self) -> Iter<'_, T> {
+        unsafe {
+            Iter {
+                front: self.space(0),
+                front_len: self.gap.start,
+                back: self.space(self.gap.end),
+                back_len: self.capacity() - self.gap.end,
+                behaves_like: PhantomData
+            }
+        }
+    }
+
+    // 初期化済みの要素が連続して並ぶ領域を、先頭から順にスライスとして返すイテレータを作る
+    // 今はギャップの前と後ろの高々2つで、空の領域は返さない
+    // 要素ごとではなく領域ごとに、ハッシュの計算や書き出しをまとめて行える
+    pub fn chunks(&self) -> Chunks<'_, T> {
+        let (front, back) = self.as_slices();
+        Chunks {
+            front: Some(front).filter(|slice| !slice.is_empty()),
+            back: Some(back).filter(|slice| !slice.is_empty())
+        }
+    }
+
+    // 論理的な範囲rangeの要素を、コピーせずに借用するビューを作る
+    // rangeが範囲外ならpanicを起こす
+    pub fn slice<R: core::ops::RangeBounds<usize>>(&self, range: R) -> GapSlice<'_, T> {
+        let (front, back) = self.as_slices();
+        GapSlice { front, back }.slice(range)
+    }
+
+    // 先頭から順に要素への可変参照を返すイテレータを作る
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        let (gap, capacity) = (self.gap.clone(), self.capacity());
+        unsafe {
+            IterMut {
+                front: self.space_mut(0),
+                front_len: gap.start,
+                back: self.space_mut(gap.end),
+                back_len: capacity - gap.end,
+                behaves_like: PhantomData
+            }
+        }
+    }
+
+    // 論理的な位置positionを指す読み取り専用のカーソルを作る
+    // 共有参照を借用するだけなので、複数のカーソルを同時に持てる。positionが範囲外ならpanicを起こす
+    pub fn cursor(&self, position: usize) -> Cursor<'_, T, A> {
+        if position > self.len() {
+            panic!("index {} out of range for GapBuffer", position);
+        }
+        Cursor { buffer: self, position }
+    }
+
+    // 論理的な位置positionで挿入・削除ができるカーソルを作る
+    // ギャップは実際に編集する時まで動かさない。positionが範囲外ならpanicを起こす
+    pub fn cursor_mut(&mut self, position: usize) -> CursorMut<'_, T, A> {
+        if position > self.len() {
+            panic!("index {} out of range for GapBuffer", position);
+        }
+        CursorMut { buffer: self, position, id: None }
+    }
+
+    // 論理的な位置positionにカーソルを置き、そのIDを返す。positionが範囲外ならpanicを起こす
+    // 置いたカーソルは印と同じく、どこで挿入・削除が起きても位置がずれて有効なまま残る
+    // 複数キャレットで編集する時は、キャレットごとにカーソルを置いてcursor_mut_atで順に編集していく
+    pub fn add_cursor(&mut self, position: usize) -> CursorId {
+        if position > self.len() {
+            panic!("index {} out of range for GapBuffer", position);
+        }
+        let at = MarkAt::encode(position, self.gap.start, self.len());
+        self.marks.push(Mark { name: None, at });
+        CursorId(self.marks.len() - 1)
+    }
+
+    // 置いたカーソルのIDを、置いた順に返す
+    pub fn cursors(&self) -> Vec<CursorId> {
+        self.marks.iter().enumerate()
+            .filter(|(_, mark)| mark.name.is_none())
+            .map(|(i, _)| CursorId(i))
+            .collect()
+    }
+
+    // 置いたカーソルの今の論理的な位置を返す
+    // カーソルを置いたGapBufferやその複製のIDでなければpanicを起こす
+    pub fn cursor_position(&self, id: CursorId) -> usize {
+        match self.marks.get(id.0) {
+            Some(mark) if mark.name.is_none() => core::cmp::min(mark.at.decode(self.len()), self.len()),
+            _ => panic!("no cursor {:?} in this GapBuffer", id)
+        }
+    }
+
+    // 置いたカーソルの位置で挿入・削除ができるカーソルを作る
+    // CursorMutで動かしたり編集したりした結果はidのカーソルに残り、他のカーソルや印もそれに合わせてずれる
+    pub fn cursor_mut_at(&mut self, id: CursorId) -> CursorMut<'_, T, A> {
+        let position = self.cursor_position(id);
+        CursorMut { buffer: self, position, id: Some(id) }
+    }
+
+    // 論理的な範囲rangeの要素を取り除き、それらを順に返すイテレータを作る
+    // 挿入点はrangeの先頭に移る。rangeが範囲外ならpanicを起こす
+    pub fn drain<R: core::ops::RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, T, A> {
+        let Range { start, end } = logical_range(range, self.len());
+
+        // ギャップを一度だけrangeの先頭に動かすと、取り除く要素はギャップの直後に並ぶ
+        self.set_position(start);
+        let raw_start = self.gap.end;
+        // 先にギャップを広げて取り除く要素をGapBufferの管理から外し、Drainに所有させる
+        // Drainがmem::forgetされても要素がリークするだけで、二重にドロップされることはない
+        self.gap.end += end - start;
+        self.remap_marks_removed(self.len() + (end - start), start .. end);
+        Drain {
+            next: raw_start,
+            end: raw_start + (end - start),
+            buffer: self
+        }
+    }
+
+    // 論理的な範囲rangeの要素をreplace_withの要素で置き換える
+    // 取り除いた要素を順に返すイテレータを返し、置き換えはそのイテレータがドロップされた時に行う
+    // ギャップを動かすのは最初の一度だけで、置き換える要素は一時的なVecを経ずにギャップへ直接書き込む
+    // 挿入点は置き換えた要素の後ろに移る
+    pub fn splice<R, I>(&mut self, range: R, replace_with: I) -> Splice<'_, I::IntoIter, A>
+        where R: core::ops::RangeBounds<usize>, I: IntoIterator<Item=T>
+    {
+        Splice {
+            drain: self.drain(range),
+            replace_with: replace_with.into_iter()
+        }
+    }
+
+    // 論理的な範囲rangeの要素を取り除いてドロップする
+    pub fn remove_range<R: core::ops::RangeBounds<usize>>(&mut self, range: R) {
+        self.drain(range);
+    }
+
+    // sliceの要素を現在の挿入点にまとめてコピーし、挿入点をその後ろにずらす
+    // 容量の確保は一度だけで、コピーも1回のmemcpyで済む
+    pub fn insert_slice(&mut self, slice: &[T]) where T: Copy {
+        self.reserve(slice.len());
+        unsafe {
+            // reserveによってギャップにはslice.len()個以上の空きがあり、
+            // sliceは&selfと&mut selfを同時に借用できないのでstorageとは重ならない
+            let index = self.gap.start;
+            core::ptr::copy_nonoverlapping(slice.as_ptr(), self.space_mut(index), slice.len());
+        }
+        self.gap.start += slice.len();
+    }
+
+    // insert_sliceのCloneできる型向けの版
+    // 容量の確保は一度だけで、要素は1つずつcloneする
+    pub fn insert_from_slice(&mut self, slice: &[T]) where T: Clone {
+        self.reserve(slice.len());
+        for element in slice {
+            // 1つ書き込むたびにギャップを縮めるので、cloneがpanicしても書き込み済みの要素は失われない
+            unsafe {
+                let index = self.gap.start;
+                core::ptr::write(self.space_mut(index), element.clone());
+            }
+            self.gap.start += 1;
+        }
+    }
+
+    // 論理的な位置atで2つに分け、at以降の要素を持つ新しいGapBufferを返す
+    // 自身には先頭からat個の要素が残り、挿入点がatより後ろにあった時はatに移る
+    // 新しいGapBufferの挿入点は先頭になる。atが範囲外ならpanicを起こす
+    // 新しいGapBufferの格納場所は、自身のアロケータの複製から確保する
+    pub fn split_off(&mut self, at: usize) -> GapBuffer<T, A>
+        where A: Clone
+    {
+        if at > self.len() {
+            panic!("index {} out of range for GapBuffer of length {}", at, self.len());
+        }
+
+        let tail_len = self.len() - at;
+        let mut tail = GapBuffer::with_capacity_in(tail_len, self.allocator().clone());
+        let gap = self.gap.clone();
+        let capacity = self.capacity();
+        unsafe {
+            // 新しいGapBufferではギャップを先頭に置くので、要素はstorageの末尾に詰めて置く
+            let dst = tail.capacity() - tail_len;
+            if at < gap.start {
+                // ギャップの前のat以降と、ギャップの後ろの全ての要素を移す
+                let front = gap.start - at;
+                core::ptr::copy_nonoverlapping(self.space(at), tail.space_mut(dst), front);
+                core::ptr::copy_nonoverlapping(self.space(gap.end), tail.space_mut(dst + front),
+                                              capacity - gap.end);
+                self.gap = at .. capacity;
+            } else {
+                // ギャップの後ろの要素のうち、先頭keep個を残して残りを移す
+                let keep = at - gap.start;
+                core::ptr::copy_nonoverlapping(self.space(gap.end + keep), tail.space_mut(dst), tail_len);
+                // 残した要素をstorageの末尾に詰め直す
+                core::ptr::copy(self.space(gap.end), self.space_mut(capacity - keep), keep);
+                self.gap.end = capacity - keep;
+            }
+        }
+        self.remap_marks_removed(at + tail_len, at .. at + tail_len);
+        tail.gap = 0 .. tail.capacity() - tail_len;
+        tail
+    }
+
+    // 引数fがtrueを返す要素だけを順序を保ったまま残し、それ以外をドロップする
+    // 残った要素はギャップに向かって詰めるので、その分ギャップが広がる
+    // 挿入点は、元の挿入点より前で残った要素の数の位置になる
+    // 印も同じく、その印より前で残った要素の数の位置に移る
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        if self.marks.is_empty() {
+            return self.retain_elements(f);
+        }
+
+        // 印を動かすために、各要素を残したかどうかを覚えておく
+        let (old_len, front_len) = (self.len(), self.gap.start);
+        let mut kept = Vec::with_capacity(old_len);
+        self.retain_elements(|element| {
+            let keep = f(element);
+            kept.push(keep);
+            keep
+        });
+        // ギャップの後ろの要素は末尾から調べるので、論理的な順に並べ直す
+        kept[front_len..].reverse();
+        let mut kept_before = Vec::with_capacity(old_len + 1);
+        kept_before.push(0);
+        for keep in kept {
+            let last = kept_before[kept_before.len() - 1];
+            kept_before.push(last + keep as usize);
+        }
+        self.remap_marks(old_len, |pos| kept_before[pos]);
+    }
+
+    fn retain_elements<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        // fや要素のDropがpanicしても、ガードのDropが未処理の要素を詰め直して整合性を保つ
+        // 処理済みの要素は既にドロップしたか詰め終えているので、二重ドロップもリークも起きない
+        let end = self.gap.start;
+        let mut front = RetainFront { buffer: self, read: 0, write: 0, end };
+        while front.read < front.end {
+            unsafe {
+                let current = front.buffer.space_mut(front.read);
+                if f(&*current) {
+                    let write = front.buffer.space_mut(front.write);
+                    front.read += 1;
+                    front.write += 1;
+                    if current != write {
+                        core::ptr::copy_nonoverlapping(current, write, 1);
+                    }
+                } else {
+                    front.read += 1;
+                    core::ptr::drop_in_place(current);
+                }
+            }
+        }
+        drop(front);
+
+        // ギャップの後ろの要素はstorageの末尾に向かって詰める
+        let (start, capacity) = (self.gap.end, self.capacity());
+        let mut back = RetainBack { buffer: self, start, read_end: capacity, write_start: capacity };
+        while back.read_end > back.start {
+            unsafe {
+                let current = back.buffer.space_mut(back.read_end - 1);
+                let keep = f(&*current);
+                back.read_end -= 1;
+                if keep {
+                    back.write_start -= 1;
+                    let write = back.buffer.space_mut(back.write_start);
+                    if current != write {
+                        core::ptr::copy_nonoverlapping(current, write, 1);
+                    }
+                } else {
+                    core::ptr::drop_in_place(current);
+                }
+            }
+        }
+    }
+
+    // 全ての要素をドロップする。確保済みの容量はそのまま残す
+    pub fn clear(&mut self) {
+        let front = self.gap.start;
+        let back = self.gap.end .. self.capacity();
+        // 要素のDropがpanicしても二重にドロップしないよう、先に全体をギャップにしておく
+        let old_len = self.len();
+        self.gap = 0 .. self.capacity();
+        self.remap_marks_removed(old_len, 0 .. old_len);
+        // 前の要素のDropがpanicしても、ガードが後ろの要素をドロップするのでリークしない
+        unsafe {
+            let _back = DropSlice(core::ptr::slice_from_raw_parts_mut(self.space_mut(back.start), back.len()));
+            core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(self.space_mut(0), front));
+        }
+    }
+
+    // 先頭からlen個だけを残し、それより後ろの要素をドロップする。確保済みの容量はそのまま残す
+    // 挿入点がlenより後ろにあった時はlenに移る
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len() {
+            return;
+        }
+
+        let gap = self.gap.clone();
+        let capacity = self.capacity();
+        let old_len = self.len();
+        if len <= gap.start {
+            // ギャップの前のlen以降と、ギャップの後ろの全ての要素を捨てる
+            // clearと同じく、前の要素のDropがpanicしても後ろの要素はガードがドロップする
+            self.gap = len .. capacity;
+            unsafe {
+                let _back = DropSlice(core::ptr::slice_from_raw_parts_mut(self.space_mut(gap.end),
+                                                                         capacity - gap.end));
+                core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(self.space_mut(len),
+                                                                           gap.start - len));
+            }
+        } else {
+            // ギャップの後ろの要素のうち先頭keep個を残す
+            // 残す要素はいったんGapBufferの管理から外し、捨てる要素をドロップした後でガードが末尾に詰め直す
+            // 要素のDropがpanicしてもガードのDropは走るので、残す要素がリークすることはない
+            let keep = len - gap.start;
+            self.gap = gap.start .. capacity;
+            let guard = TruncateBack { buffer: self, kept: gap.end, keep };
+            unsafe {
+                core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(guard.buffer.space_mut(gap.end + keep),
+                                                                           capacity - gap.end - keep));
+            }
+        }
+        self.remap_marks_removed(old_len, len .. old_len);
+    }
+
+    // needleと一致する最初の部分列の先頭の論理的な位置を返す
+    // needleが空なら0を返す
+    pub fn find(&self, needle: &[T]) -> Option<usize> where T: PartialEq {
+        self.position_of_slice(needle, 0)
+    }
+
+    // needleと一致する最後の部分列の先頭の論理的な位置を返す
+    // needleが空なら長さを返す
+    pub fn rfind(&self, needle: &[T]) -> Option<usize> where T: PartialEq {
+        let last = self.len().checked_sub(needle.len())?;
+        (0 ..= last).rev().find(|&start| self.matches_at(start, needle))
+    }
+
+    // 論理的な位置from以降で、needleと一致する最初の部分列の先頭の位置を返す
+    // 見つけた位置の後ろから探し直せば、全ての一致を順に辿れる。fromが長さを超えていればNoneを返す
+    pub fn position_of_slice(&self, needle: &[T], from: usize) -> Option<usize> where T: PartialEq {
+        let last = self.len().checked_sub(needle.len())?;
+        (from ..= last).find(|&start| self.matches_at(start, needle))
+    }
+
+    // 論理的な位置startから並ぶ要素がneedleと一致するかを返す。start + needle.len()は長さ以下でなければならない
+    // ギャップの前と後ろのスライスをそのまま比べ、ギャップをまたぐ時はneedleを2つに分けて比べる
+    fn matches_at(&self, start: usize, needle: &[T]) -> bool where T: PartialEq {
+        let (front, back) = self.as_slices();
+        let end = start + needle.len();
+        if end <= front.len() {
+            front[start..end] == *needle
+        } else if start >= front.len() {
+            back[start - front.len() .. end - front.len()] == *needle
+        } else {
+            let (head, tail) = needle.split_at(front.len() - start);
+            front[start..] == *head && back[..tail.len()] == *tail
+        }
+    }
+
+    // 現在の挿入点にnameという名前の印を付け、そのIDを返す
+    // 同じ名前の印が既にあれば、新しく作らずにその印を挿入点に動かす
+    // 印はその前で挿入・削除が起きると一緒にずれ、印をまたぐ範囲が取り除かれると範囲の先頭に移る
+    // 印の位置に挿入した時は、印は挿入した要素の前に留まる
+    pub fn set_mark(&mut self, name: &str) -> MarkId {
+        let at = MarkAt::Front(self.gap.start);
+        match self.mark(name) {
+            Some(id) => {
+                self.marks[id.0].at = at;
+                id
+            }
+            None => {
+                self.marks.push(Mark { name: Some(name.to_string()), at });
+                MarkId(self.marks.len() - 1)
+            }
+        }
+    }
+
+    // nameという名前の印のIDを返す
+    pub fn mark(&self, name: &str) -> Option<MarkId> {
+        self.marks.iter().position(|mark| mark.name.as_deref() == Some(name)).map(MarkId)
+    }
+
+    // 印の今の論理的な位置を返す
+    // 印を付けたGapBufferやその複製のIDでなければpanicを起こす
+    pub fn position_of(&self, mark: MarkId) -> usize {
+        match self.marks.get(mark.0) {
+            // retainのfがpanicした時は印を動かせていないので、範囲内に収めて返す
+            Some(mark) if mark.name.is_some() => core::cmp::min(mark.at.decode(self.len()), self.len()),
+            _ => panic!("no mark {:?} in this GapBuffer", mark)
+        }
+    }
+
+    // 長さがold_lenだった時の論理的な位置を、fで今の論理的な位置に移して持ち直す
+    // 挿入以外の編集や挿入点の移動の後で呼ぶ
+    fn remap_marks<F: FnMut(usize) -> usize>(&mut self, old_len: usize, mut f: F) {
+        let (gap_start, len) = (self.gap.start, self.len());
+        for mark in &mut self.marks {
+            // 範囲外の位置が来るのはretainのfがpanicした後だけなので、長さに切り詰める
+            let pos = core::cmp::min(f(core::cmp::min(mark.at.decode(old_len), old_len)), len);
+            mark.at = MarkAt::encode(pos, gap_start, len);
+        }
+    }
+
+    // 長さがold_lenだった時の論理的な範囲removedが取り除かれたことを印に反映する
+    fn remap_marks_removed(&mut self, old_len: usize, removed: Range<usize>) {
+        if self.marks.is_empty() {
+            return;
+        }
+        self.remap_marks(old_len, |pos| {
+            if pos <= removed.start {
+                pos
+            } else if pos <= removed.end {
+                removed.start
+            } else {
+                pos - removed.len()
+            }
+        });
+    }
+
+    // iterableが生成する要素を現在の挿入位置に挿入し、挿入点をその後ろにずらす
+    pub fn insert_iter<I>(&mut self, iterable: I)
+        where I: IntoIterator<Item=T>
+    {
+        for item in iterable {
+            self.insert(item);
+        }
+    }
+
+    // storageの容量を倍にする
+    fn enlarge_gap(&mut self) {
+        let mut new_capacity = self.capacity().checked_mul(2)
+            .expect("capacity overflow");
+        if new_capacity == 0 {
+            // 空だった時は適当な初期容量を設定
+            new_capacity = 4;
+        }
+
+        self.reallocate(new_capacity);
+    }
+
+    // ギャップに少なくともadditional個の空きができるよう、一度だけ再確保する
+    pub fn reserve(&mut self, additional: usize) {
+        if self.gap.len() >= additional {
+            return;
+        }
+        let required = self.len().checked_add(additional)
+            .expect("capacity overflow");
+        // 倍々に増やす時と同じく、少しずつ要求されても再確保の回数が増えないようにする
+        self.reallocate(core::cmp::max(required, self.capacity().saturating_mul(2)));
+    }
+
+    // reserveの確保に失敗した時にエラーを返す版
+    // 失敗した時は何も変わらず、要素もそのまま残る
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if self.gap.len() >= additional {
+            return Ok(());
+        }
+        let required = match self.len().checked_add(additional) {
+            Some(required) => required,
+            None => return Err(capacity_overflow())
+        };
+        self.try_reallocate(core::cmp::max(required, self.capacity().saturating_mul(2)))
+    }
+
+    // insertの確保に失敗した時にエラーを返す版
+    // 失敗した時はeltをドロップし、GapBufferは変わらない
+    pub fn try_insert(&mut self, elt: T) -> Result<(), TryReserveError> {
+        self.try_reserve(1)?;
+        self.insert(elt);
+        Ok(())
+    }
+
+    // 容量new_capacityのストレージを確保し直し、ギャップの前後の要素をそのまま移す
+    // panicし得るのは最初の確保だけで、その時点ではselfに何も手を付けていない
+    // 確保できた後は要素をビット列としてコピーするだけなので、途中でpanicして要素が二重に見えることはない
+    fn reallocate(&mut self, new_capacity: usize) {
+        self.try_reallocate(new_capacity).unwrap_or_else(|e| handle_reserve_error(e));
+    }
+
+    // reallocateの、確保に失敗した時にエラーを返す版
+    fn try_reallocate(&mut self, new_capacity: usize) -> Result<(), TryReserveError> {
+        // 確保し直した領域の「使っていない」部分に何が起きるかわからないので、
+        // allocのgrowやshrinkは使わず、新しい領域を確保して中の要素を移動する
+        let gap = self.gap.clone();
+        let after_gap = self.capacity() - gap.end;
+        unsafe {
+            self.raw.try_relocate(new_capacity, |old, new, new_cap| {
+                // ギャップの前の要素を移動
+                core::ptr::copy_nonoverlapping(old, new, gap.start);
+                // ギャップの後ろの要素を新しい領域の末尾へ移動
+                core::ptr::copy_nonoverlapping(old.add(gap.end), new.add(new_cap - after_gap), after_gap);
+            })?;
+        }
+        self.gap = gap.start .. self.capacity() - after_gap;
+        Ok(())
+    }
+}
+
+impl<T, A: Allocator> Drop for GapBuffer<T, A> {
+    fn drop(&mut self) {
+        // GapBufferがドロップされた時は全ての要素がドロップされることを保証しなければならない
+        // 要素のDropがpanicしても、Vecと同じく残りの要素をドロップして領域を解放する
+        // 領域はこの後rawのDropが解放する。ここでpanicしても、フィールドのドロップは巻き戻しの間に行われる
+        unsafe {
+            let back = core::ptr::slice_from_raw_parts_mut(self.space_mut(self.gap.end),
+                                                          self.capacity() - self.gap.end);
+            let _back = DropSlice(back);
+            // スライスのdrop_in_placeは、途中の要素のDropがpanicしても残りの要素をドロップする
+            core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(self.space_mut(0), self.gap.start));
+        }
+    }
+}
+
+// 要素数の計算があふれたことを表すエラーを作る
+fn capacity_overflow() -> TryReserveError {
+    TryReserveErrorKind::CapacityOverflow.into()
+}
+
+// ドロップされた時に、スライスの要素をドロップするガード
+struct DropSlice<T>(*mut [T]);
+
+impl<T> Drop for DropSlice<T> {
+    fn drop(&mut self) {
+        unsafe {
+            core::ptr::drop_in_place(self.0);
+        }
+    }
+}
+
+// truncateで残すギャップの後ろの要素を、storageの末尾に詰め直すガード
+// buffer.gap.endはcapacityになっていて、kept..kept + keepの要素はまだGapBufferの管理から外れている
+struct TruncateBack<'a, T, A: Allocator> {
+    buffer: &'a mut GapBuffer<T, A>,
+    kept: usize,
+    keep: usize
+}
+
+impl<'a, T, A: Allocator> Drop for TruncateBack<'a, T, A> {
+    fn drop(&mut self) {
+        let capacity = self.buffer.capacity();
+        unsafe {
+            // ギャップの後ろの領域はstorageの末尾まで続いていなければならないので、残した要素を末尾に詰める
+            core::ptr::copy(self.buffer.space(self.kept), self.buffer.space_mut(capacity - self.keep), self.keep);
+        }
+        self.buffer.gap.end = capacity - self.keep;
+    }
+}
+
+// GapBufferはVec<T>と同じくTの値を所有するだけで、スレッドをまたいで共有する状態を持たない
+// Vec<T>と同じ境界で、T: SendならSend、T: SyncならSyncになる
+// 今はRawGapBufの実装から自動実装されるが、境界がここで読み取れるよう明示しておく
+// アロケータも一緒に持ち運ぶので、AにもSendやSyncを求める
+unsafe impl<T: Send, A: Allocator + Send> Send for GapBuffer<T, A> {}
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for GapBuffer<T, A> {}
+
+// 初期化済みの領域だけを複製し、ギャップの位置と大きさもそのまま再現する
+impl<T: Clone, A: Allocator + Clone> Clone for GapBuffer<T, A> {
+    fn clone(&self) -> GapBuffer<T, A> {
+        // 全体がギャップの空のGapBufferを用意し、ギャップの両端から要素を書き込んでいく
+        // 書き込むたびにギャップを縮めるので、途中で要素のcloneがpanicしても
+        // cloneのDropがそれまでに書き込んだ要素だけをドロップし、リークも二重ドロップも起きない
+        let mut clone = GapBuffer::with_capacity_in(self.capacity(), self.allocator().clone());
+
+        unsafe {
+            // ギャップの前の要素を先頭から書き込む
+            for i in 0..self.gap.start {
+                let element = (*self.space(i)).clone();
+                core::ptr::write(clone.space_mut(i), element);
+                clone.gap.start += 1;
+            }
+
+            // ギャップの後ろの要素を末尾から書き込む
+            for i in (self.gap.end..self.capacity()).rev() {
+                let element = (*self.space(i)).clone();
+                let index = clone.gap.end - 1;
+                core::ptr::write(clone.space_mut(index), element);
+                clone.gap.end -= 1;
+            }
+        }
+
+        clone.marks = self.marks.clone();
+        clone
+    }
+}
+
+// retainがギャップの前の領域を処理する間のガード
+// storageの0..writeは残す要素、read..endは未処理の要素で、write..readには何も無い
+struct RetainFront<'a, T, A: Allocator> {
+    buffer: &'a mut GapBuffer<T, A>,
+    read: usize,
+    write: usize,
+    end: usize
+}
+
+impl<'a, T, A: Allocator> Drop for RetainFront<'a, T, A> {
+    fn drop(&mut self) {
+        // 未処理の要素を残す要素の直後に詰め、ギャップの先頭をその後ろに合わせる
+        let rest = self.end - self.read;
+        unsafe {
+            core::ptr::copy(self.buffer.space(self.read), self.buffer.space_mut(self.write), rest);
+        }
+        self.buffer.gap.start = self.write + rest;
+    }
+}
+
+// retainがギャップの後ろの領域を処理する間のガード
+// storageのstart..read_endは未処理の要素、write_start..capacityは残す要素で、その間には何も無い
+struct RetainBack<'a, T, A: Allocator> {
+    buffer: &'a mut GapBuffer<T, A>,
+    start: usize,
+    read_end: usize,
+    write_start: usize
+}
+
+impl<'a, T, A: Allocator> Drop for RetainBack<'a, T, A> {
+    fn drop(&mut self) {
+        // 未処理の要素を残す要素の直前に詰め、ギャップの末尾をその前に合わせる
+        let rest = self.read_end - self.start;
+        let new_end = self.write_start - rest;
+        unsafe {
+            core::ptr::copy(self.buffer.space(self.start), self.buffer.space_mut(new_end), rest);
+        }
+        self.buffer.gap.end = new_end;
+    }
+}
+
+// 論理的な内容を並べ、挿入点を|で示す
+// 例えば"ab"の後ろに挿入点があれば ['a', 'b' | 'c'] と表示する
+impl<T: core::fmt::Debug, A: Allocator> core::fmt::Debug for GapBuffer<T, A> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str("[")?;
+        for (i, element) in self.iter().enumerate() {
+            if i == self.position() {
+                f.write_str(if i == 0 { "| " } else { " | " })?;
+            } else if i > 0 {
+                f.write_str(", ")?;
+            }
+            element.fmt(f)?;
+        }
+        if self.position() == self.len() {
+            f.write_str(if self.len() == 0 { "|" } else { " |" })?;
+        }
+        f.write_str("]")
+    }
+}
+
+// 要素を現在の挿入点に順に挿入する
+// insert_iterと違い、size_hintの分だけ先にギャップを広げておく
+impl<T, A: Allocator> Extend<T> for GapBuffer<T, A> {
+    fn extend<I: IntoIterator<Item=T>>(&mut self, iterable: I) {
+        let iter = iterable.into_iter();
+        self.reserve(iter.size_hint().0);
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+
+// 挿入点は末尾になる
+impl<T> core::iter::FromIterator<T> for GapBuffer<T> {
+    fn from_iter<I: IntoIterator<Item=T>>(iterable: I) -> GapBuffer<T> {
+        let mut buf = GapBuffer::new();
+        buf.extend(iterable);
+        buf
+    }
+}
+
+// Vecの確保領域をそのまま使い、余っている容量をギャップにする
+// 要素はコピーされず、挿入点は末尾になる
+impl<T> From<Vec<T>> for GapBuffer<T> {
+    fn from(mut vec: Vec<T>) -> GapBuffer<T> {
+        let len = vec.len();
+        unsafe {
+            // 要素の所有権はGapBufferに移るので、Vecからは見えないようにする
+            vec.set_len(0);
+        }
+        // Vecの領域はGlobalから確保されているので、そのまま持ち主を引き取れる
+        // Vecのポインタは確保していない時でもアラインされたダングリングポインタで、nullにはならない
+        let mut vec = core::mem::ManuallyDrop::new(vec);
+        let raw = unsafe {
+            RawGapBuf::from_raw_parts_in(NonNull::new_unchecked(vec.as_mut_ptr()), vec.capacity(), Global)
+        };
+        GapBuffer { gap: len..raw.capacity(), raw, marks: Vec::new(), owns: PhantomData }
+    }
+}
+
+impl<T> From<GapBuffer<T>> for Vec<T> {
+    fn from(buf: GapBuffer<T>) -> Vec<T> {
+        buf.into_vec()
+    }
+}
+
+// ギャップの位置に関係なく、論理的な要素の並びが同じなら等しい
+// アロケータが違っても、論理的な内容が同じなら等しい
+impl<T: PartialEq, A: Allocator, B: Allocator> PartialEq<GapBuffer<T, B>> for GapBuffer<T, A> {
+    fn eq(&self, other: &GapBuffer<T, B>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq, A: Allocator> Eq for GapBuffer<T, A> {}
+
+impl<T: PartialEq, A: Allocator> PartialEq<[T]> for GapBuffer<T, A> {
+    fn eq(&self, other: &[T]) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: PartialEq, A: Allocator> PartialEq<Vec<T>> for GapBuffer<T, A> {
+    fn eq(&self, other: &Vec<T>) -> bool {
+        *self == other[..]
+    }
+}
+
+// 同じ要素を持つスライス[T]と同じ順序で書き込む
+// 要素数を書き込んでから、ギャップの前後をhash_sliceで続けて書き込むので、
+// DefaultHasherのように書き込みを連結して扱うHasherではスライスのハッシュ値と一致する
+// ギャップの位置が違っても等しいGapBuffer同士はもちろん一致する
+impl<T: core::hash::Hash, A: Allocator> core::hash::Hash for GapBuffer<T, A> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        let (front, back) = self.as_slices();
+        state.write_usize(self.len());
+        T::hash_slice(front, state);
+        T::hash_slice(back, state);
+    }
+}
+
+// buf[i]で要素を読み書きできるようにする
+// 範囲外のindexを渡すとVecと同じくpanicを起こす
+impl<T, A: Allocator> core::ops::Index<usize> for GapBuffer<T, A> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        match self.get(index) {
+            Some(element) => element,
+            None => panic!("index out of bounds: the len is {} but the index is {}",
+                           self.len(), index)
+        }
+    }
+}
+
+impl<T, A: Allocator> core::ops::IndexMut<usize> for GapBuffer<T, A> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        let len = self.len();
+        match self.get_mut(index) {
+            Some(element) => element,
+            None => panic!("index out of bounds: the len is {} but the index is {}",
+                           len, index)
+        }
+    }
+}
+
+// GapBuffer::drainの戻り値
+// storageのnext..endにはまだ返していない要素が残っていて、GapBufferからはギャップの一部に見える
+pub struct Drain<'a, T, A: Allocator = Global> {
+    next: usize,
+    end: usize,
+    buffer: &'a mut GapBuffer<T, A>
+}
+
+impl<'a, T, A: Allocator> Iterator for Drain<'a, T, A> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.next == self.end {
+            return None;
+        }
+        // 要素を読み出したらnextを進め、二度と読まないようにする
+        let element = unsafe { core::ptr::read(self.buffer.space(self.next)) };
+        self.next += 1;
+        Some(element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.next;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T, A: Allocator> DoubleEndedIterator for Drain<'a, T, A> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.next == self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(unsafe { core::ptr::read(self.buffer.space(self.end)) })
+    }
+}
+
+impl<'a, T, A: Allocator> ExactSizeIterator for Drain<'a, T, A> {}
+
+// 途中で捨てられた時は、まだ返していない要素をドロップする
+impl<'a, T, A: Allocator> Drop for Drain<'a, T, A> {
+    fn drop(&mut self) {
+        unsafe {
+            let remaining = core::ptr::slice_from_raw_parts_mut(self.buffer.space_mut(self.next),
+                                                               self.end - self.next);
+            // 要素のDropがpanicしても残りの要素が読まれないよう、先に空にしておく
+            self.next = self.end;
+            core::ptr::drop_in_place(remaining);
+        }
+    }
+}
+
+// GapBuffer::spliceの戻り値
+pub struct Splice<'a, I: Iterator, A: Allocator = Global> {
+    drain: Drain<'a, I::Item, A>,
+    replace_with: I
+}
+
+impl<'a, I: Iterator, A: Allocator> Iterator for Splice<'a, I, A> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.drain.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.drain.size_hint()
+    }
+}
+
+impl<'a, I: Iterator, A: Allocator> DoubleEndedIterator for Splice<'a, I, A> {
+    fn next_back(&mut self) -> Option<I::Item> {
+        self.drain.next_back()
+    }
+}
+
+impl<'a, I: Iterator, A: Allocator> ExactSizeIterator for Splice<'a, I, A> {}
+
+impl<'a, I: Iterator, A: Allocator> Drop for Splice<'a, I, A> {
+    fn drop(&mut self) {
+        // 返していない要素を先に全てドロップして、取り除いた範囲をギャップに戻す
+        self.drain.by_ref().for_each(drop);
+        // ギャップの先頭は取り除いた範囲の先頭にあるので、そのまま挿入すればよい
+        self.drain.buffer.extend(self.replace_with.by_ref());
+    }
+}
+
+// ギャップの前の領域、後ろの領域の順にrawポインタを進めて要素を返すイテレータ
+// ギャップの中は初期化されていないので決して読まない
+pub struct Iter<'a, T> {
+    // 次に返す要素と、今いる領域に残っている要素数
+    front: *const T,
+    front_len: usize,
+    // ギャップの後ろの領域の先頭と要素数
+    back: *const T,
+    back_len: usize,
+    // GapBufferを共有参照で借用しているのと同じ生存期間の制約をかける
+    behaves_like: PhantomData<&'a T>
+}
+
+impl<'a, T> Iter<'a, T> {
+    // 2つのスライスを続けて辿るイテレータを作る
+    fn from_slices(front: &'a [T], back: &'a [T]) -> Iter<'a, T> {
+        Iter {
+            front: front.as_ptr(),
+            front_len: front.len(),
+            back: back.as_ptr(),
+            back_len: back.len(),
+            behaves_like: PhantomData
+        }
+    }
+}
+
+// rawポインタを持つので自動実装されないが、中身はstd::slice::Iterと同じく&'a Tの並び
+// &'a Tと同じくT: SyncならSendかつSyncになる
+unsafe impl<'a, T: Sync> Send for Iter<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for Iter<'a, T> {}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.front_len == 0 {
+            // ギャップの前を読み終えたらギャップを飛ばして後ろの領域に移る
+            if self.back_len == 0 {
+                return None;
+            }
+            self.front = self.back;
+            self.front_len = self.back_len;
+            self.back_len = 0;
+        }
+        unsafe {
+            let element = &*self.front;
+            self.front = self.front.add(1);
+            self.front_len -= 1;
+            Some(element)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.front_len + self.back_len;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        unsafe {
+            // 後ろの領域を末尾から読み、読み終えたら前の領域の末尾に移る
+            if self.back_len > 0 {
+                self.back_len -= 1;
+                Some(&*self.back.add(self.back_len))
+            } else if self.front_len > 0 {
+                self.front_len -= 1;
+                Some(&*self.front.add(self.front_len))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+// Iterの可変参照版
+pub struct IterMut<'a, T> {
+    front: *mut T,
+    front_len: usize,
+    back: *mut T,
+    back_len: usize,
+    behaves_like: PhantomData<&'a mut T>
+}
+
+// 中身は&'a mut Tの並びなので、&'a mut Tと同じくT: SendならSend、T: SyncならSyncになる
+unsafe impl<'a, T: Send> Send for IterMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for IterMut<'a, T> {}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        if self.front_len == 0 {
+            if self.back_len == 0 {
+                return None;
+            }
+            self.front = self.back;
+            self.front_len = self.back_len;
+            self.back_len = 0;
+        }
+        unsafe {
+            // 各要素への可変参照は一度しか返さないので、可変参照が重なることはない
+            let element = &mut *self.front;
+            self.front = self.front.add(1);
+            self.front_len -= 1;
+            Some(element)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.front_len + self.back_len;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        unsafe {
+            // 前からも後ろからも返した要素は数から外すので、同じ要素を二度返すことはない
+            if self.back_len > 0 {
+                self.back_len -= 1;
+                Some(&mut *self.back.add(self.back_len))
+            } else if self.front_len > 0 {
+                self.front_len -= 1;
+                Some(&mut *self.front.add(self.front_len))
+            } else {
+                None
+            }
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {}
+
+// GapBuffer::chunksの戻り値
+// まだ返していない空でない領域を持つ
+pub struct Chunks<'a, T> {
+    front: Option<&'a [T]>,
+    back: Option<&'a [T]>
+}
+
+impl<'a, T> Iterator for Chunks<'a, T> {
+    type Item = &'a [T];
+
+    fn next(&mut self) -> Option<&'a [T]> {
+        self.front.take().or_else(|| self.back.take())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.front.is_some() as usize + self.back.is_some() as usize;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Chunks<'a, T> {
+    fn next_back(&mut self) -> Option<&'a [T]> {
+        self.back.take().or_else(|| self.front.take())
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Chunks<'a, T> {}
+
+// GapBufferの論理的な範囲を借用するビュー
+// 範囲のうちギャップの前にある部分と後ろにある部分をスライスで持つので、作るのも複製するのも安い
+// &GapBufferと位置の組の代わりに、「バッファの一部分」として関数に渡せる
+pub struct GapSlice<'a, T> {
+    front: &'a [T],
+    back: &'a [T]
+}
+
+// スライスを2つ持つだけなので、Tに関係なく複製できる
+impl<'a, T> Clone for GapSlice<'a, T> {
+    fn clone(&self) -> GapSlice<'a, T> {
+        *self
+    }
+}
+
+impl<'a, T> Copy for GapSlice<'a, T> {}
+
+impl<'a, T> GapSlice<'a, T> {
+    pub fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // ビューの先頭から数えてindex番目の要素を返す
+    pub fn get(&self, index: usize) -> Option<&'a T> {
+        if index < self.front.len() {
+            Some(&self.front[index])
+        } else {
+            self.back.get(index - self.front.len())
+        }
+    }
+
+    // ギャップの前と後ろにある部分をそれぞれ返す。どちらか、あるいは両方が空のこともある
+    pub fn as_slices(&self) -> (&'a [T], &'a [T]) {
+        (self.front, self.back)
+    }
+
+    pub fn iter(&self) -> Iter<'a, T> {
+        Iter::from_slices(self.front, self.back)
+    }
+
+    // ビューの中の範囲rangeを、さらに絞り込んだビューを作る。rangeが範囲外ならpanicを起こす
+    pub fn slice<R: core::ops::RangeBounds<usize>>(&self, range: R) -> GapSlice<'a, T> {
+        let Range { start, end } = logical_range(range, self.len());
+        let split = self.front.len();
+        GapSlice {
+            front: &self.front[core::cmp::min(start, split) .. core::cmp::min(end, split)],
+            back: &self.back[start.saturating_sub(split) .. end.saturating_sub(split)]
+        }
+    }
+}
+
+impl<'a, T> core::ops::Index<usize> for GapSlice<'a, T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        match self.get(index) {
+            Some(element) => element,
+            None => panic!("index {} out of range for GapSlice of length {}", index, self.len())
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for GapSlice<'a, T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T: core::fmt::Debug> core::fmt::Debug for GapSlice<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, 'b, T: PartialEq> PartialEq<GapSlice<'b, T>> for GapSlice<'a, T> {
+    fn eq(&self, other: &GapSlice<'b, T>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<'a, T: PartialEq> PartialEq<[T]> for GapSlice<'a, T> {
+    fn eq(&self, other: &[T]) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+// 要素を先頭から順にムーブして返すイテレータ
+// ギャップを末尾に動かして要素を1つの連続した領域に詰め、Drainと同じく両端から読み出す
+// bufferからは全体がギャップに見えるので、bufferのDropは領域を解放するだけになる
+// storageのnext..endにはまだ返していない要素が残っている
+pub struct IntoIter<T, A: Allocator = Global> {
+    buffer: GapBuffer<T, A>,
+    next: usize,
+    end: usize
+}
+
+impl<T, A: Allocator> Iterator for IntoIter<T, A> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.next == self.end {
+            return None;
+        }
+        let element = unsafe { core::ptr::read(self.buffer.space(self.next)) };
+        self.next += 1;
+        Some(element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.next;
+        (len, Some(len))
+    }
+}
+
+impl<T, A: Allocator> DoubleEndedIterator for IntoIter<T, A> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.next == self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(unsafe { core::ptr::read(self.buffer.space(self.end)) })
+    }
+}
+
+impl<T, A: Allocator> ExactSizeIterator for IntoIter<T, A> {}
+
+// まだ返していない要素をドロップする。領域はこの後bufferのDropが解放する
+impl<T, A: Allocator> Drop for IntoIter<T, A> {
+    fn drop(&mut self) {
+        unsafe {
+            let remaining = core::ptr::slice_from_raw_parts_mut(self.buffer.space_mut(self.next),
+                                                               self.end - self.next);
+            self.next = self.end;
+            core::ptr::drop_in_place(remaining);
+        }
+    }
+}
+
+impl<T, A: Allocator> IntoIterator for GapBuffer<T, A> {
+    type Item = T;
+    type IntoIter = IntoIter<T, A>;
+
+    fn into_iter(mut self) -> IntoIter<T, A> {
+        let len = self.len();
+        self.set_position(len);
+        // 要素はIntoIterが1つずつ取り出すので、GapBufferからは全体がギャップに見えるようにしておく
+        self.gap = 0..self.capacity();
+        IntoIter { buffer: self, next: 0, end: len }
+    }
+}
+
+impl<'a, T, A: Allocator> IntoIterator for &'a GapBuffer<T, A> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T, A: Allocator> IntoIterator for &'a mut GapBuffer<T, A> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+// 要素と要素の間の位置を指すカーソル
+// positionは0からlenまでの値をとり、position番目の要素の直前を指す
+pub struct Cursor<'a, T, A: Allocator = Global> {
+    buffer: &'a GapBuffer<T, A>,
+    position: usize
+}
+
+// 共有参照と位置しか持たないので、Tに関係なく複製できる
+impl<'a, T, A: Allocator> Clone for Cursor<'a, T, A> {
+    fn clone(&self) -> Cursor<'a, T, A> {
+        *self
+    }
+}
+
+impl<'a, T, A: Allocator> Copy for Cursor<'a, T, A> {}
+
+impl<'a, T, A: Allocator> Cursor<'a, T, A> {
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    // 1つ前に動かす。先頭にいて動けなければfalseを返す
+    pub fn move_left(&mut self) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+        self.position -= 1;
+        true
+    }
+
+    // 1つ後ろに動かす。末尾にいて動けなければfalseを返す
+    pub fn move_right(&mut self) -> bool {
+        if self.position == self.buffer.len() {
+            return false;
+        }
+        self.position += 1;
+        true
+    }
+
+    // カーソルの直前の要素を返す
+    pub fn peek_prev(&self) -> Option<&'a T> {
+        self.position.checked_sub(1).and_then(|i| self.buffer.get(i))
+    }
+
+    // カーソルの直後の要素を返す
+    pub fn peek_next(&self) -> Option<&'a T> {
+        self.buffer.get(self.position)
+    }
+}
+
+// GapBufferを可変参照で借用して編集するカーソル
+// cursor_mut_atで作った時は、動かしたり編集したりするたびに位置をGapBufferに置いたカーソルにも書き戻す
+// 置いた他のカーソルや印は印の仕組みでずれるので、どのカーソルから編集しても全てのカーソルが有効なまま残る
+pub struct CursorMut<'a, T, A: Allocator = Global> {
+    buffer: &'a mut GapBuffer<T, A>,
+    position: usize,
+    id: Option<CursorId>
+}
+
+impl<'a, T, A: Allocator> CursorMut<'a, T, A> {
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    // cursor_mut_atで作った時は、書き戻す先のカーソルのIDを返す
+    pub fn id(&self) -> Option<CursorId> {
+        self.id
+    }
+
+    // 同じ位置を指す読み取り専用のカーソルを返す
+    pub fn as_cursor(&self) -> Cursor<'_, T, A> {
+        Cursor { buffer: self.buffer, position: self.position }
+    }
+
+    pub fn move_left(&mut self) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+        self.position -= 1;
+        self.store();
+        true
+    }
+
+    pub fn move_right(&mut self) -> bool {
+        if self.position == self.buffer.len() {
+            return false;
+        }
+        self.position += 1;
+        self.store();
+        true
+    }
+
+    // 位置posにカーソルを動かす。posが範囲外ならpanicを起こす
+    pub fn move_to(&mut self, pos: usize) {
+        if pos > self.buffer.len() {
+            panic!("index {} out of range for GapBuffer", pos);
+        }
+        self.position = pos;
+        self.store();
+    }
+
+    // カーソルの位置に要素を挿入し、カーソルを挿入した要素の後ろに進める
+    // 同じ位置にある他のカーソルや印は、印の位置への挿入と同じく挿入した要素の前に留まる
+    pub fn insert(&mut self, elt: T) {
+        self.buffer.set_position(self.position);
+        self.buffer.insert(elt);
+        self.position += 1;
+        self.store();
+    }
+
+    // カーソルの直後の要素を取り除いて返す。末尾にいればNoneを返す
+    // 取り除いた要素より後ろにある他のカーソルや印は1つ前にずれる
+    pub fn remove(&mut self) -> Option<T> {
+        self.buffer.set_position(self.position);
+        self.buffer.remove()
+    }
+
+    // 置いたカーソルから作った時は、今の位置をそのカーソルに書き戻す
+    fn store(&mut self) {
+        if let Some(id) = self.id {
+            let (gap_start, len) = (self.buffer.gap.start, self.buffer.len());
+            self.buffer.marks[id.0].at = MarkAt::encode(self.position, gap_start, len);
+        }
+    }
+}
+
+// UTF-8のバイト列をギャップ付きで保持する文字列
+// GapBuffer<char>と違い1文字を1から4バイトで持ち、ギャップの前後をそれぞれ&strとして借用できる
+// ギャップは常に文字の境界にあるので、ギャップの前後はどちらも正しいUTF-8になる
+pub struct GapString {
+    bytes: GapBuffer<u8>
+}
+
+impl GapString {
+    pub fn new() -> GapString {
+        GapString { bytes: GapBuffer::new() }
+    }
+
+    // バイト数を返す
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // 挿入点のバイト位置を返す
+    pub fn position(&self) -> usize {
+        self.bytes.position()
+    }
+
+    // バイト位置posが文字の境界ならtrueを返す
+    // UTF-8では継続バイトだけが0b10xxxxxxの形をしている
+    pub fn is_char_boundary(&self, pos: usize) -> bool {
+        if pos == self.len() {
+            return true;
+        }
+        match self.bytes.get(pos) {
+            Some(&b) => (b as i8) >= -0x40,
+            None => false
+        }
+    }
+
+    // 挿入点をバイト位置posに動かす
+    // posが範囲外か文字の途中ならpanicを起こす
+    pub fn set_position(&mut self, pos: usize) {
+        if !self.is_char_boundary(pos) {
+            panic!("byte index {} is not a char boundary", pos);
+        }
+        self.bytes.set_position(pos);
+    }
+
+    // 挿入点に文字列を挿入し、挿入点をその後ろにずらす
+    pub fn insert_str(&mut self, s: &str) {
+        self.bytes.insert_slice(s.as_bytes());
+    }
+
+    pub fn insert(&mut self, ch: char) {
+        self.insert_str(ch.encode_utf8(&mut [0; 4]));
+    }
+
+    // 挿入点の直後の1文字を取り除いて返す。末尾にいればNoneを返す
+    pub fn remove_char(&mut self) -> Option<char> {
+        let ch = self.as_str_halves().1.chars().next()?;
+        for _ in 0..ch.len_utf8() {
+            self.bytes.remove();
+        }
+        Some(ch)
+    }
+
+    // 挿入点より前と後ろの文字列を返す
+    pub fn as_str_halves(&self) -> (&str, &str) {
+        let (front, back) = self.bytes.as_slices();
+        // insert_strは&strしか受け取らず、挿入点は文字の境界にしか置けないので
+        // ギャップの前後はどちらも正しいUTF-8になっている
+        unsafe {
+            (core::str::from_utf8_unchecked(front), core::str::from_utf8_unchecked(back))
+        }
+    }
+}
+
+impl Default for GapString {
+    fn default() -> GapString {
+        GapString::new()
+    }
+}
+
+// Stringの確保領域をそのまま使い、挿入点は末尾になる
+impl From<String> for GapString {
+    fn from(s: String) -> GapString {
+        GapString { bytes: GapBuffer::from(s.into_bytes()) }
+    }
+}
+
+impl From<&str> for GapString {
+    fn from(s: &str) -> GapString {
+        GapString::from(s.to_string())
+    }
+}
+
+impl From<GapString> for String {
+    fn from(s: GapString) -> String {
+        // ギャップの前後がそれぞれUTF-8で、どちらも文字の境界で切れているので連結してもUTF-8になる
+        unsafe { String::from_utf8_unchecked(s.bytes.into_vec()) }
+    }
+}
+
+impl core::fmt::Display for GapString {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let (front, back) = self.as_str_halves();
+        f.write_str(front)?;
+        f.write_str(back)
+    }
+}
+
+// テキストの改行の位置を覚えておき、編集のたびに全体を読み直さずに行と列を計算する
+// 改行のバイト位置をGapBufferに並べ、ギャップを最後に編集した位置に合わせておく
+// ギャップの前の位置は先頭からのバイト数、後ろの位置はテキスト末尾までのバイト数で持つので、
+// ギャップの位置での挿入・削除では後ろの改行の位置を1つも書き換えなくてよい
+pub struct LineIndex {
+    newlines: GapBuffer<usize>,
+    // テキスト全体のバイト数
+    len: usize
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> LineIndex {
+        let mut index = LineIndex { newlines: GapBuffer::new(), len: 0 };
+        index.insert(0, text);
+        index
+    }
+
+    // 行数を返す。改行で終わるテキストは最後に空の行があるものとして数える
+    pub fn line_count(&self) -> usize {
+        self.newlines.len() + 1
+    }
+
+    // i番目の改行の、先頭からのバイト位置を返す
+    fn newline(&self, i: usize) -> usize {
+        let stored = self.newlines[i];
+        if i < self.newlines.position() {
+            stored
+        } else {
+            self.len - stored
+        }
+    }
+
+    // offsetより前にある改行の数を二分探索で数える
+    fn newlines_before(&self, offset: usize) -> usize {
+        let (mut lo, mut hi) = (0, self.newlines.len());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.newline(mid) < offset {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    // バイト位置offsetの行と列を、どちらも0から数えて返す。列はバイト数で数える
+    // offsetが範囲外ならpanicを起こす
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        if offset > self.len {
+            panic!("offset {} out of range for text of length {}", offset, self.len);
+        }
+        let line = self.newlines_before(offset);
+        (line, offset - self.offset_of_line(line).unwrap())
+    }
+
+    // n行目の先頭のバイト位置を返す。そんな行がなければNoneを返す
+    pub fn offset_of_line(&self, n: usize) -> Option<usize> {
+        match n {
+            0 => Some(0),
+            n if n < self.line_count() => Some(self.newline(n - 1) + 1),
+            _ => None
+        }
+    }
+
+    // ギャップの前にoffsetより前の改行が、後ろにoffset以降の改行が来るようにギャップを動かす
+    // ギャップをまたいだ改行だけ、位置の表し方を変える
+    fn move_gap(&mut self, offset: usize) {
+        loop {
+            let pos = self.newlines.position();
+            if pos > 0 && self.newline(pos - 1) >= offset {
+                self.newlines.set_position(pos - 1);
+                self.newlines[pos - 1] = self.len - self.newlines[pos - 1];
+            } else if pos < self.newlines.len() && self.newline(pos) < offset {
+                self.newlines.set_position(pos + 1);
+                self.newlines[pos] = self.len - self.newlines[pos];
+            } else {
+                break;
+            }
+        }
+    }
+
+    // バイト位置offsetにtextが挿入されたことを反映する
+    pub fn insert(&mut self, offset: usize, text: &str) {
+        if offset > self.len {
+            panic!("offset {} out of range for text of length {}", offset, self.len);
+        }
+        self.move_gap(offset);
+        for (i, _) in text.match_indices('\n') {
+            self.newlines.insert(offset + i);
+        }
+        // ギャップの後ろの改行は末尾からの距離で持っているので、長さを変えるだけで全てずれる
+        self.len += text.len();
+    }
+
+    // バイト位置の範囲rangeが削除されたことを反映する
+    pub fn remove(&mut self, range: core::ops::Range<usize>) {
+        if range.start > range.end || range.end > self.len {
+            panic!("range {:?} out of range for text of length {}", range, self.len);
+        }
+        self.move_gap(range.start);
+        while self.newlines.position() < self.newlines.len()
+            && self.newline(self.newlines.position()) < range.end {
+            self.newlines.remove();
+        }
+        self.len -= range.len();
+    }
+}
+
+// EditLogが記録する操作
+// 取り除いた要素は記録の中に持っておき、元に戻す時にそのままバッファへ戻す
+enum Edit<T> {
+    Insert { at: usize, elements: Vec<T> },
+    Remove { at: usize, len: usize }
+}
+
+impl<T> Edit<T> {
+    // bufferに操作を適用し、それを打ち消す操作を返す
+    fn apply(self, buffer: &mut GapBuffer<T>) -> Edit<T> {
+        match self {
+            Edit::Insert { at, elements } => {
+                let len = elements.len();
+                buffer.set_position(at);
+                buffer.extend(elements);
+                Edit::Remove { at, len }
+            }
+            Edit::Remove { at, len } => {
+                let elements = buffer.drain(at .. at + len).collect();
+                Edit::Insert { at, elements }
+            }
+        }
+    }
+}
+
+// GapBufferへの挿入・削除を記録し、元に戻したりやり直したりできるようにするラッパー
+// 記録するのは各操作を打ち消す操作で、それを適用すると今度はやり直すための操作が得られる
+// 要素は記録とバッファの間をムーブするだけなので、TにCloneは要らない
+pub struct EditLog<T> {
+    buffer: GapBuffer<T>,
+    // 1つのトランザクションは打ち消す操作の並びで、undoでもredoでも後ろから適用する
+    undo: Vec<Vec<Edit<T>>>,
+    redo: Vec<Vec<Edit<T>>>,
+    // begin_transactionからcommit_transactionまでの操作
+    pending: Option<Vec<Edit<T>>>
+}
+
+impl<T> EditLog<T> {
+    pub fn new(buffer: GapBuffer<T>) -> EditLog<T> {
+        EditLog { buffer, undo: Vec::new(), redo: Vec::new(), pending: None }
+    }
+
+    pub fn buffer(&self) -> &GapBuffer<T> {
+        &self.buffer
+    }
+
+    pub fn into_inner(self) -> GapBuffer<T> {
+        self.buffer
+    }
+
+    // 以降の操作を、commit_transactionまで1回のundoで戻せる1つのトランザクションにまとめる
+    // すでにトランザクションの途中なら何もしない
+    pub fn begin_transaction(&mut self) {
+        if self.pending.is_none() {
+            self.pending = Some(Vec::new());
+        }
+    }
+
+    pub fn commit_transaction(&mut self) {
+        if let Some(edits) = self.pending.take() {
+            if !edits.is_empty() {
+                self.undo.push(edits);
+            }
+        }
+    }
+
+    // 打ち消す操作を記録する。新しい操作をしたら、それまでのやり直しの履歴は捨てる
+    fn record(&mut self, inverse: Edit<T>) {
+        self.redo.clear();
+        match self.pending {
+            Some(ref mut edits) => edits.push(inverse),
+            None => self.undo.push(vec![inverse])
+        }
+    }
+
+    // 論理的な位置atにelementsを順に挿入する。atが範囲外ならpanicを起こす
+    pub fn insert<I: IntoIterator<Item=T>>(&mut self, at: usize, elements: I) {
+        let elements: Vec<T> = elements.into_iter().collect();
+        if elements.is_empty() {
+            return;
+        }
+        let inverse = Edit::Insert { at, elements }.apply(&mut self.buffer);
+        self.record(inverse);
+    }
+
+    // 論理的な範囲rangeの要素を取り除く。rangeが範囲外ならpanicを起こす
+    pub fn remove(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let inverse = Edit::Remove { at: range.start, len: range.len() }.apply(&mut self.buffer);
+        self.record(inverse);
+    }
+
+    // 最後のトランザクションを元に戻す。戻すものがなければfalseを返す
+    pub fn undo(&mut self) -> bool {
+        self.commit_transaction();
+        match self.undo.pop() {
+            Some(edits) => {
+                let redo = self.replay(edits);
+                self.redo.push(redo);
+                true
+            }
+            None => false
+        }
+    }
+
+    // 最後に元に戻したトランザクションをやり直す。やり直すものがなければfalseを返す
+    pub fn redo(&mut self) -> bool {
+        self.commit_transaction();
+        match self.redo.pop() {
+            Some(edits) => {
+                let undo = self.replay(edits);
+                self.undo.push(undo);
+                true
+            }
+            None => false
+        }
+    }
+
+    // 操作を後ろから順に適用し、打ち消す操作を適用した順に並べて返す
+    // 返した並びも後ろから適用すれば、最後に適用した操作から打ち消していくことになる
+    fn replay(&mut self, edits: Vec<Edit<T>>) -> Vec<Edit<T>> {
+        edits.into_iter().rev()
+            .map(|edit| edit.apply(&mut self.buffer))
+            .collect()
+    }
+}
+
+// serdeフィーチャーが有効な時だけシリアライズ・デシリアライズを実装する
+// ギャップは無視して論理的な要素の並びとして書き出し、読み込んだ時はギャップを末尾に置く
+#[cfg(feature = "serde")]
+mod gap_serde {
+    use core::fmt;
+    use core::marker::PhantomData;
+    use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+    use serde::ser::{Serialize, Serializer};
+    use super::{Allocator, GapBuffer};
+
+    impl<T: Serialize, A: Allocator> Serialize for GapBuffer<T, A> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    struct GapBufferVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for GapBufferVisitor<T> {
+        type Value = GapBuffer<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a sequence")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<GapBuffer<T>, A::Error> {
+            // 入力が申告する要素数は信用できないので、先に確保するのは控えめな大きさまでにする
+            let mut buf = GapBuffer::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(element) = seq.next_element()? {
+                buf.insert(element);
+            }
+            Ok(buf)
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for GapBuffer<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<GapBuffer<T>, D::Error> {
+            deserializer.deserialize_seq(GapBufferVisitor(PhantomData))
+        }
+    }
+}
+
+// stdフィーチャーが有効な時だけ、GapBuffer<u8>をメモリ上の編集できるファイルとして読み書きできるようにする
+// ファイル位置は挿入点と同じで、Writeは挿入点に挿入し、Readは挿入点の後ろを読んで挿入点を進める
+// Seekはset_positionになる。ファイルと違って末尾より後ろには動けない
+#[cfg(feature = "std")]
+mod gap_io {
+    // クレートがno_stdでも、このモジュールだけはstdを使う
+    extern crate std;
+
+    use std::io::{self, Read, Seek, SeekFrom, Write};
+    use super::{Allocator, GapBuffer};
+
+    impl<A: Allocator> Write for GapBuffer<u8, A> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.insert_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<A: Allocator> Read for GapBuffer<u8, A> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = {
+                let after = self.as_slices().1;
+                let n = core::cmp::min(buf.len(), after.len());
+                buf[..n].copy_from_slice(&after[..n]);
+                n
+            };
+            let position = self.position();
+            self.set_position(position + n);
+            Ok(n)
+        }
+    }
+
+    impl<A: Allocator> Seek for GapBuffer<u8, A> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            let (base, offset) = match pos {
+                SeekFrom::Start(offset) => (0, offset as i128),
+                SeekFrom::End(offset) => (self.len(), offset as i128),
+                SeekFrom::Current(offset) => (self.position(), offset as i128)
+            };
+            let target = base as i128 + offset;
+            if target < 0 || target > self.len() as i128 {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "invalid seek to a negative or past-the-end position"));
+            }
+            self.set_position(target as usize);
+            Ok(target as u64)
+        }
+    }
+}
+
+// GapBufferをファジングにかけるための確認
+// testingフィーチャーが有効な時だけ使える
+#[cfg(feature = "testing")]
+pub mod testing {
+    use alloc::vec::Vec;
+    use core::ops::Range;
+    use super::{GapBuffer, MarkId};
+
+    // バイト列を操作の並びとして読み、同じ操作をGapBufferとVecに加えて結果が食い違わないか確かめる
+    // Vecには挿入点が無いので、挿入点は別に持っておく
+    // fuzz/のファズターゲットもこの関数を呼び、任意の入力で食い違いを探す
+    pub fn check_against_vec(data: &[u8]) {
+
+        // 範囲rangeが取り除かれた時に、論理的な位置posの印が移る先
+        fn removed(pos: usize, range: Range<usize>) -> usize {
+            if pos <= range.start {
+                pos
+            } else if pos <= range.end {
+                range.start
+            } else {
+                pos - range.len()
+            }
+        }
+
+        let mut buf: GapBuffer<u8> = GapBuffer::new();
+        let mut model: Vec<u8> = Vec::new();
+        let mut position = 0;
+        // 挿入点を動かすたびに付ける印と、その論理的な位置
+        let mut marks: [Option<(MarkId, usize)>; 3] = [None; 3];
+
+        for op in data.chunks(2) {
+            let arg = op.get(1).copied().unwrap_or(0);
+            match op[0] % 6 {
+                0 => {
+                    buf.insert(arg);
+                    model.insert(position, arg);
+                    for (_, mark) in marks.iter_mut().flatten() {
+                        if *mark > position {
+                            *mark += 1;
+                        }
+                    }
+                    position += 1;
+                }
+                1 => {
+                    let expected = if position < model.len() { Some(model.remove(position)) } else { None };
+                    assert_eq!(buf.remove(), expected);
+                    if expected.is_some() {
+                        for (_, mark) in marks.iter_mut().flatten() {
+                            *mark = removed(*mark, position .. position + 1);
+                        }
+                    }
+                }
+                2 => {
+                    position = arg as usize % (model.len() + 1);
+                    buf.set_position(position);
+                    let name = ["a", "b", "c"][(op[0] as usize / 6) % 3];
+                    marks[(op[0] as usize / 6) % 3] = Some((buf.set_mark(name), position));
+                }
+                3 => {
+                    let index = arg as usize;
+                    assert_eq!(buf.get(index), model.get(index));
+                }
+                4 => {
+                    let expected = if position > 0 {
+                        position -= 1;
+                        for (_, mark) in marks.iter_mut().flatten() {
+                            *mark = removed(*mark, position .. position + 1);
+                        }
+                        Some(model.remove(position))
+                    } else {
+                        None
+                    };
+                    assert_eq!(buf.remove_backward(), expected);
+                }
+                _ => {
+                    let start = arg as usize % (model.len() + 1);
+                    let end = start + (op[0] as usize / 6) % (model.len() - start + 1);
+                    let drained: Vec<u8> = buf.drain(start..end).collect();
+                    assert_eq!(drained, model.drain(start..end).collect::<Vec<_>>());
+                    for (_, mark) in marks.iter_mut().flatten() {
+                        *mark = removed(*mark, start .. end);
+                    }
+                    position = start;
+                }
+            }
+            assert_eq!(buf.len(), model.len());
+            assert_eq!(buf.position(), position);
+            for &(id, mark) in marks.iter().flatten() {
+                assert_eq!(buf.position_of(id), mark);
+            }
+        }
+        assert_eq!(buf, model);
+    }
+}
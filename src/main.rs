@@ -1,384 +1,5849 @@
-#[derive(Debug, Eq, PartialEq)]
-pub struct Ascii(
-    Vec<u8> // ASCIIテキストだけを保持する 0 - 0x7f までのバイト列
-    );
+extern crate alloc;
 
-impl Ascii {
-    // 引数 bytes 内のASCIIテキストから型 Ascii を作る
-    // ASCIIでない文字列が入っていたらNotAsciiErrorを返す
-    pub fn from_bytes(bytes: Vec<u8>) -> Result<Ascii, NotAsciiError> {
-        if bytes.iter().any(|&_byte| !bytes.is_ascii()) {
-            return Err(NotAsciiError(bytes));
+// ASCIIテキストを扱う型。no_std_check/からも読み込むので、別のファイルに置く
+#[macro_use]
+mod ascii;
+
+// わざと未定義動作を起こす例なので、共有参照を書き換えるキャストを禁じるlintを外しておく
+#[allow(invalid_reference_casting)]
+fn very_trustworthy(shared: &i32) {
+    unsafe {
+        // 引数で受け取った共有ポインタを可変ポインタに変換し、書き換えている（未定義動作）
+        let mutable = shared as *const i32 as *mut i32;
+        *mutable = 20;
+    }
+}
+
+fn option_to_raw<T>(opt: Option<&T>) -> *const T {
+    match opt {
+        None => std::ptr::null(),
+        Some(r) => r as *const T
+    }
+}
+
+// option_to_rawのNonNull版。Noneをヌルで表す代わりに、Option<NonNull<T>>のままで返す
+// NonNullはヌルにならないことを型で表すので、Option<NonNull<T>>は*const Tと同じ1語に収まる
+fn option_to_nonnull<T>(opt: Option<&T>) -> Option<std::ptr::NonNull<T>> {
+    opt.map(std::ptr::NonNull::from)
+}
+
+// Option<NonNull<T>>を、Noneをヌルとした生ポインタに戻す
+fn nonnull_to_raw<T>(opt: Option<std::ptr::NonNull<T>>) -> *const T {
+    match opt {
+        None => std::ptr::null(),
+        Some(ptr) => ptr.as_ptr()
+    }
+}
+
+// 安全性: ptrは生きているTの値を指し、返した参照の生存期間'aの間、その値を書き換える者がいてはならない
+unsafe fn nonnull_to_ref<'a, T>(ptr: std::ptr::NonNull<T>) -> &'a T {
+    ptr.as_ref()
+}
+
+// 安全性: ptrは生きているTの値を指し、返した参照の生存期間'aの間、他にその値を読み書きする者がいてはならない
+unsafe fn nonnull_to_mut<'a, T>(mut ptr: std::ptr::NonNull<T>) -> &'a mut T {
+    ptr.as_mut()
+}
+
+// 2つのrawポインタを仮引数で受け取り、leftがrightから何要素先にあるかを返す
+// アドレスを整数にして引き算すると、別々の割り当てを指すポインタでも何かしらの値が出てしまうが、
+// そのような距離には意味がない。offset_fromはその前提を呼び出し側に約束させる
+//
+// 安全性: 呼び出し側は次をすべて満たさなければならない
+// ・leftとrightが同じ割り当て（同じ配列やVecの領域）の中か、その終端の1つ先を指している
+// ・2つのアドレスの差がsize_of::<T>()のちょうど整数倍になっている
+// ・Tのサイズが0でない（0ならoffset_fromはpanicを起こす）
+unsafe fn element_distance<T>(left: *const T, right: *const T) -> isize {
+    left.offset_from(right)
+}
+
+// elementがsliceの要素を指していれば、その添字を返す
+// アドレスの範囲を比べるだけなので、sliceと無関係な参照を渡しても未定義動作にはならず、Noneが返る
+// 終端の1つ先を指す参照や、要素の境目からずれた参照もNoneになる
+// サイズが0の型は要素がすべて同じアドレスになって区別できないので、いつもNoneを返す
+fn element_offset<T>(slice: &[T], element: &T) -> Option<usize> {
+    let size = std::mem::size_of::<T>();
+    if size == 0 {
+        return None;
+    }
+    let range = slice.as_ptr_range();
+    let element = element as *const T;
+    if !range.contains(&element) {
+        return None;
+    }
+    let bytes = element.addr() - range.start.addr();
+    if bytes.is_multiple_of(size) {
+        Some(bytes / size)
+    } else {
+        None
+    }
+}
+
+// leftとrightが両方ともsliceの要素を指していることを確かめてから距離を求める
+// どちらかがsliceの要素でなければ、element_offsetと同じくNoneを返す
+fn checked_distance<T>(slice: &[T], left: &T, right: &T) -> Option<isize> {
+    let left = element_offset(slice, left)?;
+    let right = element_offset(slice, right)?;
+    // 両方ともsliceの要素の先頭を指しているので、element_distanceの前提を満たす
+    Some(unsafe { element_distance(&slice[left], &slice[right]) })
+}
+
+// アドレスや大きさをアライメントの倍数に揃える計算
+// alignは2の累乗でなければならず、そうでなければpanicを起こす（定数の文脈ではコンパイルエラーになる）
+// 2の累乗ならalign - 1が下位ビットのマスクになるので、割り算を使わずにビット演算だけで済む
+mod align {
+    const fn mask(align: usize) -> usize {
+        assert!(align.is_power_of_two(), "align must be a power of two");
+        align - 1
+    }
+
+    // value以上で最小のalignの倍数。usizeに収まらなければNone
+    pub const fn align_up(value: usize, align: usize) -> Option<usize> {
+        let mask = mask(align);
+        match value.checked_add(mask) {
+            Some(sum) => Some(sum & !mask),
+            None => None
         }
+    }
+
+    // value以下で最大のalignの倍数。0は何の倍数でもあるので溢れることはない
+    pub const fn align_down(value: usize, align: usize) -> usize {
+        value & !mask(align)
+    }
+
+    pub const fn is_aligned_to(value: usize, align: usize) -> bool {
+        value & mask(align) == 0
+    }
 
-        Ok(Ascii(bytes))
+    // valueの後ろに何バイト足せばalignの倍数になるか
+    // align_upと違って、結果がusizeに収まらない時もパディングそのものは求まる
+    pub const fn padding_needed_for(value: usize, align: usize) -> usize {
+        value.wrapping_neg() & mask(align)
     }
 
-    // 引数をチェックしないコンストラクタ
-    // 呼び出し元は0x7f以下のバイトのみ引数に渡さないと未定義動作となるためunsafeキーワードでマーク
-    pub unsafe fn from_bytes_unchecked(bytes: Vec<u8>) -> Ascii {
-        Ascii(bytes)
+    // ポインタのアドレスがalignの倍数か
+    pub fn is_ptr_aligned_to<T: ?Sized>(ptr: *const T, align: usize) -> bool {
+        is_aligned_to(ptr.cast::<u8>().addr(), align)
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
-pub struct NotAsciiError(pub Vec<u8>);
+// バイト列の任意の位置にある値を、アライメントを気にせずに読み書きする
+// バイナリのヘッダを解析する時は、u32が4の倍数の位置に並んでいるとは限らない
+// &bytes[1] as *const u8 as *const u32を参照外しするとアライメント違反の未定義動作になるので、
+// ptr::read_unaligned/write_unalignedでバイト単位にコピーする。値はその環境のバイト順で読み書きする
+mod unaligned {
+    use std::fmt;
+    use std::mem::size_of;
 
-impl From<Ascii> for String {
-    fn from(ascii: Ascii) -> String {
-        unsafe {
-            // unsafeだが安全で効率的な変換
-            // well-formedなASCIIテキストはwell-formedなUTF8テキストであるのは自明なため
-            String::from_utf8_unchecked(ascii.0)
+    // どんなバイト列を読んでも有効な値になり、パディングも持たない型
+    // T: Copyだけでは足りない。boolやcharには無効なビット列があり、パディングのある構造体を書き込むと
+    // 未初期化のバイトがu8のスライスに入ってしまう
+    //
+    // 安全性: 実装する型は、すべてのビット列が有効な値で、パディングを持たないものでなければならない
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe trait Pod: Copy {}
+
+    macro_rules! impl_pod {
+        ($($t:ty)*) => { $(unsafe impl Pod for $t {})* }
+    }
+
+    impl_pod!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize f32 f64);
+
+    // 要素の間にパディングは入らない
+    unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+    // offsetから型の大きさ分のバイトが範囲に収まらなかった
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct OutOfBounds {
+        pub offset: usize,
+        pub size: usize,
+        pub len: usize
+    }
+
+    impl fmt::Display for OutOfBounds {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{} bytes at offset {} out of range for slice of length {}", self.size, self.offset, self.len)
+        }
+    }
+
+    impl std::error::Error for OutOfBounds {}
+
+    fn range<T>(len: usize, offset: usize) -> Result<std::ops::Range<usize>, OutOfBounds> {
+        let size = size_of::<T>();
+        match offset.checked_add(size) {
+            Some(end) if end <= len => Ok(offset .. end),
+            _ => Err(OutOfBounds { offset, size, len })
         }
     }
+
+    pub fn read_unaligned_at<T: Pod>(bytes: &[u8], offset: usize) -> Result<T, OutOfBounds> {
+        let range = range::<T>(bytes.len(), offset)?;
+        // 範囲はsize_of::<T>()バイトあり、TはPodなのでどんな中身でも有効な値になる
+        Ok(unsafe { bytes[range].as_ptr().cast::<T>().read_unaligned() })
+    }
+
+    pub fn write_unaligned_at<T: Pod>(bytes: &mut [u8], offset: usize, value: T) -> Result<(), OutOfBounds> {
+        let range = range::<T>(bytes.len(), offset)?;
+        // TはPodなのでパディングがなく、書き込んだバイトはすべて初期化されている
+        unsafe { bytes[range].as_mut_ptr().cast::<T>().write_unaligned(value) };
+        Ok(())
+    }
 }
 
-fn very_trustworthy(shared: &i32) {
-    unsafe {
-        // 引数で受け取った共有ポインタを可変ポインタに変換し、書き換えている（未定義動作）
-        let mutable = shared as *const i32 as *mut i32;
-        *mutable = 20;
+// メモリマップドI/Oのレジスタのように、読み書きそのものに意味がある場所を扱うセル
+//
+// volatileの読み書きが保証すること
+// ・プログラムに書いた回数だけ、書いた順に実際の読み書きが起きる。コンパイラは省いたりまとめたり、他のvolatileな操作と入れ替えたりしない
+// ・書いたすぐ後に読んでも、書いた値を覚えておいて使い回したりせず、もう一度メモリから読む
+// 保証しないこと
+// ・不可分性。Tの大きさによっては複数回の読み書きに分かれ得る
+// ・他のスレッドとの同期や、volatileでない読み書きとの順序。それにはアトミック型やフェンスを使う
+// そのためSyncにはしない（UnsafeCellを持つので自動的にそうなる）
+mod volatile {
+    use std::cell::UnsafeCell;
+
+    #[repr(transparent)]
+    pub struct VolatileCell<T> {
+        value: UnsafeCell<T>
+    }
+
+    impl<T: Copy> VolatileCell<T> {
+        pub fn new(value: T) -> VolatileCell<T> {
+            VolatileCell { value: UnsafeCell::new(value) }
+        }
+
+        // レジスタの番地をこの型の参照として見せるのに使う
+        // 安全性: ptrは'aの間、有効でアライメントの揃ったTの場所を指していなければならない
+        #[allow(clippy::missing_safety_doc)]
+        pub unsafe fn from_ptr<'a>(ptr: *mut T) -> &'a VolatileCell<T> {
+            // repr(transparent)なので、VolatileCell<T>はTと同じレイアウトになる
+            &*(ptr as *const VolatileCell<T>)
+        }
+
+        pub fn read(&self) -> T {
+            unsafe { std::ptr::read_volatile(self.value.get()) }
+        }
+
+        pub fn write(&self, value: T) {
+            unsafe { std::ptr::write_volatile(self.value.get(), value) }
+        }
+
+        // 読んだ値をfで変換して書き戻す。読みと書きの間に割り込まれない保証はない
+        pub fn update<F: FnOnce(T) -> T>(&self, f: F) {
+            self.write(f(self.read()));
+        }
     }
 }
 
-fn option_to_raw<T>(opt: Option<&T>) -> *const T {
-    match opt {
-        None => std::ptr::null(),
-        Some(r) => r as *const T
+// ファットポインタ（&[T]や&dyn Trait）をデータのポインタとメタデータに分け、また組み立て直す
+// スライスのメタデータは要素数、トレイトオブジェクトのメタデータはvtableへのポインタになる
+// ptr::metadataやptr::from_raw_partsはまだ安定版にないので、スライスはslice_from_raw_partsで、
+// トレイトオブジェクトはtransmute_copyで2語を取り出して扱う
+mod fatptr {
+    use std::mem::{size_of, transmute_copy};
+
+    // *const Tが2語のファットポインタか
+    pub const fn is_fat<T: ?Sized>() -> bool {
+        size_of::<*const T>() == 2 * size_of::<usize>()
+    }
+
+    pub fn split_slice<T>(ptr: *const [T]) -> (*const T, usize) {
+        (ptr.cast::<T>(), ptr.len())
+    }
+
+    // 生ポインタを作るだけなので安全。参照外しする時に、dataからlen個の要素が有効であることを保証する
+    pub fn join_slice<T>(data: *const T, len: usize) -> *const [T] {
+        std::ptr::slice_from_raw_parts(data, len)
+    }
+
+    // トレイトオブジェクトへのポインタの2語
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct TraitObject {
+        pub data: *const (),
+        pub vtable: *const ()
+    }
+
+    // トレイトオブジェクトへのポインタを2語に分ける
+    // ファットポインタの語の並びは言語として保証されていないが、今のコンパイラはデータ、メタデータの順に並べる
+    // 並びが変わったら気付けるよう、取り出したdataが本当にデータのアドレスかを確かめる
+    // ファットポインタでない型を渡すとpanicを起こす
+    //
+    // 安全性: Tはdyn Traitのようなトレイトオブジェクトの型でなければならない
+    // &[T]や&strもファットポインタなので検査をすり抜け、要素数がvtableの欄に入ってしまう
+    // 返したvtableはvtable_headerやjoin_dynがvtableへのポインタとして読むので、この関数の時点で型を限っておく
+    // （stableにはトレイトオブジェクトだけを受け付ける境界（Pointee<Metadata = DynMetadata<_>>）がまだ無い）
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn split_dyn<T: ?Sized>(ptr: *const T) -> TraitObject {
+        assert!(is_fat::<T>(), "{} is not a fat pointer", std::any::type_name::<*const T>());
+        let words: [*const (); 2] = unsafe { transmute_copy(&ptr) };
+        assert_eq!(words[0], ptr.cast::<()>(), "unexpected fat pointer layout");
+        TraitObject { data: words[0], vtable: words[1] }
+    }
+
+    // 安全性: Tはトレイトオブジェクトの型で、partsは同じ型Tのポインタをsplit_dynで分けたものでなければならない
+    // 別のvtableや別の型のvtableと組み合わせると、メソッドの呼び出しで未定義動作になる
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn join_dyn<T: ?Sized>(parts: TraitObject) -> *const T {
+        assert!(is_fat::<T>(), "{} is not a fat pointer", std::any::type_name::<*const T>());
+        transmute_copy(&[parts.data, parts.vtable])
+    }
+
+    // vtableの先頭に並ぶ、どのトレイトでも共通の3つの欄
+    // 今のrustcは、ドロップ処理の関数、値のサイズ、アライメントの順に並べ、その後ろに各メソッドの関数ポインタを置く
+    // ドロップ処理の要らない型では、ドロップ処理の欄はヌルになる
+    // これも言語として保証されたレイアウトではないので、vtable_headerで値のサイズ・アラインメントと照らし合わせる
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct VTableHeader {
+        pub drop_in_place: Option<unsafe fn(*mut ())>,
+        pub size: usize,
+        pub align: usize
+    }
+
+    // トレイトオブジェクトのvtableの先頭を読む
+    // 読んだサイズ・アライメントがsize_of_val・align_of_valと一致しなければ、レイアウトが想定と違うのでpanicを起こす
+    //
+    // 安全性: Tはトレイトオブジェクトの型でなければならない
+    // &[T]を渡すと要素数（0x3のような値）をvtableのアドレスとして参照外しし、検査の前に未定義動作になる
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn vtable_header<T: ?Sized>(object: &T) -> &'static VTableHeader {
+        let parts = split_dyn(object);
+        // Tがトレイトオブジェクトなら、vtableはプログラムの終わりまで読み取り専用の領域にある
+        let header = &*(parts.vtable as *const VTableHeader);
+        assert!(header.size == std::mem::size_of_val(object) && header.align == std::mem::align_of_val(object),
+                "unexpected vtable layout");
+        header
     }
 }
 
-fn distance<T>(left: *const T, right: *const T) -> isize {
-    // 2つのrawポインタを仮引数で受け取り、両ポインタ間のメモリアドレスの距離を返す
-    (left as isize - right as isize) / std::mem::size_of::<T>() as isize
+// 構造体へのポインタとフィールドへのポインタを行き来する
+// フィールドのオフセットはcore::mem::offset_of!（Rust 1.77から）で求める
+// それより前のRustでは、MaybeUninitで作った未初期化の値の上でフィールドの番地を測るしかなかった
+#[macro_use]
+mod field {
+    // offset_of!がなかった頃のやり方
+    // 未初期化の値を読まないよう、参照ではなくaddr_of!でフィールドの番地だけを求める
+    // (*base).fieldは、$tがDerefを実装していると別の値のフィールドを指してしまうので、
+    // 構造体のパターンで$fieldが$tのフィールドそのものであることを確かめる
+    macro_rules! offset_of_uninit {
+        ($t:path, $field:ident) => {{
+            #[allow(unused_variables)]
+            let check = |value: $t| {
+                let $t { $field: _, .. } = value;
+            };
+            let uninit = core::mem::MaybeUninit::<$t>::uninit();
+            let base = uninit.as_ptr();
+            // baseは$tの大きさの割り当てを指していて、フィールドの番地はその中に収まる
+            unsafe {
+                let field = core::ptr::addr_of!((*base).$field);
+                field.cast::<u8>().offset_from(base.cast::<u8>()) as usize
+            }
+        }};
+    }
+
+    // *mut $tから、そのフィールドを指す*mut型のポインタを作る
+    // 番地を計算するだけなので安全。読み書きする時に、baseが生きている$tを指していることを保証する
+    macro_rules! project_field_raw {
+        ($base:expr, $t:ty, $field:ident) => {
+            $crate::field::project_raw::<$t, _, _>($base, core::mem::offset_of!($t, $field), |value: &$t| &value.$field)
+        };
+    }
+
+    // フィールドを指すポインタから、そのフィールドを持つ$tを指すポインタに戻す
+    // 侵入型のリストのように、ノードに埋め込んだリンクからノードそのものを求めるのに使う
+    // 戻したポインタを読み書きしてよいのは、ptrの出自が$t全体に及ぶ時（$t全体を指すポインタから作った時）だけ
+    macro_rules! container_of {
+        ($ptr:expr, $t:ty, $field:ident) => {
+            $crate::field::container_raw::<$t, _, _>($ptr, core::mem::offset_of!($t, $field), |value: &$t| &value.$field)
+        };
+    }
+
+    // 引数のクロージャは呼ばず、フィールドの型をFとして推論させるためだけに受け取る
+    pub fn project_raw<T, F, G: FnOnce(&T) -> &F>(base: *mut T, offset: usize, _project: G) -> *mut F {
+        base.wrapping_byte_add(offset).cast()
+    }
+
+    pub fn container_raw<T, F, G: FnOnce(&T) -> &F>(field: *mut F, offset: usize, _project: G) -> *mut T {
+        field.wrapping_byte_sub(offset).cast()
+    }
 }
 
-mod ref_with_flag {
+// 割り当ての範囲を覚えていて、範囲外への移動や参照外しをErrにするポインタ
+// 生ポインタのoffsetは範囲外に出た時点で未定義動作だが、こちらは添字で位置を持つので何度でも安全に試せる
+// 参照を外に渡さず、読み書きは値のコピーで行うので、複製したポインタ同士で同じ要素を指していても別名の問題は起きない
+mod bounded_ptr {
+    use std::fmt;
     use std::marker::PhantomData;
-    use std::mem::align_of;
 
-    // 古典的なbit操作をRustで安全にラップした型
-    // 型Tは少なくとも2バイト単位でアライメントされているものでなければならない
-    pub struct RefWithFlag<'a, T:'a> {
-        ptr_and_bit: usize,
-        behaves_like: PhantomData<&'a T>
+    // ログに渡す、参照外しの記録
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Access {
+        Read(usize),
+        Write(usize)
+    }
+
+    // 位置が割り当ての範囲を外れた。indexは外れた先の位置
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct OutOfBounds {
+        pub index: isize,
+        pub len: usize
+    }
+
+    impl fmt::Display for OutOfBounds {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "index {} out of bounds for allocation of length {}", self.index, self.len)
+        }
+    }
+
+    impl std::error::Error for OutOfBounds {}
+
+    pub struct BoundedPtr<'a, T> {
+        start: *mut T,
+        len: usize,
+        // 0..=lenのどこか。lenは終端の1つ先で、移動はできるが参照外しはできない
+        index: usize,
+        logger: Option<&'a dyn Fn(Access)>,
+        borrows: PhantomData<&'a mut [T]>
+    }
+
+    impl<'a, T> Clone for BoundedPtr<'a, T> {
+        fn clone(&self) -> BoundedPtr<'a, T> {
+            *self
+        }
+    }
+
+    impl<'a, T> Copy for BoundedPtr<'a, T> {}
+
+    impl<'a, T> fmt::Debug for BoundedPtr<'a, T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("BoundedPtr")
+                .field("ptr", &self.start.wrapping_add(self.index))
+                .field("index", &self.index)
+                .field("len", &self.len)
+                .finish()
+        }
     }
 
-    impl<'a, T:'a> RefWithFlag<'a, T> {
-        pub fn new(ptr: &'a T, flag: bool) -> RefWithFlag<T> {
-            assert!(align_of:: <T>() % 2 == 0); // 最下位ビットがゼロであるか検証してからrawポインタに変換
-            RefWithFlag {
-                // 参照->rawポインタ->usizeに変換（usizeはどんな計算機でもポインタ型を保持するのに十分なサイズ）
-                ptr_and_bit: ptr as *const T as usize | flag as usize,
-                // メモリを消費しないゼロサイズの型（生存期間をどう扱うかRustコンパイラに教えるために必要なフィールドで、これが無いとコンパイルできない）
-                behaves_like: PhantomData
+    impl<'a, T: Copy> BoundedPtr<'a, T> {
+        // sliceの先頭を指すポインタを作る。sliceは'aの間このポインタが借用し続ける
+        pub fn new(slice: &'a mut [T]) -> BoundedPtr<'a, T> {
+            BoundedPtr { start: slice.as_mut_ptr(), len: slice.len(), index: 0, logger: None, borrows: PhantomData }
+        }
+
+        // 読み書きのたびにloggerを呼ぶポインタを返す
+        pub fn with_logger(self, logger: &'a dyn Fn(Access)) -> BoundedPtr<'a, T> {
+            BoundedPtr { logger: Some(logger), ..self }
+        }
+
+        pub fn index(&self) -> usize {
+            self.index
+        }
+
+        // 今の位置の番地。範囲内か終端の1つ先なので、作るだけなら未定義動作にならない
+        pub fn as_ptr(&self) -> *const T {
+            self.start.wrapping_add(self.index)
+        }
+
+        // count要素だけ動かしたポインタを返す。行き先は先頭から終端の1つ先まで
+        pub fn offset(self, count: isize) -> Result<BoundedPtr<'a, T>, OutOfBounds> {
+            let error = OutOfBounds { index: (self.index as isize).saturating_add(count), len: self.len };
+            match self.index.checked_add_signed(count) {
+                Some(index) if index <= self.len => Ok(BoundedPtr { index, ..self }),
+                _ => Err(error)
             }
         }
 
-        pub fn get_ref(&self) -> &'a T {
-            unsafe {
-                let ptr = (self.ptr_and_bit & !1) as *const T;
-                &*ptr
+        fn check(&self, access: Access) -> Result<*mut T, OutOfBounds> {
+            if self.index >= self.len {
+                return Err(OutOfBounds { index: self.index as isize, len: self.len });
+            }
+            if let Some(logger) = self.logger {
+                logger(access);
             }
+            Ok(self.start.wrapping_add(self.index))
         }
 
-        pub fn get_flag(&self) -> bool {
-            // 最下位ビットをマスクしてゼロかを返す
-            self.ptr_and_bit & 1 != 0
+        pub fn read(&self) -> Result<T, OutOfBounds> {
+            let ptr = self.check(Access::Read(self.index))?;
+            // 範囲内で、newで借用したsliceの要素を指している
+            Ok(unsafe { ptr.read() })
+        }
+
+        pub fn write(&self, value: T) -> Result<(), OutOfBounds> {
+            let ptr = self.check(Access::Write(self.index))?;
+            unsafe { ptr.write(value) };
+            Ok(())
         }
     }
 }
 
-mod gap {
-    use std;
-    use std::ops::Range;
+// 構造体のサイズ、アライメント、各フィールドの位置とパディングを調べる
+// layout_of!(型 { フィールド, ... })で、列挙したフィールドのLayoutReportを作る
+#[macro_use]
+mod layout {
+    use std::fmt;
 
-    // charの値を予備領域と一緒に保持する型
-    pub struct GapBuffer<T> {
-        // 格納場所
-        // 必要とする容量を持つが、長さは常にゼロとなる
-        storage: Vec<T>,
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct FieldLayout {
+        pub name: &'static str,
+        pub offset: usize,
+        pub size: usize
+    }
 
-        // storage内で初期化されていない範囲
-        gap: Range<usize>
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct LayoutReport {
+        name: &'static str,
+        size: usize,
+        align: usize,
+        // オフセットの順に並べておく。宣言の順とは限らない
+        fields: Vec<FieldLayout>
     }
 
-    impl<T> GapBuffer<T> {
-        pub fn new() -> GapBuffer<T> {
-            GapBuffer {
-                storage: Vec::new(),
-                gap: 0..0
-            }
-        }
+    // フィールドを指す生ポインタを返す関数から、フィールドの型Fの大きさを求める
+    // 関数は呼ばないので、repr(packed)のようにフィールドへの参照を作れない構造体でも使える
+    pub fn field_size<T, F>(_project: fn(*const T) -> *const F) -> usize {
+        std::mem::size_of::<F>()
+    }
 
-        // GapBufferが再確保せず保持できる要素数を返す
-        pub fn capacity(&self) -> usize {
-            self.storage.capacity()
+    macro_rules! layout_of {
+        ($t:ty { $($field:tt),* $(,)? }) => {
+            $crate::layout::LayoutReport::new(
+                stringify!($t),
+                std::mem::size_of::<$t>(),
+                std::mem::align_of::<$t>(),
+                vec![$($crate::layout::FieldLayout {
+                    name: stringify!($field),
+                    offset: core::mem::offset_of!($t, $field),
+                    // このクロージャはfield_sizeの中で型を推論させるためだけのもので、呼ばれることはない
+                    size: $crate::layout::field_size(|base: *const $t| unsafe { core::ptr::addr_of!((*base).$field) })
+                }),*]
+            )
+        };
+    }
+
+    impl LayoutReport {
+        // 型の大きさはいつもアライメントの倍数になる。alignが2の累乗でなければpanicを起こす
+        pub fn new(name: &'static str, size: usize, align: usize, mut fields: Vec<FieldLayout>) -> LayoutReport {
+            assert!(super::align::is_aligned_to(size, align), "size {} is not a multiple of align {}", size, align);
+            fields.sort_by_key(|field| field.offset);
+            LayoutReport { name, size, align, fields }
         }
 
-        // 現在このGapBufferが保持している要素数を返す
-        pub fn len(&self) -> usize {
-            self.capacity() - self.gap.len()
+        pub fn size(&self) -> usize {
+            self.size
         }
 
-        // 現在の挿入点を返す
-        pub fn position(&self) -> usize {
-            self.gap.start
+        pub fn align(&self) -> usize {
+            self.align
         }
 
-        // ギャップを気にせず内部ストレージindex番目要素へのポインタを返す
-        unsafe fn space(&self, index: usize) -> *const T {
-            self.storage.as_ptr().offset(index as isize)
+        pub fn fields(&self) -> &[FieldLayout] {
+            &self.fields
         }
 
-        // ギャップを気にせず内部ストレージindex番目要素への可変ポインタを返す
-        unsafe fn space_mut(&mut self, index: usize) -> *mut T {
-            self.storage.as_mut_ptr().offset(index as isize)
+        pub fn field(&self, name: &str) -> Option<&FieldLayout> {
+            self.fields.iter().find(|field| field.name == name)
         }
 
-        // ギャップを計算に入れて内部ストレージのindex番目要素へのポインタを返す
-        fn index_to_raw(&self, index: usize) -> usize {
-            if index < self.gap.start {
-                index
-            } else {
-                index + self.gap.len()
+        // フィールドに使われていないバイトの範囲。最後のフィールドの後ろの詰め物も含む
+        pub fn padding_ranges(&self) -> Vec<std::ops::Range<usize>> {
+            let mut ranges = Vec::new();
+            let mut end = 0;
+            for field in &self.fields {
+                if field.offset > end {
+                    ranges.push(end .. field.offset);
+                }
+                end = end.max(field.offset + field.size);
+            }
+            if self.size > end {
+                ranges.push(end .. self.size);
             }
+            ranges
         }
 
-        // index番目の要素への参照を返す
-        // indexが範囲外ならNoneを返す
-        pub fn get(&self, index: usize) -> Option<&T> {
-            let raw = self.index_to_raw(index);
-            // rawをself.capacity()に対してチェックした
-            // index_to_rawはギャップをスキップするので安全
-            if raw < self.capacity() {
-                unsafe {
-                    Some(&*self.space(raw))
-                }
-            } else {
-                None
+        pub fn padding(&self) -> usize {
+            self.padding_ranges().iter().map(|range| range.len()).sum()
+        }
+    }
+
+    // オフセットの順に、フィールドとパディングを1行ずつ並べる
+    impl fmt::Display for LayoutReport {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            writeln!(f, "{}: size {}, align {}, padding {}", self.name, self.size, self.align, self.padding())?;
+            let padding = self.padding_ranges();
+            let mut rows: Vec<(usize, String)> = self.fields.iter()
+                .map(|field| (field.offset, format!("{}..{} {} ({})", field.offset, field.offset + field.size, field.name, field.size)))
+                .chain(padding.iter().map(|range| (range.start, format!("{}..{} padding ({})", range.start, range.end, range.len()))))
+                .collect();
+            rows.sort_by_key(|row| row.0);
+            for (_, row) in rows {
+                writeln!(f, "  {}", row)?;
             }
+            Ok(())
         }
+    }
+}
+
+// repr(packed)の構造体のフィールドを読み書きする
+// repr(packed)はパディングを詰めるので、u32のフィールドが4の倍数の番地に来るとは限らない
+// 参照はアライメントの揃った番地を指していなければならないので、&header.lengthのような参照を作るだけで未定義動作になる
+// （今のコンパイラはエラーにする）。代わりにaddr_of!で参照を経ずに生ポインタを作り、read_unaligned/write_unalignedで読み書きする
+#[macro_use]
+mod packed {
+    // フィールドごとに、値をコピーして返すgetterと書き込むsetterを作る
+    // packed_accessors!(型 { フィールド, setter: フィールドの型; ... })
+    macro_rules! packed_accessors {
+        ($t:ty { $($field:ident, $setter:ident: $field_ty:ty;)* }) => {
+            impl $t {
+                $(
+                    pub fn $field(&self) -> $field_ty {
+                        // addr_of!は参照を作らないので、揃っていない番地でも問題ない
+                        unsafe { core::ptr::addr_of!(self.$field).read_unaligned() }
+                    }
 
-        // 現在の挿入点を引数posに動かす
-        // もしposが範囲外であればpanicを起こす
-        pub fn set_position(&mut self, pos: usize) {
-            if pos > self.len() {
-                panic!("index {} out of range for GapBuffer", pos);
+                    pub fn $setter(&mut self, value: $field_ty) {
+                        unsafe { core::ptr::addr_of_mut!(self.$field).write_unaligned(value) }
+                    }
+                )*
             }
+        };
+    }
+}
 
-            unsafe {
-                let gap = self.gap.clone();
-                if pos > gap.start {
-                    // posはギャップの後ろにある
-                    // ギャップの後ろの要素をギャップの前に動かして、ギャップを右にずらす
-                    let distance = pos - gap.start;
-                    std::ptr::copy(self.space(gap.end),
-                                   self.space_mut(gap.start),
-                                   distance);
-                } else if pos < gap.start {
-                    // posはギャップの前にある
-                    // ギャップの前の要素をギャップの後ろに動かして、ギャップを左にずらす
-                    let distance = gap.start - pos;
-                    std::ptr::copy(self.space(pos),
-                                   self.space_mut(gap.end - distance),
-                                   distance);
+// バイト列から整数をリトルエンディアン/ビッグエンディアンで読む
+// 範囲を確かめる安全な版と、確かめずに生ポインタから直接読むunsafeな版を用意する
+// 安全な版はスライスと配列への変換だけで書けるので、unsafeな版の答え合わせに使える
+mod endian {
+    use std::convert::TryInto;
+    use std::mem::size_of;
+
+    macro_rules! readers {
+        ($($t:ty => $le:ident, $be:ident, $le_unchecked:ident, $be_unchecked:ident;)*) => {
+            $(
+                pub fn $le(bytes: &[u8], offset: usize) -> Option<$t> {
+                    let end = offset.checked_add(size_of::<$t>())?;
+                    Some(<$t>::from_le_bytes(bytes.get(offset..end)?.try_into().ok()?))
                 }
 
-            self.gap = pos .. pos + gap.len();
+                pub fn $be(bytes: &[u8], offset: usize) -> Option<$t> {
+                    let end = offset.checked_add(size_of::<$t>())?;
+                    Some(<$t>::from_be_bytes(bytes.get(offset..end)?.try_into().ok()?))
+                }
+
+                // 安全性: offsetから型の大きさ分のバイトがbytesに収まっていなければならない
+                // 番地が揃っているとは限らないので、read_unalignedで読んでからバイト順を直す
+                #[allow(clippy::missing_safety_doc)]
+                pub unsafe fn $le_unchecked(bytes: &[u8], offset: usize) -> $t {
+                    debug_assert!(offset <= bytes.len() && bytes.len() - offset >= size_of::<$t>());
+                    <$t>::from_le(bytes.as_ptr().add(offset).cast::<$t>().read_unaligned())
+                }
+
+                #[allow(clippy::missing_safety_doc)]
+                pub unsafe fn $be_unchecked(bytes: &[u8], offset: usize) -> $t {
+                    debug_assert!(offset <= bytes.len() && bytes.len() - offset >= size_of::<$t>());
+                    <$t>::from_be(bytes.as_ptr().add(offset).cast::<$t>().read_unaligned())
+                }
+            )*
+
+            impl<'a> ByteReader<'a> {
+                $(
+                    // 範囲を1度だけ確かめ、読み込みは確かめないunsafeな版に任せる
+                    pub fn $le(&mut self) -> Option<$t> {
+                        let start = self.advance(size_of::<$t>())?;
+                        Some(unsafe { $le_unchecked(self.bytes, start) })
+                    }
+
+                    pub fn $be(&mut self) -> Option<$t> {
+                        let start = self.advance(size_of::<$t>())?;
+                        Some(unsafe { $be_unchecked(self.bytes, start) })
+                    }
+                )*
             }
+        };
+    }
+
+    readers! {
+        u16 => read_u16_le, read_u16_be, read_u16_le_unchecked, read_u16_be_unchecked;
+        u32 => read_u32_le, read_u32_be, read_u32_le_unchecked, read_u32_be_unchecked;
+        u64 => read_u64_le, read_u64_be, read_u64_le_unchecked, read_u64_be_unchecked;
+        i16 => read_i16_le, read_i16_be, read_i16_le_unchecked, read_i16_be_unchecked;
+        i32 => read_i32_le, read_i32_be, read_i32_le_unchecked, read_i32_be_unchecked;
+        i64 => read_i64_le, read_i64_be, read_i64_le_unchecked, read_i64_be_unchecked;
+    }
+
+    // バイト列を先頭から順に読み進めるカーソル
+    // 読めなかった時はNoneを返し、位置は動かさない
+    #[derive(Clone, Debug)]
+    pub struct ByteReader<'a> {
+        bytes: &'a [u8],
+        pos: usize
+    }
+
+    impl<'a> ByteReader<'a> {
+        pub fn new(bytes: &'a [u8]) -> ByteReader<'a> {
+            ByteReader { bytes, pos: 0 }
         }
 
-        pub fn remove(&mut self) -> Option<T> {
-            if self.gap.end == self.capacity() {
-                return None;
-            }
+        pub fn position(&self) -> usize {
+            self.pos
+        }
 
-            // ギャップ直後の値をバッファから取り出す
-            let element = unsafe {
-                std::ptr::read(self.space(self.gap.end))
-            };
-            self.gap.end += 1;
-            Some(element)
+        pub fn remaining(&self) -> usize {
+            self.bytes.len() - self.pos
+        }
+
+        pub fn read_u8(&mut self) -> Option<u8> {
+            let start = self.advance(1)?;
+            Some(self.bytes[start])
+        }
+
+        // lenバイトをまとめて借りる
+        pub fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+            let start = self.advance(len)?;
+            Some(&self.bytes[start..self.pos])
+        }
+
+        pub fn skip(&mut self, len: usize) -> Option<()> {
+            self.advance(len).map(|_| ())
         }
 
-        // 引数eltを現在の挿入点に挿入し、挿入点を1つ後ろにずらす
-        pub fn insert(&mut self, elt: T) {
-            if self.gap.len() == 0 {
-                self.enlarge_gap();
+        // lenバイト残っていれば位置を進め、進める前の位置を返す
+        fn advance(&mut self, len: usize) -> Option<usize> {
+            if len > self.remaining() {
+                return None;
             }
+            let start = self.pos;
+            self.pos += len;
+            Some(start)
+        }
+    }
+}
 
-            unsafe {
-                let index = self.gap.start;
-                std::ptr::write(self.space_mut(index), elt);
+// Box<dyn Any>を自分で組み立てる
+// 値をヒープに置いて型を忘れ、生ポインタと一緒にTypeIdとドロップ用の関数を持っておく
+mod any_box {
+    use std::any::TypeId;
+    use std::fmt;
+    use std::ptr::NonNull;
+
+    // 不変条件: ptrは、TypeIdがtype_idであるただ1つの型Tについて、Box::<T>::into_rawが返したポインタである
+    // drop_valueはそのTについてのdrop_box::<T>である
+    // TypeIdは型ごとに異なるので、type_idが一致すればptrを*mut Tに戻してよい。これがダウンキャストの根拠になる
+    // T: 'staticに限るのは、TypeIdがライフタイムを区別せず、&'a strと&'static strを取り違えてしまうため
+    pub struct AnyBox {
+        ptr: NonNull<()>,
+        type_id: TypeId,
+        type_name: &'static str,
+        drop_value: unsafe fn(NonNull<()>)
+    }
+
+    // 型を忘れたポインタを、元のBox<T>に戻してドロップする
+    unsafe fn drop_box<T>(ptr: NonNull<()>) {
+        drop(Box::from_raw(ptr.cast::<T>().as_ptr()));
+    }
+
+    impl AnyBox {
+        pub fn new<T: 'static>(value: T) -> AnyBox {
+            AnyBox {
+                ptr: NonNull::from(Box::leak(Box::new(value))).cast(),
+                type_id: TypeId::of::<T>(),
+                type_name: std::any::type_name::<T>(),
+                drop_value: drop_box::<T>
             }
+        }
 
-            self.gap.start += 1;
+        pub fn is<T: 'static>(&self) -> bool {
+            self.type_id == TypeId::of::<T>()
         }
 
-        // iterableが生成する要素を現在の挿入位置に挿入し、挿入点をその後ろにずらす
-        pub fn insert_iter<I>(&mut self, iterable: I)
-            where I: IntoIterator<Item=T>
-        {
-            for item in iterable {
-                self.insert(item);
-            }
+        pub fn type_name(&self) -> &'static str {
+            self.type_name
         }
 
-        // self.storageの容量を倍にする
-        fn enlarge_gap(&mut self) {
-            let mut new_capacity = self.capacity() * 2;
-            if new_capacity == 0 {
-                // 空だった時は適当な初期容量を設定
-                new_capacity = 4;
+        pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+            if self.is::<T>() {
+                // 不変条件から、ptrは生きているTを指している
+                Some(unsafe { self.ptr.cast::<T>().as_ref() })
+            } else {
+                None
             }
+        }
 
-            // Vecをリサイズした時に「使っていない」領域に何が起きるかわからない
-            // そこで新しいVectorを作って中の要素を移動する
-            let mut new = Vec::with_capacity(new_capacity);
-            let after_gap = self.capacity() - self.gap.end;
-            let new_gap = self.gap.start .. new.capacity() - after_gap;
-            unsafe {
-                // ギャップの前の要素を移動
-                std::ptr::copy_nonoverlapping(self.space(0),
-                                           new.as_mut_ptr(),
-                                           self.gap.start);
-                // ギャップの後ろの要素を移動
-                let new_gap_end = new.as_mut_ptr().offset(new_gap.end as isize);
-                std::ptr::copy_nonoverlapping(self.space(self.gap.end),
-                                           new_gap_end,
-                                           after_gap);
+        pub fn downcast_mut<T: 'static>(&mut self) -> Option<&mut T> {
+            if self.is::<T>() {
+                Some(unsafe { self.ptr.cast::<T>().as_mut() })
+            } else {
+                None
             }
+        }
 
-            // これで古いVecが解放されるが要素はドロップされない
-            // 古いVecの長さはゼロだったので
-            self.storage = new;
-            self.gap = new_gap;
+        // 型が合えば中身をBox<T>として取り出す。合わなければselfをそのまま返す
+        pub fn downcast<T: 'static>(self) -> Result<Box<T>, AnyBox> {
+            if !self.is::<T>() {
+                return Err(self);
+            }
+            let ptr = self.ptr.cast::<T>();
+            // 所有権はBox<T>に移るので、selfのDropでもう一度ドロップしないようにする
+            std::mem::forget(self);
+            Ok(unsafe { Box::from_raw(ptr.as_ptr()) })
         }
     }
 
-    impl<T> Drop for GapBuffer<T> {
+    impl Drop for AnyBox {
         fn drop(&mut self) {
-            // GapBufferがドロップされた時は全ての要素がドロップされることを保証しなければならない
-            unsafe {
-                for i in 0 .. self.gap.start {
-                    std::ptr::drop_in_place(self.space_mut(i));
-                }
+            unsafe { (self.drop_value)(self.ptr) }
+        }
+    }
 
-                for i in self.gap.end .. self.capacity() {
-                    std::ptr::drop_in_place(self.space_mut(i));
-                }
-            }
+    impl fmt::Debug for AnyBox {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "AnyBox<{}>", self.type_name)
         }
     }
 }
 
-fn main() {
-    let mut a: usize = 0;
-    let ptr = &mut a as *mut usize;
-    unsafe {
-        *ptr.offset(3) = 0x7ffff72f484c;
+// Box<dyn io::Write>を手で組み立てる
+// コンパイラが型ごとに作るvtableを#[repr(C)]の関数ポインタの表として自分で書き、
+// 型を忘れたデータへのポインタと組にして持ち、メソッド呼び出しを表の関数ポインタ経由で行う
+mod manual_vtable {
+    use std::io;
+    use std::marker::PhantomData;
+    use std::ptr::NonNull;
+
+    // 先頭の3つの欄は、rustcのvtableの先頭（fatptr::VTableHeader）と同じ並び
+    // 関数ポインタはどれも、dataがその表を作った型Wの生きている値を指している時だけ呼んでよい
+    #[repr(C)]
+    pub struct WriterVTable {
+        pub drop_in_place: unsafe fn(*mut ()),
+        pub size: usize,
+        pub align: usize,
+        pub write: unsafe fn(*mut (), &[u8]) -> io::Result<usize>,
+        pub flush: unsafe fn(*mut ()) -> io::Result<()>
     }
 
-    // ASCIIだけで構成されたバイトベクタ
-    let bytes: Vec<u8> = b"ASCII and ye shall receive".to_vec();
-    // ヒープの確保もテキストのコピーも行われない呼び出し
-    let ascii: Ascii = Ascii::from_bytes(bytes)
-        .unwrap();
-    // unsafeで実装されておりゼロコストで変換できる
-    let string = String::from(ascii);
-    assert_eq!(string, "ASCII and ye shall receive");
+    unsafe fn drop_box<W>(data: *mut ()) {
+        drop(Box::from_raw(data as *mut W));
+    }
 
-    let illegal_bytes = vec![0xf7, 0xbf, 0xbf, 0xbf];
-    let _illegal_ascii = unsafe {
-        Ascii::from_bytes_unchecked(illegal_bytes);
-    };
+    unsafe fn write<W: io::Write>(data: *mut (), buf: &[u8]) -> io::Result<usize> {
+        (*(data as *mut W)).write(buf)
+    }
 
-    // 無効なUTF8が入っている
-    // let bogus: String = _illegal_ascii.into();
-    // assert_eq!(bogus.chars().next().unwrap() as u32, 0x1ffffff);
+    unsafe fn flush<W: io::Write>(data: *mut ()) -> io::Result<()> {
+        (*(data as *mut W)).flush()
+    }
 
-    let i = 10;
-    very_trustworthy(&i);
-    println!("{}", i * 100); // 1000が期待値だが、very_trustworthy()の中で書き換えられて2000になる
+    struct VTableFor<W>(PhantomData<W>);
 
-    let mut x = 10;
-    let ptr_x = &mut x as *mut i32; // *mut T は T へのrawポインタで、参照先の変更を許す
+    impl<W: io::Write> VTableFor<W> {
+        // 定数への参照は'staticに昇格されるので、型ごとに1つの表をプログラムの終わりまで使い回せる
+        const VTABLE: WriterVTable = WriterVTable {
+            drop_in_place: drop_box::<W>,
+            size: std::mem::size_of::<W>(),
+            align: std::mem::align_of::<W>(),
+            write: write::<W>,
+            flush: flush::<W>
+        };
+    }
 
-    let y = Box::new(20);
-    let ptr_y = &*y as *const i32;  // *const T は T へのrawポインタで、参照先の読み出しのみを許す
+    // 型Wのためのvtable
+    pub fn vtable_for<W: io::Write>() -> &'static WriterVTable {
+        &VTableFor::<W>::VTABLE
+    }
 
-    unsafe {
-        *ptr_x += *ptr_y;
+    // Box<dyn io::Write + 'a>に当たる、型を忘れた書き込み先
+    // 不変条件: dataはBox::<W>::into_rawが返したポインタで、vtableはvtable_for::<W>()である
+    pub struct DynWriter<'a> {
+        data: NonNull<()>,
+        vtable: &'static WriterVTable,
+        // Wの値を所有し、Wは'aの間だけ生きている
+        owns: PhantomData<Box<dyn io::Write + 'a>>
     }
 
-    assert_eq!(x, 30); // *mut i32型のptr_xを通してポインタの指す値が更新されている
+    impl<'a> DynWriter<'a> {
+        pub fn new<W: io::Write + 'a>(writer: W) -> DynWriter<'a> {
+            DynWriter {
+                data: NonNull::from(Box::leak(Box::new(writer))).cast(),
+                vtable: vtable_for::<W>(),
+                owns: PhantomData
+            }
+        }
 
-    // 関数option_to_raw()の呼び出しにはunsafeブロックが登場しない rawポインタの参照解決だけがunsafe
-    assert!(!option_to_raw(Some(&("pea", "pod"))).is_null());
-    assert_eq!(option_to_raw::<i32>(None), std::ptr::null());
+        pub fn vtable(&self) -> &'static WriterVTable {
+            self.vtable
+        }
 
-    // 先頭の要素と最後の要素のポインタ距離をrawポインタを渡して計算させる
-    let trucks = vec!["garbage truck", "dump truck", "moonstruck"];
+        // size_of_valに当たる、中の値のサイズ
+        pub fn size_of_val(&self) -> usize {
+            self.vtable.size
+        }
+    }
+
+    impl<'a> io::Write for DynWriter<'a> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            unsafe { (self.vtable.write)(self.data.as_ptr(), buf) }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            unsafe { (self.vtable.flush)(self.data.as_ptr()) }
+        }
+    }
+
+    impl<'a> Drop for DynWriter<'a> {
+        fn drop(&mut self) {
+            unsafe { (self.vtable.drop_in_place)(self.data.as_ptr()) }
+        }
+    }
+}
+
+// 直後にアクセスできないガードページを置いたバッファ
+// 末尾を越えた読み書きは、隣の値を黙って壊す代わりに必ずSIGSEGV（macOSではSIGBUS）になる
+// バッファの末尾をガードページの先頭にぴったり合わせるので、1バイトでも越えれば捕まえられる
+#[cfg(unix)]
+mod guarded {
+    use std::io;
+    use std::ptr::NonNull;
+
+    pub struct GuardedBuf {
+        // mmapで確保した領域全体。最後の1ページがガードページになる
+        base: NonNull<u8>,
+        map_len: usize,
+        // ガードページの直前でlenバイトのバッファが終わる
+        start: NonNull<u8>,
+        len: usize
+    }
+
+    pub fn page_size() -> usize {
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    impl GuardedBuf {
+        // 0で埋めたlenバイトのバッファを確保する
+        pub fn new(len: usize) -> io::Result<GuardedBuf> {
+            let page = page_size();
+            // ページの大きさは2の累乗なので、align_upでページの倍数に切り上げる
+            let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "buffer too large");
+            let data_len = super::align::align_up(len, page).ok_or_else(too_large)?;
+            let map_len = data_len.checked_add(page).ok_or_else(too_large)?;
+            unsafe {
+                let base = libc::mmap(std::ptr::null_mut(), map_len, libc::PROT_READ | libc::PROT_WRITE,
+                                      libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0);
+                if base == libc::MAP_FAILED {
+                    return Err(io::Error::last_os_error());
+                }
+                let base = base as *mut u8;
+                let guard = base.add(data_len);
+                if libc::mprotect(guard as *mut libc::c_void, page, libc::PROT_NONE) != 0 {
+                    let error = io::Error::last_os_error();
+                    libc::munmap(base as *mut libc::c_void, map_len);
+                    return Err(error);
+                }
+                Ok(GuardedBuf {
+                    base: NonNull::new_unchecked(base),
+                    map_len,
+                    start: NonNull::new_unchecked(guard.sub(len)),
+                    len
+                })
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.len
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        pub fn as_ptr(&self) -> *const u8 {
+            self.start.as_ptr()
+        }
+
+        // 範囲外の実験に使うポインタ。len以降を指すようにずらすとガードページに当たる
+        pub fn as_mut_ptr(&mut self) -> *mut u8 {
+            self.start.as_ptr()
+        }
+
+        pub fn as_slice(&self) -> &[u8] {
+            unsafe { std::slice::from_raw_parts(self.start.as_ptr(), self.len) }
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [u8] {
+            unsafe { std::slice::from_raw_parts_mut(self.start.as_ptr(), self.len) }
+        }
+    }
+
+    impl Drop for GuardedBuf {
+        fn drop(&mut self) {
+            unsafe {
+                libc::munmap(self.base.as_ptr() as *mut libc::c_void, self.map_len);
+            }
+        }
+    }
+
+    // fを子プロセスで実行し、メモリ保護違反のシグナルで落ちたかどうかを返す
+    // 違反したプロセスはもう続けられないので、forkした子プロセスに身代わりで落ちてもらう
+    // 子プロセスでは、fが終わったら後始末をせずに_exitで抜ける
+    pub fn faults<F: FnOnce()>(f: F) -> bool {
+        unsafe {
+            match libc::fork() {
+                -1 => panic!("fork failed: {}", io::Error::last_os_error()),
+                0 => {
+                    let status = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+                        Ok(()) => 0,
+                        Err(_) => 101
+                    };
+                    libc::_exit(status)
+                }
+                pid => {
+                    let mut status = 0;
+                    if libc::waitpid(pid, &mut status, 0) == -1 {
+                        panic!("waitpid failed: {}", io::Error::last_os_error());
+                    }
+                    libc::WIFSIGNALED(status)
+                        && (libc::WTERMSIG(status) == libc::SIGSEGV || libc::WTERMSIG(status) == libc::SIGBUS)
+                }
+            }
+        }
+    }
+}
+
+// タグ付きポインタ。tests/ui/からも読み込むので、別のファイルに置く
+mod ref_with_flag;
+
+// TaggedBoxでつないだ単方向リスト
+// ノードを指すリンクのタグは、そのノードが論理的に削除されたかどうかの印になる
+// markで印を付けたノードはiterやlenからは見えなくなり、sweepでまとめて実際に取り除く
+// 印はリンクの中にあるので、印を付けるのにノードの大きさは1ビットも増えない
+mod tagged_list {
+    use super::ref_with_flag::TaggedBox;
+
+    const LIVE: usize = 0;
+    const DELETED: usize = 1;
+
+    type Link<T> = Option<TaggedBox<Node<T>>>;
+
+    struct Node<T> {
+        value: T,
+        next: Link<T>
+    }
+
+    pub struct List<T> {
+        head: Link<T>,
+        // 印の付いていないノードの数
+        len: usize
+    }
+
+    impl<T> Default for List<T> {
+        fn default() -> List<T> {
+            List::new()
+        }
+    }
+
+    impl<T> List<T> {
+        pub fn new() -> List<T> {
+            List { head: None, len: 0 }
+        }
+
+        pub fn len(&self) -> usize {
+            self.len
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        pub fn push_front(&mut self, value: T) {
+            let next = self.head.take();
+            self.head = Some(TaggedBox::new(Node { value, next }, LIVE));
+            self.len += 1;
+        }
+
+        // 印の付いていない先頭のノードを取り除いて値を返す
+        pub fn pop_front(&mut self) -> Option<T> {
+            self.remove_first(|_| true)
+        }
+
+        // fがtrueを返す、印の付いていない最初のノードを取り除いて値を返す
+        pub fn remove_first<F: FnMut(&T) -> bool>(&mut self, mut f: F) -> Option<T> {
+            let mut link = &mut self.head;
+            while let Some(node) = link {
+                if node.tag() == LIVE && f(&node.get_ref().value) {
+                    let node = link.take().unwrap().into_inner();
+                    *link = node.next;
+                    self.len -= 1;
+                    return Some(node.value);
+                }
+                link = &mut link.as_mut().unwrap().get_mut().next;
+            }
+            None
+        }
+
+        // fがtrueを返すノードに削除の印を付け、新しく印を付けた数を返す
+        // ノードはsweepを呼ぶまでメモリに残る
+        pub fn mark<F: FnMut(&T) -> bool>(&mut self, mut f: F) -> usize {
+            let mut marked = 0;
+            let mut link = &mut self.head;
+            while let Some(node) = link {
+                if node.tag() == LIVE && f(&node.get_ref().value) {
+                    node.set_tag(DELETED);
+                    marked += 1;
+                }
+                link = &mut node.get_mut().next;
+            }
+            self.len -= marked;
+            marked
+        }
+
+        // 印の付いたノードをすべて取り除き、その数を返す
+        pub fn sweep(&mut self) -> usize {
+            let mut removed = 0;
+            let mut link = &mut self.head;
+            while let Some(node) = link {
+                if node.tag() == DELETED {
+                    *link = link.take().unwrap().into_inner().next;
+                    removed += 1;
+                } else {
+                    link = &mut link.as_mut().unwrap().get_mut().next;
+                }
+            }
+            removed
+        }
+
+        // 印の付いていないノードの値を先頭から順に返す
+        pub fn iter(&self) -> Iter<'_, T> {
+            Iter { link: self.head.as_ref() }
+        }
+    }
+
+    // 再帰的にドロップすると長いリストでスタックを使い果たすので、先頭から1つずつ手放す
+    impl<T> Drop for List<T> {
+        fn drop(&mut self) {
+            let mut link = self.head.take();
+            while let Some(node) = link {
+                link = node.into_inner().next;
+            }
+        }
+    }
+
+    pub struct Iter<'a, T> {
+        link: Option<&'a TaggedBox<Node<T>>>
+    }
+
+    impl<'a, T> Iterator for Iter<'a, T> {
+        type Item = &'a T;
+
+        fn next(&mut self) -> Option<&'a T> {
+            while let Some(node) = self.link {
+                self.link = node.get_ref().next.as_ref();
+                if node.tag() == LIVE {
+                    return Some(&node.get_ref().value);
+                }
+            }
+            None
+        }
+    }
+
+    impl<'a, T> IntoIterator for &'a List<T> {
+        type Item = &'a T;
+        type IntoIter = Iter<'a, T>;
+
+        fn into_iter(self) -> Iter<'a, T> {
+            self.iter()
+        }
+    }
+}
+
+// ポインタの代わりに32ビットの添字でオブジェクトを指すアリーナ
+// 64ビット環境ではリンク1本あたりのメモリが半分で済む（ポインタ圧縮）
+//
+// 生ポインタで互いを指すノードは、あるノードの&mutを作った時に他のノードからの生ポインタと別名になり得る
+// Handleはただの添字なので、参照を作るのはいつもArenaを借用するget/get_mutの中だけになり、
+// &Arenaと&mut Arenaの借用規則がそのまま別名の問題を防いでくれる。unsafeはどこにも要らない
+mod arena32 {
+    use std::marker::PhantomData;
+    use std::num::NonZeroU32;
+
+    // 添字の下位に持てるタグのビット数
+    pub const TAG_BITS: u32 = 2;
+
+    // 確保できるオブジェクトの数の上限
+    pub const MAX_LEN: usize = (u32::MAX >> TAG_BITS) as usize;
+
+    // (添字 + 1) << TAG_BITS | タグ を持つ。ゼロにならないので、Option<Handle<T>>も4バイトに収まる
+    // PhantomData<fn() -> T>はTを所有せず、Handle自体はTに関わらずCopy・Send・Syncになる
+    pub struct Handle<T> {
+        raw: NonZeroU32,
+        marker: PhantomData<fn() -> T>
+    }
+
+    // deriveするとT: Cloneなどの境界が付いてしまうので、手で実装する
+    impl<T> Clone for Handle<T> {
+        fn clone(&self) -> Handle<T> {
+            *self
+        }
+    }
+
+    impl<T> Copy for Handle<T> {}
+
+    impl<T> PartialEq for Handle<T> {
+        fn eq(&self, other: &Handle<T>) -> bool {
+            self.raw == other.raw
+        }
+    }
+
+    impl<T> Eq for Handle<T> {}
+
+    impl<T> std::hash::Hash for Handle<T> {
+        fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+            self.raw.hash(state);
+        }
+    }
+
+    impl<T> std::fmt::Debug for Handle<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "Handle({}, tag {})", self.index(), self.tag())
+        }
+    }
+
+    impl<T> Handle<T> {
+        const TAG_MASK: u32 = (1 << TAG_BITS) - 1;
+
+        pub fn index(self) -> usize {
+            ((self.raw.get() >> TAG_BITS) - 1) as usize
+        }
+
+        pub fn tag(self) -> u32 {
+            self.raw.get() & Self::TAG_MASK
+        }
+
+        // 同じオブジェクトを指し、タグだけを差し替えたHandleを返す
+        // タグがTAG_BITSビットに収まらなければpanicを起こす
+        pub fn with_tag(self, tag: u32) -> Handle<T> {
+            assert!(tag <= Self::TAG_MASK, "tag {} does not fit in {} bits", tag, TAG_BITS);
+            let raw = (self.raw.get() & !Self::TAG_MASK) | tag;
+            Handle { raw: NonZeroU32::new(raw).unwrap(), marker: PhantomData }
+        }
+    }
+
+    // オブジェクトは確保した順にVecに並び、個別には解放しない
+    pub struct Arena<T> {
+        items: Vec<T>
+    }
+
+    impl<T> Default for Arena<T> {
+        fn default() -> Arena<T> {
+            Arena::new()
+        }
+    }
+
+    impl<T> Arena<T> {
+        pub fn new() -> Arena<T> {
+            Arena { items: Vec::new() }
+        }
+
+        pub fn len(&self) -> usize {
+            self.items.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.items.is_empty()
+        }
+
+        // MAX_LEN個を超えて確保しようとするとpanicを起こす
+        pub fn alloc(&mut self, value: T) -> Handle<T> {
+            let index = self.items.len();
+            assert!(index < MAX_LEN, "arena32 can hold at most {} objects", MAX_LEN);
+            self.items.push(value);
+            let raw = (index as u32 + 1) << TAG_BITS;
+            Handle { raw: NonZeroU32::new(raw).unwrap(), marker: PhantomData }
+        }
+
+        // 別のArenaが返したHandleを渡すと、別のオブジェクトを返すかpanicを起こす
+        // どちらにしても未定義動作にはならない
+        pub fn get(&self, handle: Handle<T>) -> &T {
+            &self.items[handle.index()]
+        }
+
+        pub fn get_mut(&mut self, handle: Handle<T>) -> &mut T {
+            &mut self.items[handle.index()]
+        }
+
+        // 異なる2つのオブジェクトを同時に書き換える
+        // 同じオブジェクトを指していれば、&mutが2つできてしまうのでpanicを起こす
+        pub fn get_pair_mut(&mut self, a: Handle<T>, b: Handle<T>) -> (&mut T, &mut T) {
+            let (i, j) = (a.index(), b.index());
+            assert!(i != j, "handles {:?} and {:?} refer to the same object", a, b);
+            if i < j {
+                let (front, back) = self.items.split_at_mut(j);
+                (&mut front[i], &mut back[0])
+            } else {
+                let (front, back) = self.items.split_at_mut(i);
+                (&mut back[0], &mut front[j])
+            }
+        }
+    }
+}
+
+// 動的型付き言語の値を1つの64ビットの語に詰めるNaNボックス
+// f64のNaNは指数部が全部1で仮数部が0でなければ何でもよく、仮数部の残り51ビットは計算では使われない
+// そこで浮動小数点数の値はそのままのビット列で持ち、それ以外の値は符号ビットの立った静かなNaNの中に
+// 3ビットのタグと48ビットのペイロードとして埋め込む
+mod nanbox {
+    // 計算で生じるNaNはすべてこの1つのビット列にそろえ、符号ビットの立ったNaNをボックス専用にする
+    const CANONICAL_NAN: u64 = 0x7ff8_0000_0000_0000;
+
+    // 符号ビット + 指数部 + 静かなNaNのビット
+    const BOXED: u64 = 0xfff8_0000_0000_0000;
+    const TAG_SHIFT: u32 = 48;
+    const TAG_MASK: u64 = 0b111 << TAG_SHIFT;
+    const PAYLOAD_MASK: u64 = (1 << TAG_SHIFT) - 1;
+
+    // タグ0は符号ビットの立ったNaNそのものなので使わない
+    const TAG_NIL: u64 = 1;
+    const TAG_BOOL: u64 = 2;
+    const TAG_INT: u64 = 3;
+    const TAG_PTR: u64 = 4;
+
+    #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Value(u64);
+
+    // matchで扱うための、詰める前の形
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Unpacked {
+        Float(f64),
+        Int(i32),
+        Bool(bool),
+        Nil,
+        Ptr(*const ())
+    }
+
+    impl Value {
+        fn boxed(tag: u64, payload: u64) -> Value {
+            Value(BOXED | tag << TAG_SHIFT | payload)
+        }
+
+        fn tag(self) -> Option<u64> {
+            if self.0 & BOXED == BOXED {
+                Some((self.0 & TAG_MASK) >> TAG_SHIFT)
+            } else {
+                None
+            }
+        }
+
+        fn payload(self) -> u64 {
+            self.0 & PAYLOAD_MASK
+        }
+
+        // NaNはどんなビット列でもCANONICAL_NANとして持つので、取り出すとNaNであることだけが残る
+        pub fn from_f64(value: f64) -> Value {
+            if value.is_nan() {
+                Value(CANONICAL_NAN)
+            } else {
+                Value(value.to_bits())
+            }
+        }
+
+        pub fn from_i32(value: i32) -> Value {
+            Value::boxed(TAG_INT, value as u32 as u64)
+        }
+
+        pub fn from_bool(value: bool) -> Value {
+            Value::boxed(TAG_BOOL, value as u64)
+        }
+
+        pub fn nil() -> Value {
+            Value::boxed(TAG_NIL, 0)
+        }
+
+        // 現在の64ビット環境のユーザ空間のアドレスは下位48ビットに収まる
+        // 収まらないアドレスを渡すとpanicを起こす
+        // u64には出自を持たせられないので、expose_provenanceで出自を公開しておき、as_ptrで拾い直す
+        pub fn from_ptr<T>(ptr: *const T) -> Value {
+            let address = ptr.expose_provenance() as u64;
+            assert!(address & !PAYLOAD_MASK == 0, "pointer {:p} does not fit in 48 bits", ptr);
+            Value::boxed(TAG_PTR, address)
+        }
+
+        pub fn as_f64(self) -> Option<f64> {
+            match self.tag() {
+                None => Some(f64::from_bits(self.0)),
+                Some(_) => None
+            }
+        }
+
+        pub fn as_i32(self) -> Option<i32> {
+            match self.tag() {
+                Some(TAG_INT) => Some(self.payload() as u32 as i32),
+                _ => None
+            }
+        }
+
+        pub fn as_bool(self) -> Option<bool> {
+            match self.tag() {
+                Some(TAG_BOOL) => Some(self.payload() != 0),
+                _ => None
+            }
+        }
+
+        pub fn is_nil(self) -> bool {
+            self.tag() == Some(TAG_NIL)
+        }
+
+        // 返すのは生ポインタなので、どの型として参照外しするかは使う側が保証する
+        pub fn as_ptr<T>(self) -> Option<*const T> {
+            match self.tag() {
+                Some(TAG_PTR) => Some(std::ptr::with_exposed_provenance(self.payload() as usize)),
+                _ => None
+            }
+        }
+
+        pub fn unpack(self) -> Unpacked {
+            match self.tag() {
+                None => Unpacked::Float(f64::from_bits(self.0)),
+                Some(TAG_INT) => Unpacked::Int(self.payload() as u32 as i32),
+                Some(TAG_BOOL) => Unpacked::Bool(self.payload() != 0),
+                Some(TAG_NIL) => Unpacked::Nil,
+                Some(TAG_PTR) => Unpacked::Ptr(std::ptr::with_exposed_provenance(self.payload() as usize)),
+                Some(tag) => unreachable!("unknown tag {}", tag)
+            }
+        }
+
+        pub fn to_bits(self) -> u64 {
+            self.0
+        }
+    }
+
+    impl std::fmt::Debug for Value {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            self.unpack().fmt(f)
+        }
+    }
+}
+
+// GapBufferはbenches/のベンチマークからも読み込むので、別のファイルに置く
+mod gap;
+
+// Vecを一から書き直したもの。GapBufferと同じく大きいので別のファイルに置く
+mod myvec;
+
+// 短い文字列をヒープを使わずに持つString。benches/のベンチマークからも読み込むので、別のファイルに置く
+mod sso;
+
+// 要素をヒープではなく、自分の中の[MaybeUninit<T>; N]に置く容量固定のベクタ
+mod arrayvec {
+    use std::fmt;
+    use std::mem::MaybeUninit;
+    use std::ops::{Deref, DerefMut};
+
+    // 不変条件: items[..len]は初期化済みで、items[len..]は初期化されていない
+    pub struct ArrayVec<T, const N: usize> {
+        len: usize,
+        items: [MaybeUninit<T>; N]
+    }
+
+    // 満杯で入れられなかった要素を返す
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub struct CapacityError<T>(pub T);
+
+    impl<T> fmt::Debug for CapacityError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("CapacityError(..)")
+        }
+    }
+
+    impl<T> fmt::Display for CapacityError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("insufficient capacity")
+        }
+    }
+
+    impl<T, const N: usize> ArrayVec<T, N> {
+        pub fn new() -> ArrayVec<T, N> {
+            // MaybeUninitは初期化しなくてよいので、配列のどの要素も未初期化のまま作れる
+            // inline constが使えない頃は、MaybeUninit::<[MaybeUninit<T>; N]>::uninit().assume_init()と書いていた
+            ArrayVec { len: 0, items: [const { MaybeUninit::uninit() }; N] }
+        }
+
+        pub fn len(&self) -> usize {
+            self.len
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        pub const fn capacity(&self) -> usize {
+            N
+        }
+
+        pub fn is_full(&self) -> bool {
+            self.len == N
+        }
+
+        // 満杯ならpanicを起こす
+        pub fn push(&mut self, value: T) {
+            if self.try_push(value).is_err() {
+                panic!("ArrayVec is full (capacity {})", N);
+            }
+        }
+
+        pub fn try_push(&mut self, value: T) -> Result<(), CapacityError<T>> {
+            if self.len == N {
+                return Err(CapacityError(value));
+            }
+            // 初期化されていない場所への書き込みなので、古い値をドロップしないwriteを使う
+            self.items[self.len].write(value);
+            self.len += 1;
+            Ok(())
+        }
+
+        pub fn pop(&mut self) -> Option<T> {
+            if self.len == 0 {
+                return None;
+            }
+            self.len -= 1;
+            // items[len]はもう初期化されていないものとして扱うので、読み出した値の持ち主はこちらに移る
+            Some(unsafe { self.items[self.len].assume_init_read() })
+        }
+
+        pub fn clear(&mut self) {
+            let elements: *mut [T] = &mut **self;
+            // 要素のDropがpanicしても二重にドロップしないよう、先にlenを0にしておく
+            self.len = 0;
+            unsafe { std::ptr::drop_in_place(elements) };
+        }
+    }
+
+    impl<T, const N: usize> Default for ArrayVec<T, N> {
+        fn default() -> ArrayVec<T, N> {
+            ArrayVec::new()
+        }
+    }
+
+    impl<T, const N: usize> Deref for ArrayVec<T, N> {
+        type Target = [T];
+
+        fn deref(&self) -> &[T] {
+            // MaybeUninit<T>はTと同じレイアウトなので、初期化済みの部分だけを&[T]として見てよい
+            unsafe { std::slice::from_raw_parts(self.items.as_ptr().cast::<T>(), self.len) }
+        }
+    }
+
+    impl<T, const N: usize> DerefMut for ArrayVec<T, N> {
+        fn deref_mut(&mut self) -> &mut [T] {
+            unsafe { std::slice::from_raw_parts_mut(self.items.as_mut_ptr().cast::<T>(), self.len) }
+        }
+    }
+
+    // [MaybeUninit<T>; N]は中身をドロップしないので、初期化済みの要素は自分でドロップする
+    impl<T, const N: usize> Drop for ArrayVec<T, N> {
+        fn drop(&mut self) {
+            self.clear();
+        }
+    }
+
+    impl<T: Clone, const N: usize> Clone for ArrayVec<T, N> {
+        fn clone(&self) -> ArrayVec<T, N> {
+            let mut clone = ArrayVec::new();
+            for element in self.iter() {
+                clone.push(element.clone());
+            }
+            clone
+        }
+    }
+
+    impl<T: fmt::Debug, const N: usize> fmt::Debug for ArrayVec<T, N> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            (**self).fmt(f)
+        }
+    }
+}
+
+// GapBufferのチャンクを平衡木に並べたロープ
+// 1つの大きなGapBufferでは離れた位置を編集するたびに大量の要素を動かすことになるので、
+// 要素を一定の大きさのチャンクに分け、どこを編集しても動かすのは1つのチャンクの中だけで済むようにする
+// 木はtreap（優先度についてはヒープ、並び順については二分探索木になっている木）で、
+// 各ノードが部分木の要素数を持つので、位置による検索・挿入・削除は期待値でO(log n)になる
+mod rope {
+    use crate::gap::{self, GapBuffer};
+
+    // 1つのチャンクが持つ要素数の上限
+    const MAX_CHUNK: usize = 64;
+    // これより小さくなったチャンクは隣のチャンクと併合する
+    const MIN_CHUNK: usize = MAX_CHUNK / 4;
+
+    type Link<T> = Option<Box<Node<T>>>;
+
+    struct Node<T> {
+        // 空のチャンクは木に残さない
+        chunk: GapBuffer<T>,
+        // このノードを根とする部分木全体の要素数
+        len: usize,
+        priority: u32,
+        left: Link<T>,
+        right: Link<T>
+    }
+
+    impl<T> Node<T> {
+        // 子の要素数が変わった後に呼んで、部分木の要素数を数え直す
+        fn update(&mut self) {
+            self.len = len(&self.left) + self.chunk.len() + len(&self.right);
+        }
+    }
+
+    fn len<T>(link: &Link<T>) -> usize {
+        link.as_ref().map_or(0, |node| node.len)
+    }
+
+    // aの全ての要素がbの全ての要素より前に来るように2つの木をつなぐ
+    // 優先度の高い方を根にすれば、ヒープの性質が保たれる
+    fn merge<T>(a: Link<T>, b: Link<T>) -> Link<T> {
+        match (a, b) {
+            (None, b) => b,
+            (a, None) => a,
+            (Some(mut a), Some(mut b)) => {
+                if a.priority > b.priority {
+                    a.right = merge(a.right.take(), Some(b));
+                    a.update();
+                    Some(a)
+                } else {
+                    b.left = merge(Some(a), b.left.take());
+                    b.update();
+                    Some(b)
+                }
+            }
+        }
+    }
+
+    // 先頭のノードを木から外して返す
+    fn pop_first<T>(link: &mut Link<T>) -> Option<Box<Node<T>>> {
+        let node = link.as_mut()?;
+        if node.left.is_some() {
+            let first = pop_first(&mut node.left);
+            node.update();
+            return first;
+        }
+        let mut first = link.take()?;
+        *link = first.right.take();
+        Some(first)
+    }
+
+    // 末尾のノードを木から外して返す
+    fn pop_last<T>(link: &mut Link<T>) -> Option<Box<Node<T>>> {
+        let node = link.as_mut()?;
+        if node.right.is_some() {
+            let last = pop_last(&mut node.right);
+            node.update();
+            return last;
+        }
+        let mut last = link.take()?;
+        *link = last.left.take();
+        Some(last)
+    }
+
+    // 部分木の先頭または末尾のチャンクの要素数を返す
+    fn edge_chunk_len<T>(mut link: &Link<T>, first: bool) -> Option<usize> {
+        let mut chunk_len = None;
+        while let Some(node) = link {
+            chunk_len = Some(node.chunk.len());
+            link = if first { &node.left } else { &node.right };
+        }
+        chunk_len
+    }
+
+    // 小さくなったチャンクに、部分木の中で隣り合うチャンクの要素を移して1つにまとめる
+    // 隣のチャンクが祖先にある時は併合しない
+    fn absorb_neighbor<T>(node: &mut Node<T>) {
+        let chunk_len = node.chunk.len();
+        if edge_chunk_len(&node.right, true).is_some_and(|n| chunk_len + n <= MAX_CHUNK) {
+            let next = pop_first(&mut node.right).unwrap();
+            node.chunk.set_position(chunk_len);
+            node.chunk.extend(next.chunk);
+        } else if edge_chunk_len(&node.left, false).is_some_and(|n| chunk_len + n <= MAX_CHUNK) {
+            let prev = pop_last(&mut node.left).unwrap();
+            node.chunk.set_position(0);
+            node.chunk.extend(prev.chunk);
+        }
+    }
+
+    // 先頭からat個の要素を持つ木と残りの木に分ける
+    // atはチャンクの境界になければならない
+    fn split<T>(link: Link<T>, at: usize) -> (Link<T>, Link<T>) {
+        let mut node = match link {
+            Some(node) => node,
+            None => return (None, None)
+        };
+        let left_len = len(&node.left);
+        if at <= left_len {
+            let (left, right) = split(node.left.take(), at);
+            node.left = right;
+            node.update();
+            (left, Some(node))
+        } else {
+            debug_assert!(at >= left_len + node.chunk.len());
+            let (left, right) = split(node.right.take(), at - left_len - node.chunk.len());
+            node.right = left;
+            node.update();
+            (Some(node), right)
+        }
+    }
+
+    // 位置atを含むチャンクをatで切り、後半の要素を取り出して返す
+    // 木の形は変えないので、取り出した要素を新しいノードとして入れ直すのは呼び出し側の仕事
+    fn cut_chunk<T>(node: &mut Node<T>, at: usize) -> GapBuffer<T> {
+        let left_len = len(&node.left);
+        let tail = if at < left_len {
+            cut_chunk(node.left.as_mut().unwrap(), at)
+        } else if at - left_len < node.chunk.len() {
+            node.chunk.split_off(at - left_len)
+        } else {
+            cut_chunk(node.right.as_mut().unwrap(), at - left_len - node.chunk.len())
+        };
+        node.update();
+        tail
+    }
+
+    // index番目の要素を取り除いて返す
+    fn remove_from<T>(link: &mut Link<T>, index: usize) -> T {
+        let node = link.as_mut().unwrap();
+        let left_len = len(&node.left);
+        let element;
+        if index < left_len {
+            element = remove_from(&mut node.left, index);
+        } else if index - left_len < node.chunk.len() {
+            node.chunk.set_position(index - left_len);
+            element = node.chunk.remove().unwrap();
+            if node.chunk.len() < MIN_CHUNK {
+                absorb_neighbor(node);
+            }
+            if node.chunk.len() == 0 {
+                // チャンクが空になったノードは、左右の部分木をつないだものに置き換える
+                let node = link.take().unwrap();
+                *link = merge(node.left, node.right);
+                return element;
+            }
+        } else {
+            element = remove_from(&mut node.right, index - left_len - node.chunk.len());
+        }
+        node.update();
+        element
+    }
+
+    // index番目の位置にelementを挿入する
+    // indexがチャンクの末尾と次のチャンクの先頭の両方に当たる時は、前のチャンクに入れる
+    fn insert_into<T>(node: &mut Node<T>, index: usize, element: T) {
+        let left_len = len(&node.left);
+        if index < left_len {
+            insert_into(node.left.as_mut().unwrap(), index, element);
+        } else if index - left_len <= node.chunk.len() {
+            node.chunk.set_position(index - left_len);
+            node.chunk.insert(element);
+        } else {
+            insert_into(node.right.as_mut().unwrap(), index - left_len - node.chunk.len(), element);
+        }
+        node.len += 1;
+    }
+
+    pub struct Rope<T> {
+        root: Link<T>,
+        // 優先度を作る擬似乱数の状態
+        seed: u32
+    }
+
+    impl<T> Rope<T> {
+        pub fn new() -> Rope<T> {
+            Rope { root: None, seed: 0x2545_f491 }
+        }
+
+        pub fn len(&self) -> usize {
+            len(&self.root)
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.root.is_none()
+        }
+
+        // チャンクの数を返す
+        pub fn chunk_count(&self) -> usize {
+            fn count<T>(link: &Link<T>) -> usize {
+                link.as_ref().map_or(0, |node| 1 + count(&node.left) + count(&node.right))
+            }
+            count(&self.root)
+        }
+
+        // 木の高さを返す
+        pub fn depth(&self) -> usize {
+            fn depth<T>(link: &Link<T>) -> usize {
+                link.as_ref().map_or(0, |node| 1 + std::cmp::max(depth(&node.left), depth(&node.right)))
+            }
+            depth(&self.root)
+        }
+
+        // chunkを持つノードを作る。優先度はxorshiftで作った擬似乱数にする
+        fn new_node(&mut self, chunk: GapBuffer<T>) -> Box<Node<T>> {
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 17;
+            self.seed ^= self.seed << 5;
+            let mut node = Box::new(Node { chunk, len: 0, priority: self.seed, left: None, right: None });
+            node.update();
+            node
+        }
+
+        // insert_intoがindexの要素を入れるチャンクの先頭位置と要素数を返す
+        fn chunk_at(&self, mut index: usize) -> Option<(usize, usize)> {
+            let mut link = &self.root;
+            let mut start = 0;
+            while let Some(node) = link {
+                let left_len = len(&node.left);
+                if index < left_len {
+                    link = &node.left;
+                } else if index - left_len <= node.chunk.len() {
+                    return Some((start + left_len, node.chunk.len()));
+                } else {
+                    index -= left_len + node.chunk.len();
+                    start += left_len + node.chunk.len();
+                    link = &node.right;
+                }
+            }
+            None
+        }
+
+        pub fn get(&self, mut index: usize) -> Option<&T> {
+            let mut link = &self.root;
+            while let Some(node) = link {
+                let left_len = len(&node.left);
+                if index < left_len {
+                    link = &node.left;
+                } else if index - left_len < node.chunk.len() {
+                    return node.chunk.get(index - left_len);
+                } else {
+                    index -= left_len + node.chunk.len();
+                    link = &node.right;
+                }
+            }
+            None
+        }
+
+        // index番目の位置にelementを挿入する。indexが範囲外ならpanicを起こす
+        pub fn insert(&mut self, index: usize, element: T) {
+            if index > self.len() {
+                panic!("index {} out of range for Rope of length {}", index, self.len());
+            }
+            match self.chunk_at(index) {
+                None => {
+                    let mut chunk = GapBuffer::with_capacity(MAX_CHUNK);
+                    chunk.insert(element);
+                    self.root = Some(self.new_node(chunk));
+                    return;
+                }
+                Some((start, chunk_len)) if chunk_len >= MAX_CHUNK => {
+                    // 挿入先のチャンクが一杯なら、先に後半を新しいチャンクに移しておく
+                    // 新しいノードの優先度は乱数なので、チャンクの境界で木を分けてから間に挟んでつなぎ直す
+                    let at = start + chunk_len / 2;
+                    let tail = cut_chunk(self.root.as_mut().unwrap(), at);
+                    let tail = self.new_node(tail);
+                    let (left, right) = split(self.root.take(), at);
+                    self.root = merge(merge(left, Some(tail)), right);
+                }
+                Some(_) => {}
+            }
+            insert_into(self.root.as_mut().unwrap(), index, element);
+        }
+
+        pub fn push(&mut self, element: T) {
+            let len = self.len();
+            self.insert(len, element);
+        }
+
+        // index番目の要素を取り除いて返す。indexが範囲外ならpanicを起こす
+        pub fn remove(&mut self, index: usize) -> T {
+            if index >= self.len() {
+                panic!("index {} out of range for Rope of length {}", index, self.len());
+            }
+            remove_from(&mut self.root, index)
+        }
+
+        // 先頭から順に要素への参照を返すイテレータを作る
+        pub fn iter(&self) -> Iter<'_, T> {
+            let mut iter = Iter { stack: Vec::new(), chunk: None, len: self.len() };
+            iter.push_left(&self.root);
+            iter
+        }
+
+        // 各チャンクの連続した領域を、先頭から順にスライスとして返すイテレータを作る
+        // 1つのチャンクはギャップの前後で高々2つの領域になる
+        pub fn chunks(&self) -> Chunks<'_, T> {
+            let mut chunks = Chunks { stack: Vec::new(), chunk: None };
+            chunks.push_left(&self.root);
+            chunks
+        }
+    }
+
+    impl<T> Default for Rope<T> {
+        fn default() -> Rope<T> {
+            Rope::new()
+        }
+    }
+
+    impl<T> std::iter::FromIterator<T> for Rope<T> {
+        fn from_iter<I: IntoIterator<Item=T>>(iterable: I) -> Rope<T> {
+            let mut rope = Rope::new();
+            for element in iterable {
+                rope.push(element);
+            }
+            rope
+        }
+    }
+
+    impl<T> std::ops::Index<usize> for Rope<T> {
+        type Output = T;
+
+        fn index(&self, index: usize) -> &T {
+            match self.get(index) {
+                Some(element) => element,
+                None => panic!("index out of bounds: the len is {} but the index is {}",
+                               self.len(), index)
+            }
+        }
+    }
+
+    // 木を通りがけ順に辿り、各チャンクの要素を順に返すイテレータ
+    pub struct Iter<'a, T> {
+        // これから訪れるノード
+        stack: Vec<&'a Node<T>>,
+        // 今読んでいるチャンク
+        chunk: Option<gap::Iter<'a, T>>,
+        len: usize
+    }
+
+    impl<'a, T> Iter<'a, T> {
+        fn push_left(&mut self, mut link: &'a Link<T>) {
+            while let Some(node) = link {
+                self.stack.push(node);
+                link = &node.left;
+            }
+        }
+    }
+
+    impl<'a, T> Iterator for Iter<'a, T> {
+        type Item = &'a T;
+
+        fn next(&mut self) -> Option<&'a T> {
+            loop {
+                if let Some(element) = self.chunk.as_mut().and_then(|chunk| chunk.next()) {
+                    self.len -= 1;
+                    return Some(element);
+                }
+                let node = self.stack.pop()?;
+                self.push_left(&node.right);
+                self.chunk = Some(node.chunk.iter());
+            }
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (self.len, Some(self.len))
+        }
+    }
+
+    impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+    // Iterと同じく木を通りがけ順に辿り、各チャンクの領域を順に返すイテレータ
+    pub struct Chunks<'a, T> {
+        stack: Vec<&'a Node<T>>,
+        chunk: Option<gap::Chunks<'a, T>>
+    }
+
+    impl<'a, T> Chunks<'a, T> {
+        fn push_left(&mut self, mut link: &'a Link<T>) {
+            while let Some(node) = link {
+                self.stack.push(node);
+                link = &node.left;
+            }
+        }
+    }
+
+    impl<'a, T> Iterator for Chunks<'a, T> {
+        type Item = &'a [T];
+
+        fn next(&mut self) -> Option<&'a [T]> {
+            loop {
+                if let Some(slice) = self.chunk.as_mut().and_then(|chunk| chunk.next()) {
+                    return Some(slice);
+                }
+                let node = self.stack.pop()?;
+                self.push_left(&node.right);
+                self.chunk = Some(node.chunk.chunks());
+            }
+        }
+    }
+
+    impl<'a, T> IntoIterator for &'a Rope<T> {
+        type Item = &'a T;
+        type IntoIter = Iter<'a, T>;
+
+        fn into_iter(self) -> Iter<'a, T> {
+            self.iter()
+        }
+    }
+}
+
+// panicを捕まえて結果を返す
+// わざとpanicさせる確認で標準エラー出力が汚れないよう、その間だけpanicメッセージを表示しない
+fn catch_panic<F, R>(f: F) -> std::thread::Result<R>
+    where F: FnOnce() -> R + std::panic::UnwindSafe
+{
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let result = std::panic::catch_unwind(f);
+    std::panic::set_hook(hook);
+    result
+}
+
+// GapBufferのunsafeな操作を一通り行い、結果と要素がドロップされた回数を確かめる
+// Miriで実行すれば、範囲外や未初期化の領域の読み書き、Stacked Borrowsに反するポインタの使い方がここで報告される
+fn exercise_gap_buffer() {
+    use std::rc::Rc;
+    use gap::GapBuffer;
+
+    let alive = Rc::new(());
+    {
+        let mut buf = GapBuffer::new();
+        // 再確保をまたいで挿入する
+        for i in 0..10 {
+            buf.insert((i, alive.clone()));
+        }
+        buf.set_position(3);
+        assert_eq!(buf.remove().map(|(i, _)| i), Some(3));
+        buf.set_position(8);
+        buf.insert((10, alive.clone()));
+        buf.set_position(1);
+
+        // ギャップの前後への可変参照を同時に使う
+        {
+            let (front, back) = buf.as_mut_slices();
+            front[0].0 += 100;
+            back[0].0 += 200;
+        }
+        for element in buf.iter_mut().rev().take(2) {
+            element.0 += 1000;
+        }
+        let mut iter = buf.iter();
+        assert_eq!(iter.next().map(|e| e.0), Some(100));
+        assert_eq!(iter.next_back().map(|e| e.0), Some(1009));
+
+        // 途中まで読んだDrainを捨てる
+        assert_eq!(buf.drain(2..5).next().map(|(i, _)| i), Some(2));
+        let clone = buf.clone();
+        buf.retain(|e| e.0 % 2 == 0);
+        let tail = buf.split_off(1);
+        assert_eq!(Rc::strong_count(&alive), 1 + buf.len() + tail.len() + clone.len());
+
+        let vec = clone.into_vec();
+        let mut buf = GapBuffer::from(vec);
+        buf.set_position(0);
+        buf.insert((-1, alive.clone()));
+        buf.shrink_to_fit();
+        assert_eq!(buf.iter().map(|e| e.0).collect::<Vec<_>>(), [-1, 100, 201, 6, 7, 8, 1010, 1009]);
+    }
+    // 全ての要素がちょうど一度ずつドロップされている
+    assert_eq!(Rc::strong_count(&alive), 1);
+
+    // ゼロサイズの型では領域を確保しない
+    let mut units: GapBuffer<()> = GapBuffer::new();
+    units.insert(());
+    units.set_position(0);
+    assert_eq!(units.remove(), Some(()));
+    assert_eq!(units.into_vec(), Vec::<()>::new());
+}
+
+// 下位ビットや上位ビットにタグを付けたポインタを作り、タグを落として読み書きする
+// -Zmiri-strict-provenanceを付けたMiriで実行すれば、タグの付け外しで出自が失われていないことがここで検査される
+fn exercise_tagged_pointers() {
+    use ref_with_flag::{AtomicTaggedPtr, PackedOption, PtrEither, RefWithFlag, RefWithFlagMut, RefWithTag};
+    use std::sync::atomic::Ordering;
+
+    let words = vec![String::from("tagged"), String::from("pointer")];
+    let mut flagged = RefWithFlag::new(&words, true);
+    flagged.toggle_flag();
+    assert_eq!((flagged.len(), flagged.get_flag()), (2, false));
+
+    let mut numbers = vec![1u64, 2];
+    let mut flagged_mut = RefWithFlagMut::new(&mut numbers, true);
+    flagged_mut.get_mut().push(3);
+    flagged_mut.set_flag(false);
+    flagged_mut.into_mut().push(4);
+    assert_eq!(numbers, [1, 2, 3, 4]);
+
+    let tagged = RefWithTag::<_, 3>::new(&numbers[1], 7).map_tag(|tag| tag - 2);
+    assert_eq!((*tagged.get_ref(), tagged.tag()), (2, 5));
+    let packed = PackedOption::new(Some(&numbers[2]), true);
+    assert_eq!(packed.get(), Some(&3));
+    let either = PtrEither::<u64, String>::new_b(&words[1]);
+    assert_eq!(either.as_b().map(String::as_str), Some("pointer"));
+    let fat = ref_with_flag::FatRefWithFlag::new(&numbers[1..], true);
+    assert_eq!((&fat[..], fat.get_flag()), (&[2, 3, 4][..], true));
+    let object = ref_with_flag::FatRefWithFlag::<dyn std::fmt::Debug>::new(&words, true);
+    assert_eq!(format!("{:?}", object.get_ref()), r#"["tagged", "pointer"]"#);
+
+    let mut first = 10u32;
+    let mut second = 20u32;
+    let atomic = AtomicTaggedPtr::new(&mut first as *mut u32, false);
+    assert!(atomic.compare_exchange((&mut first, false), (&mut second, true), Ordering::AcqRel, Ordering::Acquire).is_ok());
+    let (ptr, flag) = atomic.load(Ordering::Acquire);
+    unsafe { *ptr += 1 };
+    assert_eq!((second, flag), (21, true));
+
+    #[cfg(all(target_pointer_width = "64", any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        let mut byte = 5u8;
+        let high = ref_with_flag::HighTagPtr::new(&mut byte as *mut u8, 0xbeef);
+        unsafe { *high.ptr() += 1 };
+        assert_eq!((byte, high.tag()), (6, 0xbeef));
+    }
+}
+
+// tagged_listの操作を一通り行い、TaggedBoxがノードをちょうど1回ずつ解放することを確かめる
+// Miriで実行すれば、タグを落として組み立て直したBoxの解放や、タグ付きのリンクをたどる参照がここで検査される
+fn exercise_tagged_list() {
+    use std::rc::Rc;
+    use tagged_list::List;
+
+    let shared = Rc::new(());
+    let mut list = List::new();
+    for _ in 0..20 {
+        list.push_front(Rc::clone(&shared));
+    }
+    let mut count = 0;
+    list.mark(|_| { count += 1; count % 2 == 1 });
+    assert_eq!((list.len(), Rc::strong_count(&shared)), (10, 21));
+    assert!(list.remove_first(|_| true).is_some());
+    assert_eq!(list.sweep(), 10);
+    assert_eq!((list.len(), Rc::strong_count(&shared)), (9, 10));
+    list.mark(|_| true);
+    // 印を付けたままのノードも、リストと一緒にちょうど1回だけドロップされる
+    drop(list);
+    assert_eq!(Rc::strong_count(&shared), 1);
+}
+
+// MyVecの操作を一通り行い、同じ操作を加えたVecと食い違わないことと、要素がちょうど1回ずつドロップされることを確かめる
+// Miriで実行すれば、確保し直しやinsert/removeの要素の移動、Drainの詰め直しの読み書きがここで検査される
+fn exercise_my_vec() {
+    use myvec::MyVec;
+    use std::rc::Rc;
+
+    let mut vec = MyVec::new();
+    let mut model = Vec::new();
+    let mut seed = 99u32;
+    for i in 0..200 {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let r = (seed >> 8) as usize;
+        match r % 6 {
+            0 | 1 => {
+                vec.push(i);
+                model.push(i);
+            }
+            2 => assert_eq!(vec.pop(), model.pop()),
+            3 => {
+                let index = r % (model.len() + 1);
+                vec.insert(index, i);
+                model.insert(index, i);
+            }
+            4 if !model.is_empty() => {
+                let index = r % model.len();
+                assert_eq!(vec.remove(index), model.remove(index));
+            }
+            _ if !model.is_empty() => {
+                let index = r % model.len();
+                assert_eq!(vec.swap_remove(index), model.swap_remove(index));
+            }
+            _ => {}
+        }
+        assert_eq!(&vec[..], &model[..]);
+        assert!(vec.len() <= vec.capacity());
+    }
+
+    // Drainは途中で捨てても、残りをドロップして後ろの要素を詰める
+    vec.extend(1000..1010);
+    model.extend(1000..1010);
+    let len = vec.len();
+    let mut drain = vec.drain(2..len - 3);
+    assert_eq!(drain.next(), Some(model[2]));
+    assert_eq!(drain.next_back(), Some(model[len - 4]));
+    drop(drain);
+    model.drain(2..len - 3);
+    assert_eq!(&vec[..], &model[..]);
+    assert_eq!(vec.drain(..).rev().collect::<Vec<_>>(), model.drain(..).rev().collect::<Vec<_>>());
+    assert!(vec.is_empty());
+    vec.shrink_to_fit();
+    assert_eq!(vec.capacity(), 0);
+
+    // どの経路で取り除いても、要素はちょうど1回だけドロップされる
+    let shared = Rc::new(());
+    let mut vec: MyVec<Rc<()>> = (0..10).map(|_| Rc::clone(&shared)).collect();
+    vec.extend(vec.clone());
+    assert_eq!(Rc::strong_count(&shared), 21);
+    vec.truncate(15);
+    drop(vec.remove(0));
+    drop(vec.drain(3..5));
+    let mut iter = vec.into_iter();
+    drop(iter.next());
+    drop(iter.next_back());
+    assert_eq!(Rc::strong_count(&shared), 11);
+    drop(iter);
+    assert_eq!(Rc::strong_count(&shared), 1);
+
+    // ゼロサイズの型では確保せず、容量は無限大として扱う
+    let mut units = MyVec::new();
+    for _ in 0..5 {
+        units.push(());
+    }
+    assert_eq!((units.len(), units.capacity()), (5, usize::MAX));
+    assert_eq!(units.drain(1..3).count(), 2);
+    assert_eq!(units.into_iter().count(), 3);
+}
+
+// N個まではArrayVecと同じく自分の中に要素を置き、それを超えるとヒープに移すベクタ
+// 自分の中の配列とヒープのポインタ・長さ・容量は同時には使わないので、共用体で同じ場所に重ねる
+mod smallvec {
+    use std::fmt;
+    use std::mem::{ManuallyDrop, MaybeUninit};
+    use std::ops::{Deref, DerefMut};
+    use std::ptr::{self, NonNull};
+
+    struct Inline<T, const N: usize> {
+        len: usize,
+        items: [MaybeUninit<T>; N]
+    }
+
+    // Vec::from_raw_partsで組み立て直せる3つ組
+    struct Heap<T> {
+        ptr: NonNull<T>,
+        len: usize,
+        cap: usize
+    }
+
+    // 共用体のフィールドはCopyかManuallyDropでなければならない
+    // Heap<T>はTの値を直接持たないのでCopyにできるが、derive(Copy)ではT: Copyが要求されるので手で実装する
+    impl<T> Clone for Heap<T> {
+        fn clone(&self) -> Heap<T> {
+            *self
+        }
+    }
+
+    impl<T> Copy for Heap<T> {}
+
+    // どちらのフィールドが有効かは共用体自身は覚えていないので、SmallVecのspilledで見分ける
+    // repr(C)の共用体では、どのフィールドも先頭（オフセット0）に置かれる
+    #[repr(C)]
+    union Data<T, const N: usize> {
+        inline: ManuallyDrop<Inline<T, N>>,
+        heap: Heap<T>
+    }
+
+    // 不変条件: spilledがfalseならdata.inlineが、trueならdata.heapが有効で、
+    // どちらでも格納場所の先頭からlen個が初期化済みになっている
+    // 一度ヒープに移したら、要素が減っても自分の中には戻さない
+    pub struct SmallVec<T, const N: usize> {
+        spilled: bool,
+        data: Data<T, N>
+    }
+
+    impl<T, const N: usize> SmallVec<T, N> {
+        pub fn new() -> SmallVec<T, N> {
+            SmallVec {
+                spilled: false,
+                data: Data { inline: ManuallyDrop::new(Inline { len: 0, items: [const { MaybeUninit::uninit() }; N] }) }
+            }
+        }
+
+        pub fn spilled(&self) -> bool {
+            self.spilled
+        }
+
+        // data.inlineへのポインタ
+        // ManuallyDropを参照外しすると配列全体への&mutができ、前に作った要素へのポインタが無効になってしまうので、
+        // 自分の中の配列や長さには必ずこのポインタから参照を経ずにたどる
+        // フィールドはどれも先頭にあり、ManuallyDropはrepr(transparent)なので、共用体へのポインタをそのままキャストしてよい
+        // （addr_of!(self.data.inline)と書くと、Rust 1.87ではフィールドの読み出しとみなされてunsafeが要る）
+        fn inline_ptr(&self) -> *const Inline<T, N> {
+            ptr::addr_of!(self.data).cast()
+        }
+
+        fn inline_mut_ptr(&mut self) -> *mut Inline<T, N> {
+            ptr::addr_of_mut!(self.data).cast()
+        }
+
+        pub fn len(&self) -> usize {
+            unsafe {
+                if self.spilled {
+                    self.data.heap.len
+                } else {
+                    (*self.inline_ptr()).len
+                }
+            }
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        pub fn capacity(&self) -> usize {
+            if self.spilled {
+                unsafe { self.data.heap.cap }
+            } else {
+                N
+            }
+        }
+
+        // 格納場所の先頭へのポインタ
+        // 自分の中に置いている時は、SmallVecをムーブすると無効になる
+        pub fn as_ptr(&self) -> *const T {
+            unsafe {
+                if self.spilled {
+                    self.data.heap.ptr.as_ptr()
+                } else {
+                    ptr::addr_of!((*self.inline_ptr()).items).cast()
+                }
+            }
+        }
+
+        pub fn as_mut_ptr(&mut self) -> *mut T {
+            unsafe {
+                if self.spilled {
+                    self.data.heap.ptr.as_ptr()
+                } else {
+                    ptr::addr_of_mut!((*self.inline_mut_ptr()).items).cast()
+                }
+            }
+        }
+
+        // lenまでが初期化済みでなければならない
+        unsafe fn set_len(&mut self, len: usize) {
+            if self.spilled {
+                self.data.heap.len = len;
+            } else {
+                (*self.inline_mut_ptr()).len = len;
+            }
+        }
+
+        pub fn push(&mut self, value: T) {
+            let len = self.len();
+            if len == self.capacity() {
+                self.grow(1);
+            }
+            unsafe {
+                self.as_mut_ptr().add(len).write(value);
+                self.set_len(len + 1);
+            }
+        }
+
+        pub fn pop(&mut self) -> Option<T> {
+            let len = self.len();
+            if len == 0 {
+                return None;
+            }
+            unsafe {
+                self.set_len(len - 1);
+                Some(self.as_mut_ptr().add(len - 1).read())
+            }
+        }
+
+        pub fn truncate(&mut self, len: usize) {
+            let old_len = self.len();
+            if len >= old_len {
+                return;
+            }
+            unsafe {
+                // 要素のDropがpanicしても二重にドロップしないよう、先にlenを縮めておく
+                // &mut selfを取るset_lenは自分の中の配列全体を借り直すので、要素へのポインタはその後で作る
+                self.set_len(len);
+                let tail = ptr::slice_from_raw_parts_mut(self.as_mut_ptr().add(len), old_len - len);
+                ptr::drop_in_place(tail);
+            }
+        }
+
+        pub fn clear(&mut self) {
+            self.truncate(0);
+        }
+
+        // 容量をadditional個以上増やす
+        // ヒープの確保と確保し直しは、3つ組から組み立て直したVecに任せる
+        fn grow(&mut self, additional: usize) {
+            let len = self.len();
+            let vec = if self.spilled {
+                let heap = unsafe { self.data.heap };
+                let mut vec = ManuallyDrop::new(unsafe { Vec::from_raw_parts(heap.ptr.as_ptr(), heap.len, heap.cap) });
+                // reserveがpanicしても、確保し直す前なのでheapはそのまま有効
+                vec.reserve(additional);
+                vec
+            } else {
+                // 自分の中からヒープへ移す
+                // with_capacityがpanicしても、まだ何も動かしていないので自分の中の要素はそのまま残る
+                let mut vec = ManuallyDrop::new(Vec::with_capacity(std::cmp::max(N * 2, len + additional)));
+                unsafe {
+                    ptr::copy_nonoverlapping(self.as_ptr(), vec.as_mut_ptr(), len);
+                    vec.set_len(len);
+                }
+                vec
+            };
+            // 要素の持ち主はヒープ側に移ったので、自分の中の配列は上書きしてよい
+            // 共用体のフィールドへの代入は、古いフィールドをドロップしない
+            self.data = Data {
+                heap: Heap { ptr: unsafe { NonNull::new_unchecked(vec.as_ptr() as *mut T) }, len, cap: vec.capacity() }
+            };
+            self.spilled = true;
+        }
+    }
+
+    impl<T, const N: usize> Default for SmallVec<T, N> {
+        fn default() -> SmallVec<T, N> {
+            SmallVec::new()
+        }
+    }
+
+    impl<T, const N: usize> Drop for SmallVec<T, N> {
+        fn drop(&mut self) {
+            unsafe {
+                if self.spilled {
+                    let heap = self.data.heap;
+                    drop(Vec::from_raw_parts(heap.ptr.as_ptr(), heap.len, heap.cap));
+                } else {
+                    ptr::drop_in_place(&mut **self as *mut [T]);
+                }
+            }
+        }
+    }
+
+    impl<T, const N: usize> Deref for SmallVec<T, N> {
+        type Target = [T];
+
+        fn deref(&self) -> &[T] {
+            unsafe { std::slice::from_raw_parts(self.as_ptr(), self.len()) }
+        }
+    }
+
+    impl<T, const N: usize> DerefMut for SmallVec<T, N> {
+        fn deref_mut(&mut self) -> &mut [T] {
+            let len = self.len();
+            unsafe { std::slice::from_raw_parts_mut(self.as_mut_ptr(), len) }
+        }
+    }
+
+    impl<T, const N: usize> Extend<T> for SmallVec<T, N> {
+        fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+            for element in iter {
+                self.push(element);
+            }
+        }
+    }
+
+    impl<T, const N: usize> std::iter::FromIterator<T> for SmallVec<T, N> {
+        fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> SmallVec<T, N> {
+            let mut vec = SmallVec::new();
+            vec.extend(iter);
+            vec
+        }
+    }
+
+    impl<T: fmt::Debug, const N: usize> fmt::Debug for SmallVec<T, N> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            (**self).fmt(f)
+        }
+    }
+
+    // 要素を先頭から順にムーブして返すイテレータ
+    // vecの長さは0にしてあるので、vecのDropは格納場所を解放するだけになる
+    // 格納場所のnext..endにまだ返していない要素が残っている
+    pub struct IntoIter<T, const N: usize> {
+        vec: SmallVec<T, N>,
+        next: usize,
+        end: usize
+    }
+
+    impl<T, const N: usize> IntoIterator for SmallVec<T, N> {
+        type Item = T;
+        type IntoIter = IntoIter<T, N>;
+
+        fn into_iter(mut self) -> IntoIter<T, N> {
+            let len = self.len();
+            unsafe { self.set_len(0) };
+            IntoIter { vec: self, next: 0, end: len }
+        }
+    }
+
+    impl<T, const N: usize> Iterator for IntoIter<T, N> {
+        type Item = T;
+
+        fn next(&mut self) -> Option<T> {
+            if self.next == self.end {
+                return None;
+            }
+            let element = unsafe { self.vec.as_ptr().add(self.next).read() };
+            self.next += 1;
+            Some(element)
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            let len = self.end - self.next;
+            (len, Some(len))
+        }
+    }
+
+    impl<T, const N: usize> DoubleEndedIterator for IntoIter<T, N> {
+        fn next_back(&mut self) -> Option<T> {
+            if self.next == self.end {
+                return None;
+            }
+            self.end -= 1;
+            Some(unsafe { self.vec.as_ptr().add(self.end).read() })
+        }
+    }
+
+    impl<T, const N: usize> ExactSizeIterator for IntoIter<T, N> {}
+
+    // まだ返していない要素をドロップする。格納場所はこの後vecのDropが解放する
+    impl<T, const N: usize> Drop for IntoIter<T, N> {
+        fn drop(&mut self) {
+            unsafe {
+                let remaining = ptr::slice_from_raw_parts_mut(self.vec.as_mut_ptr().add(self.next), self.end - self.next);
+                self.next = self.end;
+                ptr::drop_in_place(remaining);
+            }
+        }
+    }
+}
+
+// 1スレッドだけで使う参照カウントのポインタ
+// 値と2つのカウントをヒープの1つの箱に入れ、MyRcとWeakはその箱へのNonNullだけを持つ
+// どのMyRcも値を所有しているので、最後の1つがドロップされた時だけ値をドロップしなければならず、
+// 借用規則では表せない「共有された所有権」をunsafeで実装することになる
+mod myrc {
+    use std::cell::Cell;
+    use std::marker::PhantomData;
+    use std::mem::ManuallyDrop;
+    use std::ops::Deref;
+    use std::ptr::NonNull;
+
+    // strongが0になった時点で値をドロップし、weakが0になった時点で箱を解放する
+    // stdのRcと同じく、MyRcが1つでも残っている間はweakを余分に1つ数えておく
+    // こうすると、MyRcたちは全体で1つのWeakのように振る舞い、箱の解放はweakを見るだけで決められる
+    struct RcBox<T> {
+        strong: Cell<usize>,
+        weak: Cell<usize>,
+        // 値のドロップは箱の解放より先に自分で行うので、Boxに戻して解放する時にはドロップしない
+        value: ManuallyDrop<T>
+    }
+
+    // 箱のカウントを読み書きする
+    // 値がドロップされた後も、箱全体ではなくカウントのフィールドだけを参照する
+    unsafe fn strong<'a, T>(ptr: NonNull<RcBox<T>>) -> &'a Cell<usize> {
+        &(*ptr.as_ptr()).strong
+    }
+
+    unsafe fn weak<'a, T>(ptr: NonNull<RcBox<T>>) -> &'a Cell<usize> {
+        &(*ptr.as_ptr()).weak
+    }
+
+    // カウントがあふれるほどmem::forgetされたら、解放済みの箱を指すことになる前に中断する
+    fn increment(count: &Cell<usize>) {
+        let n = count.get().checked_add(1).unwrap_or_else(|| std::process::abort());
+        count.set(n);
+    }
+
+    // NonNullもCellもSendでもSyncでもないので、MyRcとWeakを他のスレッドに渡すことはできない
+    // カウントを原子的に更新しないので、渡せてしまうとカウントが食い違う
+    pub struct MyRc<T> {
+        ptr: NonNull<RcBox<T>>,
+        // RcBox<T>を所有していることをドロップチェッカーに教える
+        owns: PhantomData<RcBox<T>>
+    }
+
+    pub struct Weak<T> {
+        ptr: NonNull<RcBox<T>>
+    }
+
+    impl<T> MyRc<T> {
+        pub fn new(value: T) -> MyRc<T> {
+            let rc_box = Box::new(RcBox { strong: Cell::new(1), weak: Cell::new(1), value: ManuallyDrop::new(value) });
+            MyRc { ptr: NonNull::from(Box::leak(rc_box)), owns: PhantomData }
+        }
+
+        pub fn strong_count(this: &MyRc<T>) -> usize {
+            unsafe { strong(this.ptr).get() }
+        }
+
+        // MyRcたちの分として余分に数えている1を除いた、Weakの数
+        pub fn weak_count(this: &MyRc<T>) -> usize {
+            unsafe { weak(this.ptr).get() - 1 }
+        }
+
+        pub fn downgrade(this: &MyRc<T>) -> Weak<T> {
+            unsafe { increment(weak(this.ptr)) };
+            Weak { ptr: this.ptr }
+        }
+
+        pub fn ptr_eq(this: &MyRc<T>, other: &MyRc<T>) -> bool {
+            this.ptr == other.ptr
+        }
+
+        // 他にMyRcもWeakもなければ、値への&mutを返す
+        // Weakが残っていると、後でupgradeした側と&mutが重なってしまうので返さない
+        pub fn get_mut(this: &mut MyRc<T>) -> Option<&mut T> {
+            if MyRc::strong_count(this) == 1 && MyRc::weak_count(this) == 0 {
+                Some(unsafe { &mut (*this.ptr.as_ptr()).value })
+            } else {
+                None
+            }
+        }
+
+        // 他にMyRcがなければ値を取り出す。あればthisをそのまま返す
+        pub fn try_unwrap(this: MyRc<T>) -> Result<T, MyRc<T>> {
+            if MyRc::strong_count(&this) != 1 {
+                return Err(this);
+            }
+            let this = ManuallyDrop::new(this);
+            unsafe {
+                strong(this.ptr).set(0);
+                let value = ManuallyDrop::take(&mut (*this.ptr.as_ptr()).value);
+                // MyRcたちの分のweakを手放す。Weakが残っていれば、箱はそのWeakが解放する
+                drop(Weak { ptr: this.ptr });
+                Ok(value)
+            }
+        }
+    }
+
+    impl<T> Clone for MyRc<T> {
+        fn clone(&self) -> MyRc<T> {
+            unsafe { increment(strong(self.ptr)) };
+            MyRc { ptr: self.ptr, owns: PhantomData }
+        }
+    }
+
+    impl<T> Deref for MyRc<T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            // MyRcが生きている間はstrongが1以上なので、値はまだドロップされていない
+            unsafe { &(*self.ptr.as_ptr()).value }
+        }
+    }
+
+    impl<T> Drop for MyRc<T> {
+        fn drop(&mut self) {
+            unsafe {
+                let strong = strong(self.ptr);
+                strong.set(strong.get() - 1);
+                if strong.get() == 0 {
+                    // 値のDropが自分へのWeakをupgradeしても、strongが0なのでNoneになる
+                    ManuallyDrop::drop(&mut (*self.ptr.as_ptr()).value);
+                    drop(Weak { ptr: self.ptr });
+                }
+            }
+        }
+    }
+
+    impl<T: std::fmt::Debug> std::fmt::Debug for MyRc<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            (**self).fmt(f)
+        }
+    }
+
+    impl<T> Weak<T> {
+        // 値がまだドロップされていなければ、新しいMyRcを返す
+        pub fn upgrade(&self) -> Option<MyRc<T>> {
+            let strong = unsafe { strong(self.ptr) };
+            if strong.get() == 0 {
+                return None;
+            }
+            increment(strong);
+            Some(MyRc { ptr: self.ptr, owns: PhantomData })
+        }
+
+        pub fn strong_count(&self) -> usize {
+            unsafe { strong(self.ptr).get() }
+        }
+    }
+
+    impl<T> Clone for Weak<T> {
+        fn clone(&self) -> Weak<T> {
+            unsafe { increment(weak(self.ptr)) };
+            Weak { ptr: self.ptr }
+        }
+    }
+
+    impl<T> Drop for Weak<T> {
+        fn drop(&mut self) {
+            unsafe {
+                let weak = weak(self.ptr);
+                weak.set(weak.get() - 1);
+                if weak.get() == 0 {
+                    // 値はもうドロップ済みで、valueはManuallyDropなので、箱の領域を解放するだけになる
+                    drop(Box::from_raw(self.ptr.as_ptr()));
+                }
+            }
+        }
+    }
+}
+
+// ArrayVecの要素がちょうど1回ずつドロップされ、初期化されていない要素は決して読まれないことを確かめる
+// Miriで実行すれば、assume_init_readや&[T]への変換が初期化済みの範囲に収まっているかがここで検査される
+fn exercise_array_vec() {
+    use arrayvec::{ArrayVec, CapacityError};
+    use std::rc::Rc;
+
+    let shared = Rc::new(());
+    let mut vec: ArrayVec<Rc<()>, 4> = ArrayVec::new();
+    for _ in 0..4 {
+        vec.push(Rc::clone(&shared));
+    }
+    assert!(vec.is_full());
+    // 入れられなかった要素は、ドロップされずに返ってくる
+    match vec.try_push(Rc::clone(&shared)) {
+        Err(CapacityError(rejected)) => assert_eq!(Rc::strong_count(&rejected), 6),
+        Ok(()) => unreachable!()
+    }
+    assert_eq!(Rc::strong_count(&shared), 5);
+    drop(vec.pop());
+    let clone = vec.clone();
+    assert_eq!(Rc::strong_count(&shared), 7);
+    drop(vec);
+    drop(clone);
+    assert_eq!(Rc::strong_count(&shared), 1);
+
+    // 要素のDropがpanicしても、残りの要素はドロップされ、二重にはドロップされない
+    struct PanicOnDrop(#[allow(dead_code)] Rc<()>, bool);
+
+    impl Drop for PanicOnDrop {
+        fn drop(&mut self) {
+            if self.1 {
+                panic!("drop");
+            }
+        }
+    }
+
+    let mut vec: ArrayVec<PanicOnDrop, 3> = ArrayVec::new();
+    vec.push(PanicOnDrop(Rc::clone(&shared), false));
+    vec.push(PanicOnDrop(Rc::clone(&shared), true));
+    vec.push(PanicOnDrop(Rc::clone(&shared), false));
+    assert!(catch_panic(move || drop(vec)).is_err());
+    assert_eq!(Rc::strong_count(&shared), 1);
+
+    // ゼロサイズの型と容量ゼロ
+    let mut units: ArrayVec<(), 3> = ArrayVec::default();
+    units.push(());
+    units.push(());
+    assert_eq!((units.len(), units.capacity(), std::mem::size_of_val(&units)), (2, 3, std::mem::size_of::<usize>()));
+    assert_eq!(units.pop(), Some(()));
+    let mut none: ArrayVec<String, 0> = ArrayVec::new();
+    assert!(none.is_full() && none.is_empty());
+    assert_eq!(none.try_push("x".to_string()), Err(CapacityError("x".to_string())));
+}
+
+// SmallVecを、自分の中に置いたまま・ちょうど移す時・ヒープに移した後のどの長さでも一通り操作し、
+// 要素がちょうど1回ずつドロップされることを確かめる
+// Miriで実行すれば、共用体のフィールドの切り替えとヒープへの移し替えがここで検査される
+fn exercise_small_vec() {
+    use smallvec::SmallVec;
+    use std::rc::Rc;
+
+    fn check<const N: usize>() {
+        let shared = Rc::new(());
+        for len in 0..N * 2 + 3 {
+            for consumed in 0..len + 1 {
+                let mut vec: SmallVec<(usize, Rc<()>), N> = SmallVec::new();
+                for i in 0..len {
+                    vec.push((i, Rc::clone(&shared)));
+                }
+                assert_eq!(vec.spilled(), len > N);
+                assert_eq!(vec.iter().map(|e| e.0).collect::<Vec<_>>(), (0..len).collect::<Vec<_>>());
+                assert_eq!(Rc::strong_count(&shared), len + 1);
+
+                // 前後からconsumed個取り出して、残りはIntoIterと一緒にドロップする
+                let mut iter = vec.into_iter();
+                for i in 0..consumed {
+                    let element = if i % 2 == 0 { iter.next() } else { iter.next_back() };
+                    assert!(element.is_some());
+                }
+                assert_eq!(iter.len(), len - consumed);
+                assert_eq!(Rc::strong_count(&shared), len - consumed + 1);
+                drop(iter);
+                assert_eq!(Rc::strong_count(&shared), 1);
+            }
+
+            let mut vec: SmallVec<Rc<()>, N> = (0..len).map(|_| Rc::clone(&shared)).collect();
+            drop(vec.pop());
+            vec.truncate(len / 2);
+            assert_eq!(Rc::strong_count(&shared), vec.len() + 1);
+            // ムーブしても、自分の中に置いた要素は一緒に動く
+            let moved = vec;
+            assert_eq!(moved.len(), std::cmp::min(len.saturating_sub(1), len / 2));
+            drop(moved);
+            assert_eq!(Rc::strong_count(&shared), 1);
+        }
+    }
+
+    check::<0>();
+    check::<1>();
+    check::<4>();
+
+    // ゼロサイズの型は、ヒープに移してもVecと同じく何も確保しない
+    let mut units: SmallVec<(), 2> = SmallVec::new();
+    units.extend(std::iter::repeat_n((), 5));
+    assert!(units.spilled());
+    assert_eq!((units.len(), units.capacity()), (5, usize::MAX));
+    units.clear();
+    assert!(units.is_empty());
+}
+
+// SsoStringに1バイトずつ書き足し、自分の中に置ける長さの境目とヒープへ移る所でStringと食い違わないことを確かめる
+// Miriで実行すれば、共用体のどちらのフィールドを読んでいるかの見分けと、Stringとの領域のやり取りがここで検査される
+fn exercise_sso_string() {
+    use sso::{SsoString, INLINE_CAP};
+
+    let mut sso = SsoString::new();
+    let mut model = String::new();
+    for (i, c) in "abcdefghijklmnopqrstuvwxyzあいうえお".chars().cycle().take(INLINE_CAP * 3).enumerate() {
+        sso.push(c);
+        model.push(c);
+        assert_eq!(sso.as_str(), model);
+        assert_eq!(sso.is_inline(), model.len() <= INLINE_CAP, "{}", i);
+        assert!(sso.len() <= sso.capacity());
+    }
+
+    // Stringとの行き来は、ヒープに置いている時は領域をそのまま受け渡す
+    let string = sso.clone().into_string();
+    let ptr = string.as_ptr();
+    let sso = SsoString::from(string);
+    assert_eq!(sso.as_ptr(), ptr);
+    assert_eq!(String::from(sso), model);
+
+    let short = SsoString::from(String::from("short"));
+    assert!(short.is_inline());
+    assert_eq!(short.into_string(), "short");
+    let mut cleared = SsoString::from(model.as_str());
+    cleared.clear();
+    assert!(cleared.is_inline() && cleared.is_empty());
+}
+
+// MyRcとWeakを作っては捨て、値がちょうど1回ドロップされ、箱がちょうど1回解放されることを確かめる
+// Miriで実行すれば、値をドロップした後にWeakがカウントだけを読む所や、箱の解放の漏れがここで検査される
+fn exercise_my_rc() {
+    use myrc::{MyRc, Weak};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let shared = Rc::new(());
+    let a = MyRc::new(Rc::clone(&shared));
+    let b = a.clone();
+    let weak = MyRc::downgrade(&a);
+    assert!(MyRc::ptr_eq(&a, &b));
+    assert_eq!((MyRc::strong_count(&a), MyRc::weak_count(&a)), (2, 1));
+    drop(a);
+    assert_eq!(weak.strong_count(), 1);
+    let c = weak.upgrade().unwrap();
+    drop((b, c));
+    // 値はドロップされたが、箱はWeakが残っているのでまだ解放されない
+    assert_eq!(Rc::strong_count(&shared), 1);
+    assert!(weak.upgrade().is_none());
+    drop(weak.clone());
+    drop(weak);
+
+    // try_unwrapとget_mut
+    let mut rc = MyRc::new(String::from("only"));
+    MyRc::get_mut(&mut rc).unwrap().push('!');
+    let weak = MyRc::downgrade(&rc);
+    assert!(MyRc::get_mut(&mut rc).is_none());
+    let other = rc.clone();
+    let rc = MyRc::try_unwrap(rc).unwrap_err();
+    drop(other);
+    assert_eq!(MyRc::try_unwrap(rc).unwrap(), "only!");
+    assert!(weak.upgrade().is_none());
+
+    // 親は子をMyRcで、子は親をWeakで指せば、循環しないので木全体がドロップされる
+    struct Node {
+        parent: RefCell<Option<Weak<Node>>>,
+        children: RefCell<Vec<MyRc<Node>>>,
+        _alive: Rc<()>
+    }
+
+    let leaf = MyRc::new(Node { parent: RefCell::new(None), children: RefCell::new(Vec::new()), _alive: Rc::clone(&shared) });
+    let branch = MyRc::new(Node {
+        parent: RefCell::new(None),
+        children: RefCell::new(vec![leaf.clone()]),
+        _alive: Rc::clone(&shared)
+    });
+    *leaf.parent.borrow_mut() = Some(MyRc::downgrade(&branch));
+    let parent = leaf.parent.borrow().as_ref().unwrap().upgrade().unwrap();
+    assert!(MyRc::ptr_eq(&parent, &branch) && MyRc::ptr_eq(&parent.children.borrow()[0], &leaf));
+    drop(parent);
+    assert_eq!((MyRc::strong_count(&branch), MyRc::weak_count(&branch)), (1, 1));
+    assert_eq!(MyRc::strong_count(&leaf), 2);
+    drop(branch);
+    // 親はドロップされたので、子からはもうたどれない
+    assert!(leaf.parent.borrow().as_ref().unwrap().upgrade().is_none());
+    assert_eq!((MyRc::strong_count(&leaf), Rc::strong_count(&shared)), (1, 2));
+    drop(leaf);
+    assert_eq!(Rc::strong_count(&shared), 1);
+
+    // お互いをMyRcで指すと、手元のMyRcを全部捨ててもstrongが0にならず、どちらもドロップされない
+    struct Link {
+        next: RefCell<Option<MyRc<Link>>>,
+        _alive: Rc<()>
+    }
+
+    let a = MyRc::new(Link { next: RefCell::new(None), _alive: Rc::clone(&shared) });
+    let b = MyRc::new(Link { next: RefCell::new(Some(a.clone())), _alive: Rc::clone(&shared) });
+    *a.next.borrow_mut() = Some(b.clone());
+    let weak = MyRc::downgrade(&a);
+    drop((a, b));
+    assert_eq!(Rc::strong_count(&shared), 3);
+    // Weakからたどって循環を断ち切れば、連鎖してどちらもドロップされる
+    let a = weak.upgrade().unwrap();
+    a.next.borrow_mut().take();
+    drop(a);
+    assert!(weak.upgrade().is_none());
+    assert_eq!(Rc::strong_count(&shared), 1);
+}
+
+fn main() {
+    use ascii::{Ascii, AsciiChar, AsciiStr};
+    use ascii::{is_ascii_fast, is_ascii_scalar, is_ascii_word};
+
+    // Miriで実行した時は、GapBufferとタグ付きポインタのunsafeな操作を確かめるだけで終える
+    // この後にはわざと範囲外に書き込む例や、Miriでは時間がかかりすぎる速度の計測がある
+    if cfg!(miri) {
+        exercise_gap_buffer();
+        exercise_tagged_pointers();
+        exercise_tagged_list();
+        exercise_my_vec();
+        exercise_array_vec();
+        exercise_small_vec();
+        exercise_sso_string();
+        exercise_my_rc();
+        return;
+    }
+
+    let mut a: usize = 0;
+    let ptr = &mut a as *mut usize;
+    unsafe {
+        *ptr.offset(3) = 0x7ffff72f484c;
+    }
+
+    // ASCIIだけで構成されたバイトベクタ
+    let bytes: Vec<u8> = b"ASCII and ye shall receive".to_vec();
+    // ヒープの確保もテキストのコピーも行われない呼び出し
+    let ascii: Ascii = Ascii::from_bytes(bytes)
+        .unwrap();
+    // unsafeで実装されておりゼロコストで変換できる
+    let string = String::from(ascii);
+    assert_eq!(string, "ASCII and ye shall receive");
+
+    // バイト単位の比較がそのままASCIIの辞書順となる
+    let apple = Ascii::from_bytes(b"apple".to_vec()).unwrap();
+    let banana = Ascii::from_bytes(b"banana".to_vec()).unwrap();
+    assert!(apple < banana);
+    assert_eq!(apple.cmp(&banana), std::cmp::Ordering::Less);
+    assert_eq!(apple, "apple");
+    assert!(apple == *"apple");
+    assert!(apple.eq_ignore_ascii_case(&Ascii::from_bytes(b"APPLE".to_vec()).unwrap()));
+    assert!(!apple.eq_ignore_ascii_case(&banana));
+
+    {
+        // どの長さ・どの位置に非ASCIIバイトがあっても高速版と素朴な実装の結果が一致する
+        for len in 0..40 {
+            let mut bytes = vec![b'a'; len];
+            assert!(is_ascii_word(&bytes[..]) && is_ascii_fast(&bytes[..]));
+            for i in 0..len {
+                bytes[i] = 0x80;
+                assert!(!is_ascii_scalar(&bytes[..]));
+                assert!(!is_ascii_word(&bytes[..]) && !is_ascii_fast(&bytes[..]));
+                // アラインメントのずれた位置から始まるスライスでも同じ
+                assert_eq!(is_ascii_fast(&bytes[1.min(len)..]), is_ascii_scalar(&bytes[1.min(len)..]));
+                bytes[i] = b'a';
+            }
+        }
+        // 数MBの入力での速度はbenches/ascii.rsで比べる
+    }
+
+    #[cfg(feature = "serde")]
+    {
+        use serde::Deserialize;
+        use serde::de::IntoDeserializer;
+        use serde::de::value::{Error, StrDeserializer};
+        // デシリアライズ時にASCIIでない入力はエラーになる
+        let ok: StrDeserializer<Error> = "config".into_deserializer();
+        assert_eq!(Ascii::deserialize(ok).unwrap(), "config");
+        let ng: StrDeserializer<Error> = "caf\u{e9}".into_deserializer();
+        let error = Ascii::deserialize(ng).unwrap_err().to_string();
+        assert!(error.starts_with("invalid value: string \"caf\u{e9}\""), "{}", error);
+        let ng: serde::de::value::StringDeserializer<Error> = String::from("caf\u{e9}").into_deserializer();
+        assert!(Ascii::deserialize(ng).unwrap_err().to_string().starts_with("invalid value: string"));
+    }
+
+    // 検証に失敗した位置とバイトを報告し、元のバッファを取り戻せる
+    let error = Ascii::from_bytes(b"na\xefve".to_vec()).unwrap_err();
+    assert_eq!(error.valid_up_to(), 2);
+    assert_eq!(error.invalid_byte(), 0xef);
+    assert_eq!(error.to_string(), "invalid ASCII byte 0xef at index 2");
+    #[cfg(feature = "std")]
+    {
+        let error: Box<dyn std::error::Error> = Box::new(error);
+        assert!(error.source().is_none());
+    }
+    let error = Ascii::from_bytes(vec![0xff]).unwrap_err();
+    assert_eq!(error.as_bytes(), &[0xff]);
+    assert_eq!(error.into_bytes(), vec![0xff]);
+
+    // 不正なバイトを置き換えて必ずAsciiを作る
+    let dirty = b"caf\xc3\xa9 log".to_vec();
+    assert_eq!(Ascii::from_bytes_lossy(dirty.clone()), "caf?? log");
+    let star = AsciiChar::new(b'*').unwrap();
+    assert_eq!(star.as_char(), '*');
+    assert_eq!(AsciiChar::new(0xc3), None);
+    assert_eq!(Ascii::from_bytes_lossy_with(dirty, star), "caf** log");
+    // 全てASCIIなら受け取ったバッファをそのまま使う
+    let clean = b"clean".to_vec();
+    let clean_ptr = clean.as_ptr();
+    let clean = Ascii::from_bytes_lossy(clean);
+    assert_eq!(clean.as_bytes().as_ptr(), clean_ptr);
+    // 借用したバイト列からは、全てASCIIならコピーせずに借用したまま返す
+    use std::borrow::Cow;
+    let clean: &[u8] = b"clean";
+    let borrowed = AsciiStr::from_bytes_lossy(clean);
+    assert!(matches!(borrowed, Cow::Borrowed(_)));
+    assert_eq!(borrowed.as_bytes().as_ptr(), clean.as_ptr());
+    let replaced = AsciiStr::from_bytes_lossy_with(b"caf\xc3\xa9", star);
+    assert!(matches!(replaced, Cow::Owned(_)));
+    assert_eq!(replaced.as_str(), "caf**");
+
+    // Debug表示では制御文字がエスケープされる
+    let bell = Ascii::from_bytes(b"tab\there\n\x07".to_vec()).unwrap();
+    assert_eq!(format!("{:?}", bell), r#""tab\there\n\x07""#);
+    assert_eq!(bell.escape_default().collect::<String>(), r"tab\there\n\x07");
+    assert_eq!(bell.escape_default().to_string(), r"tab\there\n\x07");
+
+    // Stringと同じように演算子で連結できる
+    let mut greeting = Ascii::from_bytes(b"Hello".to_vec()).unwrap();
+    let comma = AsciiStr::from_bytes(b", ").unwrap();
+    greeting += comma;
+    let world = Ascii::from_bytes(b"world".to_vec()).unwrap();
+    let mut greeting = greeting + &world;
+    greeting.extend(AsciiChar::new(b'!'));
+    greeting.extend(vec![comma, &world]);
+    assert_eq!(greeting, "Hello, world!, world");
+    assert_eq!(greeting.as_str(), "Hello, world!, world");
+    assert_eq!(greeting.len(), 20);
+    assert!(!greeting.is_empty());
+    assert_eq!(AsciiStr::from_bytes(b"\xff"), None);
+
+    // Stringに変換せずに1文字や部分列を取り出せる
+    assert_eq!(greeting[0], AsciiChar::new(b'H').unwrap());
+    assert_eq!(greeting.get(4).map(AsciiChar::as_char), Some('o'));
+    assert_eq!(greeting.get(20), None);
+    assert_eq!(greeting[7..12].as_str(), "world");
+    assert_eq!(&greeting[..5], &greeting[0..=4]);
+    assert_eq!(greeting[15..].as_str(), "world");
+    assert_eq!(greeting[..=4].len(), 5);
+    assert_eq!(greeting[..].len(), greeting.len());
+
+    {
+        use std::borrow::Cow;
+        use std::collections::HashMap;
+
+        // 小文字に変換する必要がある時だけメモリを確保する
+        fn to_lower(s: &AsciiStr) -> Cow<'_, AsciiStr> {
+            if s.as_bytes().iter().any(u8::is_ascii_uppercase) {
+                let mut owned = s.to_owned();
+                owned.make_ascii_lowercase();
+                Cow::Owned(owned)
+            } else {
+                Cow::Borrowed(s)
+            }
+        }
+        let lower = AsciiStr::from_bytes(b"content-type").unwrap();
+        assert!(matches!(to_lower(lower), Cow::Borrowed(_)));
+        let upper = AsciiStr::from_bytes(b"Content-Type").unwrap();
+        assert!(matches!(to_lower(upper), Cow::Owned(_)));
+        assert_eq!(to_lower(upper).as_ref(), lower);
+
+        // 借用したキーでHashMapを引ける
+        let mut headers: HashMap<Ascii, u32> = HashMap::new();
+        headers.insert(lower.to_owned(), 1);
+        assert_eq!(headers.get(lower), Some(&1));
+        assert_eq!(headers.get(&greeting[..5]), None);
+        assert_eq!(headers.get("content-type"), Some(&1));
+
+        // Ascii、AsciiStr、strのハッシュ値は全て一致する
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        fn hash_of<T: Hash + ?Sized>(value: &T) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+        let key = lower.to_owned();
+        assert_eq!(hash_of(&key), hash_of("content-type"));
+        assert_eq!(hash_of(lower), hash_of("content-type"));
+        assert_ne!(hash_of(&key), hash_of("content-typ"));
+    }
+
+    // プロトコルの定数を実行時の検証なしで宣言できる
+    const GET: &AsciiStr = AsciiStr::from_static(b"GET ");
+    let request = Ascii::from_bytes(b"GET /index.html".to_vec()).unwrap();
+    assert_eq!(&request[..4], GET);
+    assert_eq!(ascii!("HTTP/1.1").as_str(), "HTTP/1.1");
+    // const S: &AsciiStr = AsciiStr::from_static(b"caf\xc3\xa9"); // evaluation panicked: non-ASCII byte in AsciiStr::from_static
+    // ascii!("caf\u{e9}"); // 同じくコンパイルエラーになる
+
+    {
+        use ascii::encodings;
+
+        // Latin-1とは0x7fまで共通で、それ以外は置き換える
+        assert_eq!(encodings::from_latin1_lossy(b"na\xefve"), "na?ve");
+        assert_eq!(encodings::to_latin1(&request), b"GET /index.html");
+
+        // メインフレームのEBCDIC(CP037)と相互変換する
+        let hello = ascii!("Hello, [world]!\n");
+        let ebcdic = encodings::encode_cp037(hello);
+        assert_eq!(&ebcdic[..5], &[0xc8, 0x85, 0x93, 0x93, 0x96]);
+        assert_eq!(encodings::decode_cp037(&ebcdic).unwrap().as_str(), hello.as_str());
+        // 0x9fはCP037では'¤'でASCIIに対応する文字が無い
+        let error = encodings::decode_cp037(&[0xc8, 0x9f]).unwrap_err();
+        assert_eq!(error, encodings::Cp037Error { index: 1, byte: 0x9f });
+        assert_eq!(error.to_string(), "CP037 byte 0x9f at index 1 has no ASCII equivalent");
+    }
+
+    {
+        // Stringに変換せずに行やフィールドに分割する
+        let log = ascii!("GET /a 200\r\nPOST  /b\t404\n\nlast");
+        let lines: Vec<&str> = log.lines().map(AsciiStr::as_str).collect();
+        assert_eq!(lines, ["GET /a 200", "POST  /b\t404", "", "last"]);
+        let fields: Vec<&str> = log.lines().nth(1).unwrap().split_whitespace().map(AsciiStr::as_str).collect();
+        assert_eq!(fields, ["POST", "/b", "404"]);
+        assert_eq!(ascii!(" \t ").split_whitespace().count(), 0);
+        let csv: Vec<&str> = ascii!("a,,b,").split(AsciiChar::new(b',').unwrap()).map(AsciiStr::as_str).collect();
+        assert_eq!(csv, ["a", "", "b", ""]);
+        assert_eq!(ascii!("").lines().count(), 0);
+    }
+
+    {
+        // Stringに変換せずにパターンを探す
+        let header = ascii!("GET /search?q=rust HTTP/1.1");
+        assert!(header.starts_with("GET "));
+        assert!(header.starts_with(GET));
+        assert!(header.ends_with(AsciiChar::new(b'1').unwrap()));
+        assert!(!header.ends_with("HTTP/2"));
+        assert!(header.contains(ascii!("?q=")));
+        assert!(header.contains(&request[..4]));
+        assert!(!header.contains("r\u{fc}st"));
+        assert_eq!(header.find("rust"), Some(14));
+        assert_eq!(header.find(AsciiChar::new(b'/').unwrap()), Some(4));
+        assert_eq!(header.find(""), Some(0));
+        assert_eq!(header.find("HTTP/1.1 "), None);
+
+        // 長い入力でもバイト単位の探索と一致する
+        let mut long = [b'.'; 100];
+        for i in 0..long.len() {
+            long[i] = b'x';
+            assert_eq!(ascii::memchr(b'x', &long[..]), Some(i));
+            assert_eq!(ascii::memchr(b'x', &long[i + 1..]), None);
+            long[i] = b'.';
+        }
+        let request_ref = &request;
+        assert_eq!(AsciiStr::from_bytes(b"GET /index.html!").unwrap().find(request_ref), Some(0));
+    }
+
+    {
+        use ascii::{AsciiChars, AsciiValidator};
+
+        // 少しずつ届く入力を届いた分だけ検証する
+        let mut validator = AsciiValidator::new();
+        assert!(validator.is_empty());
+        for chunk in [&b"HTTP/1.1 "[..], b"200 ", b"OK"].iter() {
+            validator.feed(chunk).unwrap();
+        }
+        assert_eq!(validator.len(), 15);
+        assert_eq!(validator.finish(), "HTTP/1.1 200 OK");
+
+        let mut validator = AsciiValidator::new();
+        validator.feed(b"abc").unwrap();
+        let error = validator.feed(b"d\xffe").unwrap_err();
+        assert_eq!(error.valid_up_to(), 4);
+        assert_eq!(error.as_bytes(), b"abcd\xffe");
+        assert!(validator.is_empty());
+
+        // 任意のIterator<Item = u8>を包んで検証する
+        let mut chars = AsciiChars::new(b"ok\x80ng".iter().cloned());
+        let prefix: Ascii = chars.by_ref().collect();
+        assert_eq!(prefix, "ok");
+        assert_eq!(chars.invalid(), Some((2, 0x80)));
+        assert_eq!(chars.next(), None);
+        let mut chars = AsciiChars::new(vec![b'o', b'k']);
+        assert_eq!(chars.by_ref().collect::<Ascii>(), "ok");
+        assert_eq!(chars.invalid(), None);
+    }
+
+    #[cfg(feature = "std")]
+    {
+        use std::io::{ErrorKind, Read, Write};
+        use ascii::io::{AsciiReader, AsciiWriter};
+
+        // 読み出し時に検証する
+        let mut text = String::new();
+        AsciiReader::new(&b"plain text"[..]).read_to_string(&mut text).unwrap();
+        assert_eq!(text, "plain text");
+        let error = AsciiReader::new(&b"caf\xc3\xa9"[..]).read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "invalid ASCII byte 0xc3 at index 3");
+        let mut lossy = AsciiReader::lossy(&b"caf\xc3\xa9"[..], AsciiChar::new(b'_').unwrap());
+        let mut text = String::new();
+        lossy.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "caf__");
+        assert!(lossy.into_inner().is_empty());
+
+        // 書き込み時に検証する
+        let mut writer = AsciiWriter::new(Vec::new());
+        writer.write_ascii(ascii!("GET ")).unwrap();
+        writer.write_all(b"/index.html").unwrap();
+        let error = writer.write_all(b" \xe2\x9c\x93").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        writer.flush().unwrap();
+        assert_eq!(writer.into_inner(), b"GET /index.html ");
+    }
+
+    {
+        use ascii::codec::{self, DecodeError};
+
+        // 符号化の結果は最初からAsciiとして得られる
+        let hex = codec::encode_hex(b"\x00\x7f\xff");
+        assert_eq!(hex, "007fff");
+        assert_eq!(codec::decode_hex(&hex).unwrap(), b"\x00\x7f\xff");
+        assert_eq!(codec::decode_hex(ascii!("7F")).unwrap(), b"\x7f");
+        assert_eq!(codec::decode_hex(ascii!("abc")), Err(DecodeError::InvalidLength(3)));
+        assert_eq!(codec::decode_hex(ascii!("0g")), Err(DecodeError::InvalidByte(1, b'g')));
+
+        for (plain, encoded) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foobar", "Zm9vYmFy")].iter() {
+            assert_eq!(codec::encode_base64(plain.as_bytes()), *encoded);
+            assert_eq!(codec::decode_base64(AsciiStr::from_bytes(encoded.as_bytes()).unwrap()).unwrap(), plain.as_bytes());
+        }
+        assert_eq!(codec::decode_base64(ascii!("Zm9")), Err(DecodeError::InvalidLength(3)));
+        assert_eq!(codec::decode_base64(ascii!("Zm=v")), Err(DecodeError::InvalidByte(2, b'=')));
+        assert_eq!(codec::decode_base64(ascii!("Zg==")).unwrap(), b"f");
+        assert_eq!(DecodeError::InvalidByte(2, b'=').to_string(), "invalid byte '=' at index 2");
+    }
+
+    {
+        use std::ffi::CStr;
+        use std::os::raw::c_char;
+        use ascii::AsciiNulTerminated;
+
+        extern "C" {
+            fn strlen(s: *const c_char) -> usize;
+        }
+
+        // 途中にNULを含むテキストはC言語の文字列にできない
+        assert_eq!(request.to_cstring().unwrap().as_bytes(), b"GET /index.html");
+        let nul = Ascii::from_bytes(b"a\0b".to_vec()).unwrap();
+        assert_eq!(nul.to_cstring().unwrap_err().nul_position(), 1);
+        assert!(AsciiNulTerminated::new(nul).is_err());
+
+        let c_str = CStr::from_bytes_with_nul(b"from C\0").unwrap();
+        assert_eq!(Ascii::from_cstr(c_str).unwrap(), "from C");
+        let latin1 = CStr::from_bytes_with_nul(b"caf\xe9\0").unwrap();
+        assert_eq!(Ascii::from_cstr(latin1).unwrap_err().valid_up_to(), 3);
+        assert!(AsciiNulTerminated::from_cstr(latin1).is_err());
+
+        // コピーせずにC言語の関数へ渡す
+        let terminated = AsciiNulTerminated::new(request.clone()).unwrap();
+        assert_eq!(unsafe { strlen(terminated.as_ptr()) }, 15);
+        assert_eq!(terminated.as_c_str().to_bytes_with_nul(), b"GET /index.html\0");
+        assert_eq!(terminated.as_ascii_str(), &request[..]);
+        assert_eq!(AsciiNulTerminated::from_cstr(c_str).unwrap().into_ascii(), "from C");
+    }
+
+    #[cfg(feature = "testing")]
+    {
+        use arbitrary::{Arbitrary, Unstructured};
+        use proptest::test_runner::{Config, TestRunner};
+        use ascii::testing;
+
+        // ファザーが与える任意のバイト列から必ず正しいAsciiができる
+        let mut u = Unstructured::new(&[4, 0xff, b'a', 0x80, b'z', 1, 2]);
+        let fuzzed = Ascii::arbitrary(&mut u).unwrap();
+        assert!(Ascii::from_bytes(fuzzed.as_bytes().to_vec()).is_ok());
+
+        // mainから実行するので失敗したケースをファイルに保存しない
+        let mut runner = TestRunner::new(Config { failure_persistence: None, ..Config::default() });
+        runner.run(&testing::ascii(), |ascii| {
+            assert_eq!(Ascii::from_bytes(ascii.as_bytes().to_vec()).unwrap(), ascii);
+            Ok(())
+        }).unwrap();
+        runner.run(&testing::almost_ascii(), |bytes| {
+            let error = Ascii::from_bytes(bytes).unwrap_err();
+            assert!(error.invalid_byte() >= 0x80);
+            assert!(error.as_bytes()[..error.valid_up_to()].is_ascii());
+            Ok(())
+        }).unwrap();
+        runner.run(&testing::multibyte_utf8(), |bytes| {
+            assert!(std::str::from_utf8(&bytes).is_ok());
+            assert!(Ascii::from_bytes(bytes).is_err());
+            Ok(())
+        }).unwrap();
+    }
+
+    {
+        use std::convert::TryFrom;
+        use ascii::{AsciiArray, CapacityError};
+
+        // ヒープを確保せずにASCIIテキストを組み立てる
+        let mut frame: AsciiArray<8> = AsciiArray::new();
+        assert!(frame.is_empty());
+        frame.try_push_str(ascii!("AT+")).unwrap();
+        frame.try_push(AsciiChar::new(b'Z').unwrap()).unwrap();
+        assert_eq!(frame.as_str(), "AT+Z");
+        assert_eq!(frame.try_push_str(ascii!("ABCDE")), Err(CapacityError));
+        assert_eq!(frame.len(), 4);
+        assert_eq!(frame.capacity(), 8);
+        let copied = frame;
+        assert_eq!(format!("{:?}", copied), r#""AT+Z""#);
+        assert_eq!(Ascii::from(copied), "AT+Z");
+        assert_eq!(AsciiArray::<4>::try_from(ascii!("OK\r\n")).unwrap().to_ascii(), "OK\r\n");
+        assert_eq!(AsciiArray::<3>::try_from(ascii!("OK\r\n")).unwrap_err().to_string(), "insufficient capacity");
+        let mut full = AsciiArray::<1>::default();
+        full.try_push(AsciiChar::new(b'!').unwrap()).unwrap();
+        assert!(full.try_push(AsciiChar::new(b'!').unwrap()).is_err());
+    }
+
+    {
+        use ascii::archive::{self, ArchiveError, ArchivedAscii};
+
+        // 一度検証すれば、後はコピーせずにレコードを借用できる
+        let records = [ascii!("alpha"), ascii!(""), ascii!("gamma ray")];
+        let bytes = archive::archive(records.iter().cloned()).unwrap();
+        let archived = ArchivedAscii::new(&bytes).unwrap();
+        assert_eq!(archived.len(), 3);
+        assert!(!archived.is_empty());
+        assert!(archived.iter().eq(records.iter().cloned()));
+        let first = archived.into_iter().next().unwrap();
+        assert_eq!(first.as_bytes().as_ptr(), bytes[8..].as_ptr());
+
+        // 壊れたバイト列は検証で弾く
+        assert_eq!(ArchivedAscii::new(&bytes[..bytes.len() - 1]).unwrap_err(), ArchiveError::Truncated(21));
+        assert_eq!(ArchivedAscii::new(&bytes[..2]).unwrap_err(), ArchiveError::Truncated(0));
+        let mut corrupted = bytes.clone();
+        corrupted[9] = 0xff;
+        assert_eq!(ArchivedAscii::new(&corrupted).unwrap_err(), ArchiveError::NotAscii(9));
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(ArchivedAscii::new(&trailing).unwrap_err(), ArchiveError::TrailingBytes(bytes.len()));
+        assert!(ArchivedAscii::new(&archive::archive(Vec::new()).unwrap()).unwrap().is_empty());
+    }
+
+    {
+        use std::collections::{BTreeMap, HashMap};
+        use ascii::AsciiCaseless;
+
+        let caseless = |s: &AsciiStr| AsciiCaseless::new(s.to_owned());
+
+        // 大文字・小文字の違いを無視してキーを引ける
+        let mut headers = HashMap::new();
+        headers.insert(caseless(ascii!("Content-Length")), 42);
+        assert_eq!(headers.get(&caseless(ascii!("content-length"))), Some(&42));
+        assert_eq!(headers.get(&caseless(ascii!("CONTENT-LENGTH"))), Some(&42));
+        assert_eq!(headers.get(&caseless(ascii!("Content-Type"))), None);
+
+        let mut sorted = BTreeMap::new();
+        sorted.insert(caseless(ascii!("b")), 1);
+        sorted.insert(caseless(ascii!("A")), 2);
+        sorted.insert(caseless(ascii!("B")), 3);
+        let keys: Vec<&str> = sorted.keys().map(|key| key.as_str()).collect();
+        assert_eq!(keys, ["A", "b"]);
+        assert_eq!(sorted[&caseless(ascii!("a"))], 2);
+        assert_eq!(sorted[&caseless(ascii!("b"))], 3);
+
+        // 元の綴りは保たれる
+        assert!(caseless(ascii!("_")) < caseless(ascii!("a")));
+        assert_eq!(format!("{:?}", caseless(ascii!("X-Id"))), r#""X-Id""#);
+        assert_eq!(caseless(ascii!("X-Id")).into_inner(), "X-Id");
+    }
+
+    let illegal_bytes = vec![0xf7, 0xbf, 0xbf, 0xbf];
+    let _illegal_ascii = unsafe {
+        Ascii::from_bytes_unchecked(illegal_bytes)
+    };
+
+    // 無効なUTF8が入っている
+    // let bogus: String = _illegal_ascii.into();
+    // assert_eq!(bogus.chars().next().unwrap() as u32, 0x1ffffff);
+
+    let i = 10;
+    very_trustworthy(&i);
+    println!("{}", i * 100); // 1000が期待値だが、very_trustworthy()の中で書き換えられて2000になる
+
+    let mut x = 10;
+    let ptr_x = &mut x as *mut i32; // *mut T は T へのrawポインタで、参照先の変更を許す
+
+    let y = Box::new(20);
+    let ptr_y = &*y as *const i32;  // *const T は T へのrawポインタで、参照先の読み出しのみを許す
+
+    unsafe {
+        *ptr_x += *ptr_y;
+    }
+
+    assert_eq!(x, 30); // *mut i32型のptr_xを通してポインタの指す値が更新されている
+
+    // 関数option_to_raw()の呼び出しにはunsafeブロックが登場しない rawポインタの参照解決だけがunsafe
+    assert!(!option_to_raw(Some(&("pea", "pod"))).is_null());
+    assert_eq!(option_to_raw::<i32>(None), std::ptr::null());
+
+    // 先頭の要素と最後の要素のポインタ距離をrawポインタを渡して計算させる
+    let trucks = vec!["garbage truck", "dump truck", "moonstruck"];
     let first = &trucks[0];
     let last = &trucks[2];
-    assert_eq!(distance(last, first), 2);
-    assert_eq!(distance(first, last), -2);
+    // 両方ともtrucksの要素を指しているので、element_distanceの前提を満たす
+    assert_eq!(unsafe { element_distance(last, first) }, 2);
+    assert_eq!(unsafe { element_distance(first, last) }, -2);
+    // 終端の1つ先も同じ割り当ての中として扱える
+    assert_eq!(unsafe { element_distance(trucks.as_ptr_range().end, first) }, 3);
+
+    // checked_distanceはsliceの要素かどうかを確かめるので、unsafeブロックが要らない
+    assert_eq!(checked_distance(&trucks, last, first), Some(2));
+    assert_eq!(checked_distance(&trucks[1..], last, &trucks[1]), Some(1));
+    assert_eq!(checked_distance(&trucks[1..], last, first), None); // firstは部分スライスの外
+    let elsewhere = "garbage truck";
+    assert_eq!(checked_distance(&trucks, &elsewhere, first), None);
+    let pairs = [[1u16, 2], [3, 4]];
+    let halves = unsafe { std::slice::from_raw_parts(pairs.as_ptr() as *const u16, 4) };
+    let straddling = unsafe { &*(halves[1..3].as_ptr() as *const [u16; 2]) }; // 要素の境目をまたぐ参照
+    assert_eq!(checked_distance(&pairs, straddling, &pairs[0]), None);
+    assert_eq!(checked_distance(&[(), ()], &(), &()), None);
+
+    // &vec![42_u8] as *const String; // casting `&std::vec::Vec<u8>` as `*const std::string::String` is invalid
+    let _ = &vec![42_u8] as *const Vec<u8> as *const String; // この変換は許される
+
+    let vec = vec![10, 20, 30];
+    let flagged = ref_with_flag::RefWithFlag::new(&vec, true);
+    // 1バイト単位の型でnewを呼ぶとビルドできないことは、tests/ui/ref_with_flag_align.rsで確かめる
+    assert_eq!(flagged.get_ref()[1], 20); // ラップしたvec参照の要素を取り出す
+    assert!(flagged.get_flag()); // ラップしたvecのメモリに保存した値boolを取り出す
+
+    // 計算機プロセッサによって型のサイズとアラインメントが決定される
+    assert_eq!(std::mem::size_of::<i64>(), 8);
+    let pair = layout_of!((i32, i32) { 0, 1 });
+    assert_eq!((pair.size(), pair.align(), pair.padding()), (8, 4, 0));
+
+    let slice: &[i32] = &[1, 3, 9, 27, 81];
+    assert_eq!(std::mem::size_of_val(slice), 20);
+    let text: &str = "alligator";
+    assert_eq!(std::mem::size_of_val(text), 9);
+
+    // トレイトオブジェクトそのものではなく、トレイトオブジェクトが指す値のサイズ・アラインメントを返す
+    use std::fmt::Display;
+    let unremarkable: &dyn Display = &193_u8;
+    let remarkable: &dyn Display = &0.0072973525664;
+    assert_eq!(std::mem::size_of_val(unremarkable), 1);
+    assert_eq!(std::mem::align_of_val(remarkable), 8);
+    {
+        let pot = "pasta".to_string();
+        // 宣言と初期化を分けて、ムーブで初期化されることを見せる
+        #[allow(clippy::needless_late_init)]
+        let _plate;
+        _plate = pot; // 変数potのメモリアドレスは未初期化状態になる
+    }
+    {
+        let mut noodles = vec!["udon".to_string()]; // noodles[0]のみメモリ確保された状態
+        let soba = "soba".to_string();
+        #[allow(clippy::needless_late_init)]
+        let _last; // 最終的に変数lastだけが所有権を持つ
+        noodles.push(soba); // noodles[1]にメモリ確保され、変数sobaは未初期化状態になる
+        _last = noodles.pop().unwrap(); // noodles[1]は未初期化状態になる
+    }
+
+    {
+        use gap::GapBuffer;
+        // type GapBufferを使ったコード
+        let mut buf = GapBuffer::new();
+        buf.insert_iter("Lord of the Rings".chars());
+        buf.set_position(12);
+        buf.insert_iter("Onion ".chars());
+
+        assert_eq!('R', buf.remove().unwrap());
+        assert_eq!('i', buf.remove().unwrap());
+        assert_eq!('n', buf.remove().unwrap());
+        assert_eq!('g', buf.remove().unwrap());
+        assert_eq!('s', buf.remove().unwrap());
+
+        let n = buf.get(buf.position());
+        assert_eq!(None, n);
+        let m = buf.get(buf.len());
+        assert_eq!(None, m);
+    }
+
+    {
+        use gap::GapBuffer;
+        // ギャップがどの位置にあってもギャップを飛ばして先頭から順に辿れる
+        let text = "gap buffer";
+        for pos in 0..=text.len() {
+            let mut buf = GapBuffer::new();
+            buf.insert_iter(text.chars());
+            buf.set_position(pos);
+            assert_eq!(buf.iter().collect::<String>(), text);
+            assert_eq!(buf.iter().size_hint(), (text.len(), Some(text.len())));
+
+            for c in &mut buf {
+                *c = c.to_ascii_uppercase();
+            }
+            assert_eq!((&buf).into_iter().collect::<String>(), "GAP BUFFER");
+            assert_eq!(buf.position(), pos);
+
+            // 途中でやめても残りの要素はドロップされる
+            let mut words = GapBuffer::new();
+            words.insert_iter(text.split(' ').map(String::from));
+            words.set_position(pos.min(words.len()));
+            let mut into_iter = words.into_iter();
+            assert_eq!(into_iter.size_hint(), (2, Some(2)));
+            assert_eq!(into_iter.next().as_deref(), Some("gap"));
+        }
+        let owned: Vec<String> = {
+            let mut buf = GapBuffer::new();
+            buf.insert_iter(vec!["a".to_string(), "c".to_string()]);
+            buf.set_position(1);
+            buf.insert("b".to_string());
+            buf.into_iter().collect()
+        };
+        assert_eq!(owned, ["a", "b", "c"]);
+        assert_eq!(GapBuffer::<u8>::new().iter().next(), None);
+    }
+
+    {
+        use gap::GapBuffer;
+        // 添字で読み書きできる
+        let mut buf = GapBuffer::new();
+        buf.insert_iter("cat".chars());
+        buf.set_position(1);
+        assert_eq!(buf[0], 'c');
+        assert_eq!(buf[2], 't');
+        buf[1] = 'u';
+        buf[2] = 'p';
+        assert_eq!(buf.iter().collect::<String>(), "cup");
+
+        // 範囲外の添字はpanicを起こす
+        let result = catch_panic(|| buf[3]);
+        assert!(result.is_err());
+    }
+
+    {
+        use gap::GapBuffer;
+        // 取り出さずにその場で書き換えるのでギャップは動かない
+        let mut buf = GapBuffer::new();
+        buf.insert_iter(vec![1, 2, 3]);
+        buf.set_position(1);
+        if let Some(second) = buf.get_mut(1) {
+            *second *= 10;
+        }
+        assert_eq!(buf.position(), 1);
+        assert_eq!(buf.iter().cloned().collect::<Vec<_>>(), [1, 20, 3]);
+        assert_eq!(buf.get_mut(3), None);
+    }
+
+    {
+        use std::cell::Cell;
+        use std::panic::AssertUnwindSafe;
+        use std::rc::Rc;
+        use gap::GapBuffer;
+
+        // 複製してもギャップの位置は変わらない
+        let mut buf = GapBuffer::new();
+        buf.insert_iter(vec!["x".to_string(), "y".to_string(), "z".to_string()]);
+        buf.set_position(1);
+        let mut copy = buf.clone();
+        assert_eq!(copy.position(), 1);
+        assert_eq!(copy.capacity(), buf.capacity());
+        copy.insert("w".to_string());
+        assert_eq!(buf.iter().cloned().collect::<String>(), "xyz");
+        assert_eq!(copy.iter().cloned().collect::<String>(), "xwyz");
+
+        // n回目のcloneでpanicする型
+        struct Fragile {
+            clones_left: Rc<Cell<usize>>,
+            drops: Rc<Cell<usize>>
+        }
+        impl Clone for Fragile {
+            fn clone(&self) -> Fragile {
+                if self.clones_left.get() == 0 {
+                    panic!("clone failed");
+                }
+                self.clones_left.set(self.clones_left.get() - 1);
+                Fragile { clones_left: self.clones_left.clone(), drops: self.drops.clone() }
+            }
+        }
+        impl Drop for Fragile {
+            fn drop(&mut self) {
+                self.drops.set(self.drops.get() + 1);
+            }
+        }
+
+        // ギャップの前後どちらでpanicしても、複製済みの要素だけがちょうど1回ずつドロップされる
+        for clones_left in 0..5 {
+            let clones = Rc::new(Cell::new(clones_left));
+            let drops = Rc::new(Cell::new(0));
+            let mut buf = GapBuffer::new();
+            buf.insert_iter((0..5).map(|_| Fragile { clones_left: clones.clone(), drops: drops.clone() }));
+            buf.set_position(2);
+            assert!(catch_panic(AssertUnwindSafe(|| buf.clone())).is_err());
+            assert_eq!(drops.get(), clones_left);
+            drop(buf);
+            assert_eq!(drops.get(), clones_left + 5);
+        }
+    }
+
+    {
+        use gap::GapBuffer;
+        // 内容と挿入点が一目で分かる
+        let mut buf = GapBuffer::new();
+        assert_eq!(format!("{:?}", buf), "[|]");
+        buf.insert_iter(vec!["a", "b", "c"]);
+        assert_eq!(format!("{:?}", buf), r#"["a", "b", "c" |]"#);
+        buf.set_position(2);
+        assert_eq!(format!("{:?}", buf), r#"["a", "b" | "c"]"#);
+        buf.set_position(0);
+        assert_eq!(format!("{:?}", buf), r#"[| "a", "b", "c"]"#);
+    }
+
+    {
+        use gap::GapBuffer;
+        // collect()で作れる
+        let mut buf: GapBuffer<char> = "text".chars().collect();
+        assert_eq!(buf.len(), 4);
+        assert_eq!(buf.position(), 4);
+        // 大きさが分かっているイテレータなら再確保は一度で済む
+        buf.set_position(0);
+        buf.extend(std::iter::repeat_n('-', 100));
+        assert_eq!(buf.capacity(), 104);
+        assert_eq!(buf.position(), 100);
+        assert_eq!(buf.iter().skip(99).collect::<String>(), "-text");
+    }
+
+    {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        use gap::GapBuffer;
+
+        fn hash_of<T: Hash + ?Sized>(value: &T) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        // ギャップの位置が違っても内容が同じなら等しく、ハッシュ値も一致する
+        let mut left: GapBuffer<u32> = (0..10).collect();
+        let mut right: GapBuffer<u32> = (0..10).collect();
+        for pos in 0..=10 {
+            left.set_position(pos);
+            right.set_position(10 - pos);
+            assert_eq!(left, right);
+            assert_eq!(left, (0..10).collect::<Vec<_>>());
+            assert!(left == [0, 1, 2, 3, 4, 5, 6, 7, 8, 9][..]);
+            assert_eq!(hash_of(&left), hash_of(&right));
+            assert_eq!(hash_of(&left), hash_of(&(0..10).collect::<Vec<u32>>()[..]));
+        }
+        right.remove();
+        assert_ne!(left, right);
+        let words: GapBuffer<&str> = vec!["a", "b"].into_iter().collect();
+        assert_eq!(hash_of(&words), hash_of(&["a", "b"][..]));
+    }
+
+    {
+        use gap::GapBuffer;
+        // 最初に必要な容量を確保しておけば途中で再確保されない
+        let mut buf = GapBuffer::with_capacity(64);
+        assert_eq!(buf.capacity(), 64);
+        buf.insert_iter("hello world".chars());
+        assert_eq!(buf.capacity(), 64);
+
+        // ギャップを一度に広げる
+        buf.set_position(5);
+        buf.reserve(100);
+        assert!(buf.capacity() >= 111);
+        assert_eq!(buf.position(), 5);
+        let capacity = buf.capacity();
+        buf.insert_iter(std::iter::repeat_n(',', 100));
+        assert_eq!(buf.capacity(), capacity);
+
+        // 要素を詰めて余分な容量を手放す
+        buf.shrink_to_fit();
+        assert_eq!(buf.capacity(), 111);
+        assert_eq!(buf.position(), 105);
+        assert_eq!(buf.iter().filter(|&&c| c != ',').collect::<String>(), "hello world");
+        buf.insert('!');
+        assert_eq!(buf.len(), 112);
+    }
+
+    {
+        use std::rc::Rc;
+        use gap::GapBuffer;
+
+        // 選択範囲を一度に取り除く
+        let mut buf: GapBuffer<char> = "select this text".chars().collect();
+        buf.set_position(2);
+        let removed: String = buf.drain(7..12).collect();
+        assert_eq!(removed, "this ");
+        assert_eq!(buf.position(), 7);
+        assert_eq!(buf.iter().collect::<String>(), "select text");
+        assert_eq!(buf.drain(..=6).rev().collect::<String>(), " tceles");
+        buf.remove_range(1..);
+        assert_eq!(buf, vec!['t']);
+        assert!(catch_panic(move || { buf.drain(0..2); }).is_err());
+
+        // 途中で捨てても、mem::forgetしても、要素が二重にドロップされることはない
+        let counter = Rc::new(());
+        let mut buf: GapBuffer<Rc<()>> = std::iter::repeat_n(counter.clone(), 6).collect();
+        let mut drain = buf.drain(1..5);
+        drop(drain.next());
+        drop(drain.next_back());
+        drop(drain);
+        assert_eq!(Rc::strong_count(&counter), 3);
+        std::mem::forget(buf.drain(..1));
+        assert_eq!(buf.len(), 1);
+        assert_eq!(Rc::strong_count(&counter), 3);
+        drop(buf);
+        assert_eq!(Rc::strong_count(&counter), 2);
+    }
+
+    {
+        use std::rc::Rc;
+        use gap::GapBuffer;
+
+        // 挿入点がどこにあっても、先頭からlen個だけが残る
+        let text = "truncate";
+        for pos in 0..=text.len() {
+            for len in 0..=text.len() + 1 {
+                let mut buf: GapBuffer<char> = text.chars().collect();
+                buf.set_position(pos);
+                buf.truncate(len);
+                assert_eq!(buf.iter().collect::<String>(), text[..len.min(text.len())]);
+                assert_eq!(buf.position(), pos.min(len));
+                assert_eq!(buf.capacity(), text.len());
+                buf.insert('!');
+            }
+        }
+
+        // 全ての要素をドロップしても容量はそのまま
+        let counter = Rc::new(());
+        let mut buf: GapBuffer<Rc<()>> = std::iter::repeat_n(counter.clone(), 4).collect();
+        buf.set_position(1);
+        buf.truncate(2);
+        assert_eq!(Rc::strong_count(&counter), 3);
+        buf.clear();
+        assert_eq!(Rc::strong_count(&counter), 1);
+        assert_eq!((buf.len(), buf.position(), buf.capacity()), (0, 0, 4));
+    }
+
+    {
+        use gap::GapBuffer;
+
+        // まとめて挿入しても1つずつ挿入しても結果は同じ
+        let mut buf: GapBuffer<u8> = b"held".iter().cloned().collect();
+        buf.set_position(2);
+        buf.insert_slice(b"llo, wor");
+        assert_eq!(buf, b"hello, world".to_vec());
+        let mut names: GapBuffer<String> = GapBuffer::new();
+        names.insert_from_slice(&["ada".to_string(), "grace".to_string()]);
+        names.set_position(1);
+        names.insert_from_slice(&["linus".to_string()]);
+        assert_eq!(names, vec!["ada".to_string(), "linus".to_string(), "grace".to_string()]);
+
+        // 1要素ずつ挿入する場合と同じ内容になる。速度はbenches/gap.rsで比べる
+        let text: Vec<char> = "All work and no play makes Jack a dull boy. ".chars().cycle().take(1 << 12).collect();
+        let mut per_element = GapBuffer::new();
+        per_element.insert_iter(text.iter().cloned());
+        let mut bulk = GapBuffer::new();
+        bulk.insert_slice(&text);
+        assert_eq!(per_element, bulk);
+    }
+
+    {
+        use gap::GapBuffer;
+        // 選択範囲を貼り付けたテキストで置き換える
+        let mut buf: GapBuffer<char> = "Hello, world!".chars().collect();
+        buf.set_position(3);
+        let replaced: String = buf.splice(7..12, "gap buffer".chars()).collect();
+        assert_eq!(replaced, "world");
+        assert_eq!(buf.iter().collect::<String>(), "Hello, gap buffer!");
+        assert_eq!(buf.position(), 17);
+
+        // 取り除いた要素を読まなくても置き換えは行われる
+        buf.splice(..5, "Goodbye".chars());
+        assert_eq!(buf.iter().collect::<String>(), "Goodbye, gap buffer!");
+        buf.splice(7..7, " cruel".chars());
+        assert_eq!(buf.iter().collect::<String>(), "Goodbye cruel, gap buffer!");
+        buf.splice(13.., std::iter::empty());
+        assert_eq!(buf.iter().collect::<String>(), "Goodbye cruel");
+    }
+
+    {
+        use gap::GapBuffer;
+        // 挿入点がどこにあっても、atの前後で正しく2つに分かれる
+        let text = "split off";
+        for pos in 0..=text.len() {
+            for at in 0..=text.len() {
+                let mut head: GapBuffer<String> = text.chars().map(String::from).collect();
+                head.set_position(pos);
+                let mut tail = head.split_off(at);
+                assert_eq!(head.iter().cloned().collect::<String>(), text[..at]);
+                assert_eq!(tail.iter().cloned().collect::<String>(), text[at..]);
+                assert_eq!(head.position(), pos.min(at));
+                assert_eq!(tail.position(), 0);
+                head.insert("<".to_string());
+                tail.insert(">".to_string());
+                assert_eq!(head.len() + tail.len(), text.len() + 2);
+            }
+        }
+    }
+
+    {
+        use std::io::{IoSlice, Write};
+        use gap::GapBuffer;
+
+        // ギャップの前後をそれぞれスライスとしてコピーせずに扱える
+        let mut buf: GapBuffer<u8> = b"vectored write".iter().cloned().collect();
+        buf.set_position(8);
+        assert_eq!(buf.as_slices(), (&b"vectored"[..], &b" write"[..]));
+        let (front, back) = buf.as_mut_slices();
+        front.make_ascii_uppercase();
+        back.reverse();
+        assert_eq!(buf.as_slices(), (&b"VECTORED"[..], &b"etirw "[..]));
+
+        let (front, back) = buf.as_slices();
+        let mut out = Vec::new();
+        let written = out.write_vectored(&[IoSlice::new(front), IoSlice::new(back)]).unwrap();
+        assert_eq!(written, 14);
+        assert_eq!(out, b"VECTOREDetirw ");
+    }
+
+    {
+        use gap::GapBuffer;
+        // 1つのスライスにまとめれば、スライスを受け取るAPIにそのまま渡せる
+        let mut buf: GapBuffer<i32> = vec![5, 3, 9, 1].into_iter().collect();
+        buf.set_position(2);
+        buf.make_contiguous().sort();
+        assert_eq!(buf, vec![1, 3, 5, 9]);
+        assert_eq!(buf.position(), 4);
+        assert_eq!(buf.as_slices(), (&[1, 3, 5, 9][..], &[][..]));
+        assert!(GapBuffer::<i32>::new().make_contiguous().is_empty());
+    }
+
+    {
+        use std::cell::Cell;
+        use std::panic::AssertUnwindSafe;
+        use std::rc::Rc;
+        use gap::GapBuffer;
+
+        // 挿入点がどこにあっても順序を保って残る
+        let text = "remove the vowels";
+        for pos in 0..=text.len() {
+            let mut buf: GapBuffer<char> = text.chars().collect();
+            buf.set_position(pos);
+            buf.retain(|c| !"aeiou".contains(*c));
+            assert_eq!(buf.iter().collect::<String>(), "rmv th vwls");
+            let kept_before = text[..pos].chars().filter(|c| !"aeiou".contains(*c)).count();
+            assert_eq!(buf.position(), kept_before);
+            buf.insert('!');
+        }
+
+        // 述語がpanicしても、要素は二重にドロップもリークもされない
+        let counter = Rc::new(());
+        for panic_at in 0..6 {
+            let mut buf: GapBuffer<(usize, Rc<()>)> = (0..6).map(|i| (i, counter.clone())).collect();
+            buf.set_position(3);
+            let calls = Cell::new(0);
+            let result = catch_panic(AssertUnwindSafe(|| {
+                buf.retain(|&(i, _)| {
+                    calls.set(calls.get() + 1);
+                    if calls.get() > panic_at {
+                        panic!("predicate failed");
+                    }
+                    i % 2 == 0
+                });
+            }));
+            assert!(result.is_err());
+            let alive: Vec<usize> = buf.iter().map(|&(i, _)| i).collect();
+            assert_eq!(Rc::strong_count(&counter), 1 + alive.len());
+            assert!(alive.windows(2).all(|w| w[0] < w[1]));
+            drop(buf);
+            assert_eq!(Rc::strong_count(&counter), 1);
+        }
+    }
+
+    {
+        use std::cell::Cell;
+        use gap::GapBuffer;
+        use ref_with_flag::RefWithFlag;
+
+        // Send/Syncの境界はコンパイル時に検査される
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+        assert_send::<GapBuffer<String>>();
+        assert_sync::<GapBuffer<String>>();
+        assert_send::<GapBuffer<Cell<i32>>>();
+        assert_send::<gap::Iter<'static, String>>();
+        assert_sync::<gap::IterMut<'static, String>>();
+        assert_send::<RefWithFlag<'static, Vec<i32>>>();
+        assert_sync::<RefWithFlag<'static, Vec<i32>>>();
+        // 境界を満たさない型がコンパイルできないことは、tests/ui/not_send_sync.rsで確かめる
+
+        // 別のスレッドに送ったり共有したりできる
+        let mut buf: GapBuffer<String> = vec!["sent".to_string()].into_iter().collect();
+        buf = std::thread::spawn(move || {
+            buf.insert(" across threads".to_string());
+            buf
+        }).join().unwrap();
+        assert_eq!(buf.iter().cloned().collect::<String>(), "sent across threads");
+
+        let shared = vec![2, 4, 6];
+        let flagged = RefWithFlag::new(&shared, true);
+        let sum: i32 = std::thread::scope(|scope| {
+            let flagged = &flagged;
+            let handles: Vec<_> = (0..3).map(|i| scope.spawn(move || flagged.get_ref()[i])).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).sum()
+        });
+        assert_eq!(sum, 12);
+        assert!(flagged.get_flag());
+    }
+
+    #[cfg(feature = "serde")]
+    {
+        use serde::Deserialize;
+        use serde::de::IntoDeserializer;
+        use serde::de::value::{Error, SeqDeserializer};
+        use gap::GapBuffer;
+
+        // 要素の並びから読み込むとギャップは末尾に置かれる
+        let seq: SeqDeserializer<_, Error> = vec!['a', 'b', 'c'].into_deserializer();
+        let buf = GapBuffer::<char>::deserialize(seq).unwrap();
+        assert_eq!(format!("{:?}", buf), "['a', 'b', 'c' |]");
+    }
+
+    {
+        use gap::GapBuffer;
+
+        // Vecの確保領域を使い回すので、変換の前後でポインタが変わらない
+        let mut vec = Vec::with_capacity(8);
+        vec.extend_from_slice(&[1, 2, 3]);
+        let ptr = vec.as_ptr();
+        let mut buf = GapBuffer::from(vec);
+        assert_eq!(buf.capacity(), 8);
+        assert_eq!(buf.position(), 3);
+        buf.insert(4);
+        let vec = buf.into_vec();
+        assert_eq!(vec.as_ptr(), ptr);
+        assert_eq!(vec, [1, 2, 3, 4]);
+
+        // ギャップが途中にあっても要素は論理的な順に並ぶ
+        let mut buf: GapBuffer<String> = vec!["a".to_string(), "c".to_string()].into();
+        buf.set_position(1);
+        buf.insert("b".to_string());
+        let vec: Vec<String> = buf.into();
+        assert_eq!(vec, ["a", "b", "c"]);
+    }
+
+    {
+        use gap::GapBuffer;
+
+        // ギャップがどこにあっても、逆順に辿ると論理的な並びのちょうど逆になる
+        let expected: Vec<i32> = (0..5).collect();
+        for pos in 0..=5 {
+            let mut buf: GapBuffer<i32> = (0..5).collect();
+            buf.set_position(pos);
+            assert_eq!(buf.iter().len(), 5);
+            assert!(buf.iter().rev().eq(expected.iter().rev()));
+            assert_eq!(buf.iter().rposition(|&x| x % 2 == 1), Some(3));
+
+            // 両端から交互に取り出しても、全ての要素をちょうど一度ずつ返す
+            let mut iter = buf.iter();
+            let mut seen = Vec::new();
+            while let (Some(&front), back) = (iter.next(), iter.next_back()) {
+                seen.push(front);
+                seen.extend(back);
+                assert_eq!(iter.len(), 5 - seen.len());
+            }
+            seen.sort();
+            assert_eq!(seen, expected);
+
+            for x in buf.iter_mut().rev().take(2) {
+                *x *= 10;
+            }
+            assert_eq!(buf, vec![0, 1, 2, 30, 40]);
+
+            let mut buf: GapBuffer<String> = (0..5).map(|i| i.to_string()).collect();
+            buf.set_position(pos);
+            let mut iter = buf.into_iter();
+            assert_eq!(iter.len(), 5);
+            assert_eq!(iter.next_back().as_deref(), Some("4"));
+            assert_eq!(iter.next().as_deref(), Some("0"));
+            assert_eq!(iter.rev().collect::<Vec<_>>(), ["3", "2", "1"]);
+        }
+
+        let mut buf: GapBuffer<i32> = (0..5).collect();
+        buf.set_position(2);
+        assert_eq!(buf.drain(1..4).rev().collect::<Vec<_>>(), [3, 2, 1]);
+        assert_eq!(buf.splice(0..1, vec![7]).len(), 1);
+        assert_eq!(buf, vec![7, 4]);
+
+        // ゼロサイズの型でも、要素数を数えるだけでメモリを読まずに正しく辿れる
+        for pos in 0..=3 {
+            let mut buf = GapBuffer::new();
+            assert_eq!(buf.len(), 0);
+            for _ in 0..3 {
+                buf.insert(());
+            }
+            buf.set_position(pos);
+            assert_eq!(buf.len(), 3);
+            assert_eq!(buf.iter().len(), 3);
+            assert_eq!(buf.iter().rev().count(), 3);
+            assert_eq!(buf.iter_mut().rev().count(), 3);
+            assert_eq!(buf.clone().into_iter().rev().count(), 3);
+            assert_eq!(buf.drain(..).rev().count(), 3);
+            assert_eq!(buf.len(), 0);
+        }
+    }
+
+    {
+        use gap::GapBuffer;
+
+        let mut buf: GapBuffer<char> = "abcd".chars().collect();
+
+        // 読み取り専用のカーソルはいくつでも同時に持てる
+        let mut first = buf.cursor(0);
+        let last = buf.cursor(4);
+        assert_eq!(first.peek_prev(), None);
+        assert!(first.move_right());
+        assert_eq!((first.peek_prev(), first.peek_next()), (Some(&'a'), Some(&'b')));
+        assert_eq!(last.peek_next(), None);
+        let mut copy = last;
+        assert!(copy.move_left());
+        assert!(!buf.cursor(4).move_right());
+        assert_eq!((last.position(), copy.position()), (4, 3));
+
+        // 置いておくカーソルは印として持つので、どのカーソルから編集しても他のカーソルの位置がずれて有効なまま残る
+        let (first, last) = (first.position(), last.position());
+        let carets = [buf.add_cursor(first), buf.add_cursor(last)];
+        assert_eq!(buf.cursors(), carets);
+        {
+            let mut cursor = buf.cursor_mut_at(carets[0]);
+            cursor.insert('x');
+            assert_eq!(cursor.remove(), Some('b'));
+            assert!(cursor.move_left());
+            assert_eq!(cursor.as_cursor().peek_next(), Some(&'x'));
+            assert_eq!(cursor.id(), Some(carets[0]));
+        }
+        assert_eq!((buf.cursor_position(carets[0]), buf.cursor_position(carets[1])), (1, 4));
+        let mut cursor = buf.cursor_mut_at(carets[1]);
+        cursor.insert('y');
+        cursor.move_to(0);
+        assert_eq!(cursor.remove(), Some('a'));
+        assert_eq!((buf.cursor_position(carets[0]), buf.cursor_position(carets[1])), (0, 0));
+        assert_eq!(buf.iter().collect::<String>(), "xcdy");
+
+        // 複数キャレットでの入力。キャレットごとに同じ編集をすれば、前のキャレットでの挿入・削除は後ろのキャレットに反映される
+        let mut buf: GapBuffer<char> = "let a = 1;\nlet b = 2;\nlet c = 3;".chars().collect();
+        buf.set_position(0);
+        let selection = buf.set_mark("selection");
+        for line_start in [0, 11, 22] {
+            buf.add_cursor(line_start);
+        }
+        for id in buf.cursors() {
+            let mut cursor = buf.cursor_mut_at(id);
+            for ch in "pub ".chars() {
+                cursor.insert(ch);
+            }
+            cursor.move_to(cursor.position() + 5);
+            assert_eq!(cursor.remove(), Some(' '));
+        }
+        assert_eq!(buf.iter().collect::<String>(), "pub let a= 1;\npub let b= 2;\npub let c= 3;");
+        let positions: Vec<usize> = buf.cursors().into_iter().map(|id| buf.cursor_position(id)).collect();
+        assert_eq!(positions, [9, 23, 37]);
+        // 印とカーソルは別々に数える。先頭の印は、同じ位置への挿入では動かない
+        assert_eq!(buf.position_of(selection), 0);
+        assert_eq!(buf.cursors().len(), 3);
+        // 複製したGapBufferでも同じIDで引ける
+        let copy = buf.clone();
+        assert_eq!(copy.cursor_position(buf.cursors()[2]), 37);
+    }
+
+    {
+        use gap::GapString;
+
+        let mut text = GapString::from("héllo");
+        assert_eq!(text.len(), 6);
+        assert_eq!(text.as_str_halves(), ("héllo", ""));
+
+        // 'é'は2バイトなので、その途中には挿入点を置けない
+        assert!(!text.is_char_boundary(2));
+        assert!(catch_panic(|| GapString::from("héllo").set_position(2)).is_err());
+
+        text.set_position(1);
+        assert_eq!(text.remove_char(), Some('é'));
+        text.insert_str("ë");
+        text.insert('🦀');
+        assert_eq!(text.as_str_halves(), ("hë🦀", "llo"));
+        assert_eq!(text.to_string(), "hë🦀llo");
+
+        text.set_position(text.len());
+        assert_eq!(text.remove_char(), None);
+        assert_eq!(String::from(text), "hë🦀llo");
+        assert!(GapString::new().is_empty());
+    }
+
+    {
+        use rope::Rope;
+
+        // 同じ編集をVecにも加えて、結果が一致することを確かめる
+        let mut rope: Rope<u32> = (0..1000).collect();
+        let mut vec: Vec<u32> = (0..1000).collect();
+        let mut seed = 12345u32;
+        for step in 0..5000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let r = (seed >> 8) as usize;
+            if r.is_multiple_of(3) && !vec.is_empty() {
+                let index = r % vec.len();
+                assert_eq!(rope.remove(index), vec.remove(index));
+            } else {
+                let index = r % (vec.len() + 1);
+                rope.insert(index, step);
+                vec.insert(index, step);
+            }
+        }
+        assert_eq!(rope.len(), vec.len());
+        assert!(rope.iter().eq(vec.iter()));
+        assert_eq!(rope.iter().len(), vec.len());
+        assert_eq!(rope[vec.len() / 2], vec[vec.len() / 2]);
+        assert_eq!(rope.get(vec.len()), None);
+
+        // 小さくなったチャンクは併合されるので、チャンクの数は要素数に見合った数に収まる
+        while rope.len() > 100 {
+            rope.remove(rope.len() / 3);
+        }
+        assert!(rope.chunk_count() <= 100 / 16 * 2 + 2);
+
+        // 常に先頭に挿入しても木の高さは要素数の対数程度に収まる
+        let mut rope = Rope::new();
+        for i in 0..20000 {
+            rope.insert(0, i);
+        }
+        assert!(rope.depth() < 40);
+        assert_eq!(rope.iter().next(), Some(&19999));
+        for _ in 0..20000 {
+            rope.remove(0);
+        }
+        assert!(rope.is_empty());
+    }
+
+    {
+        use gap::LineIndex;
+
+        let mut text = String::from("fn main() {\n    println!();\n}\n");
+        let mut index = LineIndex::new(&text);
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.line_col(0), (0, 0));
+        assert_eq!(index.line_col(16), (1, 4));
+        assert_eq!(index.offset_of_line(2), Some(28));
+        assert_eq!(index.offset_of_line(3), Some(30));
+        assert_eq!(index.offset_of_line(4), None);
+
+        // 同じ編集を加えた文字列を毎回読み直した結果と一致する
+        let mut seed = 42u32;
+        for _ in 0..500 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let r = (seed >> 8) as usize;
+            let offset = r % (text.len() + 1);
+            if r.is_multiple_of(2) {
+                let end = std::cmp::min(offset + r % 7, text.len());
+                text.replace_range(offset..end, "");
+                index.remove(offset..end);
+            } else {
+                let insert = ["\n", "ab", "x\ny\n", ""][r % 4];
+                text.insert_str(offset, insert);
+                index.insert(offset, insert);
+            }
+
+            let starts: Vec<usize> = std::iter::once(0)
+                .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+                .collect();
+            assert_eq!(index.line_count(), starts.len());
+            for (line, &start) in starts.iter().enumerate() {
+                assert_eq!(index.offset_of_line(line), Some(start));
+            }
+            let probe = r % (text.len() + 1);
+            let line = starts.iter().rposition(|&start| start <= probe).unwrap();
+            assert_eq!(index.line_col(probe), (line, probe - starts[line]));
+        }
+    }
+
+    {
+        use gap::{EditLog, GapBuffer};
+
+        let mut log = EditLog::new("hello".chars().collect::<GapBuffer<char>>());
+        let text = |log: &EditLog<char>| log.buffer().iter().collect::<String>();
+
+        log.insert(5, " world".chars());
+        log.remove(0..1);
+        log.insert(0, Some('j'));
+        assert_eq!(text(&log), "jello world");
+
+        // トランザクションにまとめた操作は1回のundoで戻る
+        log.begin_transaction();
+        log.remove(5..11);
+        log.insert(5, "y".chars());
+        log.commit_transaction();
+        assert_eq!(text(&log), "jelloy");
+
+        assert!(log.undo());
+        assert_eq!(text(&log), "jello world");
+        assert!(log.undo());
+        assert!(log.undo());
+        assert_eq!(text(&log), "hello world");
+        assert!(log.redo());
+        assert!(log.redo());
+        assert_eq!(text(&log), "jello world");
+        assert!(log.redo());
+        assert_eq!(text(&log), "jelloy");
+        assert!(!log.redo());
+
+        // 新しい操作をするとやり直しの履歴は消える
+        assert!(log.undo());
+        log.insert(11, Some('!'));
+        assert!(!log.redo());
+        while log.undo() {}
+        assert_eq!(log.into_inner(), "hello".chars().collect::<Vec<_>>());
+    }
+
+    exercise_gap_buffer();
+
+    #[cfg(feature = "testing")]
+    {
+        // ファズターゲットと同じ確認を、擬似乱数で作った入力でも行っておく
+        let mut seed = 7u32;
+        for len in 0..64 {
+            let data: Vec<u8> = (0..len * 8).map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8
+            }).collect();
+            gap::testing::check_against_vec(&data);
+        }
+    }
+
+    {
+        use std::cell::Cell;
+        use std::panic::AssertUnwindSafe;
+        use std::rc::Rc;
+        use gap::GapBuffer;
+
+        // 指定した番号の要素だけがDropでpanicする型
+        struct Bomb {
+            id: usize,
+            fuse: usize,
+            drops: Rc<Cell<usize>>
+        }
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                self.drops.set(self.drops.get() + 1);
+                if self.id == self.fuse {
+                    panic!("drop failed");
+                }
+            }
+        }
+        let bombs = |fuse: usize, drops: &Rc<Cell<usize>>| -> GapBuffer<Bomb> {
+            (0..6).map(|id| Bomb { id, fuse, drops: drops.clone() }).collect()
+        };
+
+        // ギャップの前後どちらの要素のDropがpanicしても、全ての要素がちょうど1回ずつドロップされる
+        for fuse in 0..6 {
+            let drops = Rc::new(Cell::new(0));
+            let mut buf = bombs(fuse, &drops);
+            buf.set_position(3);
+            assert!(catch_panic(AssertUnwindSafe(move || drop(buf))).is_err());
+            assert_eq!(drops.get(), 6);
+        }
+
+        // truncateで捨てる要素のDropがpanicしても、残す要素はリークせずに残る
+        for fuse in 3..6 {
+            let drops = Rc::new(Cell::new(0));
+            let mut buf = bombs(fuse, &drops);
+            buf.set_position(1);
+            assert!(catch_panic(AssertUnwindSafe(|| buf.truncate(3))).is_err());
+            assert_eq!(buf.iter().map(|bomb| bomb.id).collect::<Vec<_>>(), [0, 1, 2]);
+            drop(buf);
+            assert_eq!(drops.get(), 6);
+        }
+
+        // clearや、挿入点より前で切り詰めるtruncateでギャップの前の要素のDropがpanicしても、後ろの要素はドロップされる
+        for fuse in 0..6 {
+            let drops = Rc::new(Cell::new(0));
+            let mut buf = bombs(fuse, &drops);
+            buf.set_position(4);
+            assert!(catch_panic(AssertUnwindSafe(|| buf.clear())).is_err());
+            assert_eq!(buf.len(), 0);
+            assert_eq!(drops.get(), 6);
+            drop(buf);
+            assert_eq!(drops.get(), 6);
+        }
+        for fuse in 2..6 {
+            let drops = Rc::new(Cell::new(0));
+            let mut buf = bombs(fuse, &drops);
+            buf.set_position(4);
+            assert!(catch_panic(AssertUnwindSafe(|| buf.truncate(2))).is_err());
+            assert_eq!(buf.iter().map(|bomb| bomb.id).collect::<Vec<_>>(), [0, 1]);
+            drop(buf);
+            assert_eq!(drops.get(), 6);
+        }
+
+        // 容量の計算があふれる時は、再確保の前にpanicするので中身は変わらない
+        let drops = Rc::new(Cell::new(0));
+        let mut buf = bombs(usize::MAX, &drops);
+        assert!(catch_panic(AssertUnwindSafe(|| buf.reserve(usize::MAX))).is_err());
+        assert_eq!(buf.len(), 6);
+        assert_eq!(drops.get(), 0);
+        drop(buf);
+        assert_eq!(drops.get(), 6);
+    }
+
+    {
+        use gap::GapBuffer;
+
+        // バックスペースは挿入点の直前を、デリートは直後を消す
+        let mut buf: GapBuffer<char> = "typo!".chars().collect();
+        buf.set_position(4);
+        assert_eq!(buf.remove_backward(), Some('o'));
+        assert_eq!(buf.position(), 3);
+        buf.insert('e');
+        assert_eq!(buf.remove(), Some('!'));
+        assert_eq!(buf.iter().collect::<String>(), "type");
+
+        buf.set_position(0);
+        assert_eq!(buf.remove_backward(), None);
+        assert_eq!(buf.len(), 4);
+    }
+
+    {
+        use gap::GapBuffer;
+
+        // 空いた場所にはギャップに隣接する要素が移り、間の要素は動かない
+        let mut buf: GapBuffer<char> = "abcdef".chars().collect();
+        buf.set_position(3);
+        assert_eq!(buf.swap_remove(0), 'a');
+        assert_eq!(buf.iter().collect::<String>(), "cbdef");
+        assert_eq!(buf.position(), 2);
+        assert_eq!(buf.swap_remove(4), 'f');
+        assert_eq!(buf.iter().collect::<String>(), "cbed");
+        // ギャップに隣接する要素自身を取り除く時は何も移らない
+        assert_eq!(buf.swap_remove(1), 'b');
+        assert_eq!(buf.swap_remove(1), 'e');
+        assert_eq!(buf.iter().collect::<String>(), "cd");
+        assert!(catch_panic(move || buf.swap_remove(2)).is_err());
+    }
+
+    {
+        use gap::GapBuffer;
+
+        let mut buf: GapBuffer<u64> = (0..5).collect();
+        buf.set_position(2);
+        assert!(buf.try_reserve(10).is_ok());
+        assert!(buf.capacity() >= 15);
+        assert!(buf.try_insert(99).is_ok());
+
+        // 確保できない大きさを要求してもabortもpanicもせず、中身はそのまま残る
+        assert!(buf.try_reserve(usize::MAX).is_err());
+        assert!(buf.try_reserve(isize::MAX as usize / 8).is_err());
+        assert_eq!(buf, vec![0, 1, 99, 2, 3, 4]);
+        assert_eq!(buf.position(), 3);
+
+        let mut empty: GapBuffer<u64> = GapBuffer::new();
+        assert!(empty.try_insert(1).is_ok());
+        assert_eq!(empty, vec![1]);
+    }
+
+    {
+        use std::alloc::Layout;
+        use std::cell::Cell;
+        use std::ptr::NonNull;
+        use allocator_api2::alloc::{AllocError, Allocator, Global};
+        use gap::GapBuffer;
+
+        // Globalに委ね、確保中のバイト数と確保の回数を数えるアロケータ
+        struct Counting<'a> {
+            live: &'a Cell<usize>,
+            allocations: &'a Cell<usize>
+        }
+
+        unsafe impl<'a> Allocator for &'a Counting<'a> {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                let ptr = Global.allocate(layout)?;
+                self.live.set(self.live.get() + layout.size());
+                self.allocations.set(self.allocations.get() + 1);
+                Ok(ptr)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.live.set(self.live.get() - layout.size());
+                Global.deallocate(ptr, layout)
+            }
+        }
+
+        let (live, allocations) = (Cell::new(0), Cell::new(0));
+        let counting = Counting { live: &live, allocations: &allocations };
+        {
+            let mut buf: GapBuffer<u32, &Counting> = GapBuffer::new_in(&counting);
+            assert_eq!(allocations.get(), 0);
+            buf.insert_iter(0..10);
+            buf.set_position(5);
+            buf.insert(100);
+            assert_eq!(live.get(), buf.capacity() * 4);
+
+            // 分けた後半も、複製も同じアロケータから確保する
+            let tail = buf.split_off(6);
+            let clone = tail.clone();
+            assert_eq!(live.get(), (buf.capacity() + tail.capacity() + clone.capacity()) * 4);
+            assert_eq!(tail, clone);
+            assert_eq!(clone.into_iter().collect::<Vec<_>>(), [5, 6, 7, 8, 9]);
+            assert!(buf.try_reserve(usize::MAX).is_err());
+            assert_eq!(buf, [0, 1, 2, 3, 4, 100][..]);
+        }
+        // GapBufferがドロップされれば、確保した領域は全て同じアロケータに返っている
+        assert_eq!(live.get(), 0);
+        assert!(allocations.get() > 0);
+    }
+
+    {
+        use allocator_api2::alloc::Global;
+        use gap::RawGapBuf;
+
+        // RawGapBufは領域の確保と移し替えだけを行い、中身の初期化とドロップは使う側が受け持つ
+        let mut raw: RawGapBuf<String> = RawGapBuf::with_capacity_in(4, Global);
+        assert_eq!(raw.capacity(), 4);
+        unsafe {
+            for i in 0..4 {
+                raw.as_ptr().add(i).write(i.to_string());
+            }
+            // 前半2つはそのまま、後半2つは新しい領域の末尾へ移す
+            raw.try_relocate(8, |old, new, cap| {
+                std::ptr::copy_nonoverlapping(old, new, 2);
+                std::ptr::copy_nonoverlapping(old.add(2), new.add(cap - 2), 2);
+            }).unwrap();
+            assert_eq!(raw.capacity(), 8);
+            let read: Vec<String> = [0, 1, 6, 7].iter().map(|&i| raw.as_ptr().add(i).read()).collect();
+            assert_eq!(read, ["0", "1", "2", "3"]);
+        }
+
+        // 確保に失敗した時は移し替えを呼ばず、元の領域もそのまま残る
+        let mut called = false;
+        assert!(unsafe { raw.try_relocate(usize::MAX, |_, _, _| called = true) }.is_err());
+        assert!(!called);
+        assert_eq!(raw.capacity(), 8);
+        assert!(RawGapBuf::<u64>::try_with_capacity_in(usize::MAX / 4, Global).is_err());
+
+        // 分解しても解放されず、作り直せば同じ領域になる
+        let ptr = raw.as_ptr();
+        let (parts, cap, alloc) = raw.into_raw_parts();
+        let raw = unsafe { RawGapBuf::<String>::from_raw_parts_in(parts, cap, alloc) };
+        assert_eq!(raw.as_ptr(), ptr);
+
+        // ゼロサイズの型では確保せず、容量はusize::MAXになる
+        assert_eq!(RawGapBuf::<()>::new_in(Global).capacity(), usize::MAX);
+        assert_eq!(RawGapBuf::<u8>::new_in(Global).capacity(), 0);
+    }
+
+    {
+        use gap::GapBuffer;
+
+        let mut buf: GapBuffer<char> = "hello world".chars().collect();
+        buf.set_position(5);
+        let selection = buf.set_mark("selection");
+        buf.set_position(11);
+        let end = buf.set_mark("end");
+        assert_eq!(buf.mark("selection"), Some(selection));
+        assert_eq!(buf.mark("missing"), None);
+
+        // 印の前への挿入・削除では印がずれ、印の位置への挿入では印は動かない
+        buf.set_position(0);
+        buf.insert_iter("oh, ".chars());
+        assert_eq!((buf.position_of(selection), buf.position_of(end)), (9, 15));
+        buf.set_position(9);
+        buf.insert(',');
+        assert_eq!(buf.position_of(selection), 9);
+        buf.remove_backward();
+        buf.remove_range(0..4);
+        assert_eq!((buf.position_of(selection), buf.position_of(end)), (5, 11));
+
+        // 印をまたぐ範囲を取り除くと、印は範囲の先頭に移る
+        buf.drain(3..8).for_each(drop);
+        assert_eq!((buf.position_of(selection), buf.position_of(end)), (3, 6));
+        assert_eq!(buf.iter().collect::<String>(), "helrld");
+
+        // retainでは、印より前で残った要素の数の位置に移る
+        buf.retain(|&c| c != 'l');
+        assert_eq!((buf.position_of(selection), buf.position_of(end)), (2, 4));
+
+        // 同じ名前で付け直すと同じ印が動く。複製にも印は引き継がれる
+        buf.set_position(1);
+        assert_eq!(buf.set_mark("end"), end);
+        let clone = buf.clone();
+        buf.truncate(0);
+        assert_eq!((buf.position_of(selection), buf.position_of(end)), (0, 0));
+        assert_eq!((clone.position_of(selection), clone.position_of(end)), (2, 1));
+    }
+
+    #[cfg(feature = "std")]
+    {
+        use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+        use gap::GapBuffer;
+
+        // Writeは挿入点に挿入するので、上書きではなく書き足しになる
+        let mut file: GapBuffer<u8> = GapBuffer::new();
+        write!(file, "line 1\nline 3\n").unwrap();
+        file.seek(SeekFrom::Start(7)).unwrap();
+        file.write_all(b"line 2\n").unwrap();
+        assert_eq!(file.position(), 14);
+
+        // Readは挿入点の後ろを読み、読んだ分だけ挿入点を進める
+        let mut rest = String::new();
+        file.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "line 3\n");
+        assert_eq!(file.stream_position().unwrap(), 21);
+
+        assert_eq!(file.seek(SeekFrom::End(-7)).unwrap(), 14);
+        assert_eq!(file.seek(SeekFrom::Current(-14)).unwrap(), 0);
+        assert_eq!(file.seek(SeekFrom::Current(-1)).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(file.seek(SeekFrom::End(1)).is_err());
+        assert_eq!(file.position(), 0);
+
+        // ioのトレイトを受け取る関数にそのまま渡せる
+        let lines: Vec<String> = BufReader::new(&mut file).lines().map(Result::unwrap).collect();
+        assert_eq!(lines, ["line 1", "line 2", "line 3"]);
+        let mut copy: GapBuffer<u8> = GapBuffer::new();
+        file.rewind().unwrap();
+        io::copy(&mut file, &mut copy).unwrap();
+        assert_eq!(copy, file);
+    }
+
+    {
+        use gap::GapBuffer;
+
+        let text = "abracadabra";
+        let mut buf: GapBuffer<u8> = text.bytes().collect();
+        // ギャップをどこに置いても、前後をまたぐ一致も含めて同じ位置が見つかる
+        for gap in 0..=text.len() {
+            buf.set_position(gap);
+            assert_eq!(buf.find(b"abra"), Some(0));
+            assert_eq!(buf.rfind(b"abra"), Some(7));
+            assert_eq!(buf.find(b"cad"), Some(4));
+            assert_eq!(buf.position_of_slice(b"a", 1), Some(3));
+            assert_eq!(buf.find(b"abrax"), None);
+            assert_eq!(buf.position_of_slice(b"bra", 2), Some(8));
+        }
+        assert_eq!(buf.find(b""), Some(0));
+        assert_eq!(buf.rfind(b""), Some(text.len()));
+        assert_eq!(buf.position_of_slice(b"a", text.len() + 1), None);
+        assert_eq!(buf.find(b"abracadabra!"), None);
+
+        // 見つけた位置を次々に置き換える
+        let mut from = 0;
+        while let Some(at) = buf.position_of_slice(b"abra", from) {
+            buf.splice(at .. at + 4, b"ABRA".iter().copied());
+            from = at + 4;
+        }
+        assert_eq!(buf, b"ABRAcadABRA".to_vec());
+    }
+
+    {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::Hasher;
+        use gap::GapBuffer;
+        use rope::Rope;
+
+        let mut buf: GapBuffer<u8> = GapBuffer::new();
+        assert_eq!(buf.chunks().count(), 0);
+        buf.insert_slice(b"hello world");
+        assert_eq!(buf.chunks().collect::<Vec<_>>(), [&b"hello world"[..]]);
+        buf.set_position(5);
+        assert_eq!(buf.chunks().len(), 2);
+        assert_eq!(buf.chunks().rev().collect::<Vec<_>>(), [&b" world"[..], &b"hello"[..]]);
+
+        // 領域ごとに書き込んでも、要素ごとに書き込んだのと同じハッシュになる
+        let mut by_chunk = DefaultHasher::new();
+        buf.chunks().for_each(|chunk| by_chunk.write(chunk));
+        let mut by_byte = DefaultHasher::new();
+        buf.iter().for_each(|&b| by_byte.write_u8(b));
+        assert_eq!(by_chunk.finish(), by_byte.finish());
+
+        // ロープでは各チャンクの領域が順に並ぶ
+        let mut rope: Rope<u32> = (0..1000).collect();
+        rope.insert(500, 0);
+        assert!(rope.chunks().count() >= rope.chunk_count());
+        assert!(rope.chunks().all(|chunk| !chunk.is_empty()));
+        assert_eq!(rope.chunks().flatten().copied().collect::<Vec<_>>(), rope.iter().copied().collect::<Vec<_>>());
+    }
+
+    {
+        use gap::{GapBuffer, GapSlice};
+
+        // 領域の中の空白を数える関数は、バッファ全体と位置の組ではなくビューを受け取ればよい
+        fn count_spaces(region: GapSlice<char>) -> usize {
+            region.iter().filter(|c| c.is_whitespace()).count()
+        }
+
+        let mut buf: GapBuffer<char> = "the quick brown fox".chars().collect();
+        buf.set_position(7);
+        let region = buf.slice(4..15);
+        assert_eq!(region.len(), 11);
+        assert_eq!(region.as_slices(), (&['q', 'u', 'i'][..], &['c', 'k', ' ', 'b', 'r', 'o', 'w', 'n'][..]));
+        assert_eq!((region[0], region.get(10), region.get(11)), ('q', Some(&'n'), None));
+        assert_eq!(count_spaces(region), 1);
+        assert_eq!(region.iter().rev().collect::<String>(), "nworb kciuq");
+        assert_eq!(format!("{:?}", region.slice(..3)), "['q', 'u', 'i']");
+
+        // 絞り込んだビューは、ギャップの片側だけになることもある
+        assert_eq!(region.slice(3..5).as_slices(), (&[][..], &['c', 'k'][..]));
+        assert_eq!(region.slice(..3), ['q', 'u', 'i'][..]);
+        assert!(region.slice(3..3).is_empty());
+        assert_eq!(buf.slice(..), buf.slice(0..buf.len()));
+        assert_eq!(buf.slice(16..).into_iter().collect::<String>(), "fox");
+        assert!(catch_panic(|| { "ab".chars().collect::<GapBuffer<char>>().slice(1..3); }).is_err());
+    }
+
+    {
+        use ref_with_flag::RefWithFlagMut;
+
+        let mut vec = vec![10, 20, 30];
+        {
+            let mut flagged = RefWithFlagMut::new(&mut vec, false);
+            flagged.get_mut().push(40);
+            flagged.set_flag(true);
+            assert!(flagged.get_flag());
+            assert_eq!(flagged.get_ref().len(), 4);
+            // フラグを書き換えてもポインタは変わらない
+            flagged.set_flag(false);
+            assert!(!flagged.get_flag());
+            // let first = flagged.get_mut(); let second = flagged.get_ref(); first.push(1); // cannot borrow `flagged` as immutable because it is also borrowed as mutable
+            flagged.into_mut()[0] = 0;
+        }
+        assert_eq!(vec, [0, 20, 30, 40]);
+        // let flagged = RefWithFlagMut::new(&mut vec, true); vec.push(1); flagged.get_ref(); // cannot borrow `vec` as mutable more than once at a time
+    }
+
+    {
+        use ref_with_flag::RefWithTag;
+
+        // u64は8バイト単位なので、下位3ビットをタグに使える
+        let value = 42u64;
+        let mut tagged: RefWithTag<u64, 3> = RefWithTag::new(&value, 5);
+        assert_eq!((*tagged.get_ref(), tagged.tag()), (42, 5));
+        tagged.set_tag(7);
+        assert_eq!(tagged.tag(), 7);
+        let tagged = tagged.map_tag(|tag| tag - 6);
+        assert_eq!((*tagged.get_ref(), tagged.tag()), (42, 1));
+        assert!(catch_panic(|| RefWithTag::<u64, 3>::new(&0, 8)).is_err());
+
+        // u32は4バイト単位なので2ビットまで
+        let small = RefWithTag::<u32, 2>::new(&7, 3);
+        assert_eq!((*small.get_ref(), small.tag()), (7, 3));
+        // RefWithTag::<u32, 3>::new(&7, 0); // evaluation panicked: alignment of T is too small for BITS tag bits
+    }
+
+    {
+        use ref_with_flag::TaggedBox;
+        use std::rc::Rc;
+
+        // 構文木のノードの種類をタグに持たせれば、子へのポインタ1語でノードを表せる
+        const NUMBER: usize = 0;
+        const NEGATE: usize = 1;
+        struct Node {
+            value: i64,
+            child: Option<TaggedBox<Node>>
+        }
+        fn eval(node: &TaggedBox<Node>) -> i64 {
+            match node.tag() {
+                NUMBER => node.get_ref().value,
+                NEGATE => -eval(node.get_ref().child.as_ref().unwrap()),
+                _ => unreachable!()
+            }
+        }
+        let leaf = TaggedBox::new(Node { value: 7, child: None }, NUMBER);
+        let mut tree = TaggedBox::new(Node { value: 0, child: Some(leaf) }, NEGATE);
+        assert_eq!(eval(&tree), -7);
+        tree.get_mut().child.as_mut().unwrap().get_mut().value = 3;
+        assert_eq!(eval(&tree), -3);
+        assert_eq!(std::mem::size_of::<Option<TaggedBox<Node>>>(), std::mem::size_of::<usize>());
+
+        // u64なら3ビットのタグを持てる
+        let mut boxed = TaggedBox::<u64, 3>::new(10, 6);
+        *boxed.get_mut() += 1;
+        boxed.set_tag(2);
+        let (inner, tag) = boxed.into_box();
+        assert_eq!((*inner, tag), (11, 2));
+        assert!(catch_panic(|| TaggedBox::<u64, 3>::new(0, 8)).is_err());
+        // TaggedBox::<u16, 2>::new(0, 0); // evaluation panicked: alignment of T is too small for BITS tag bits
+
+        // ドロップでもinto_innerでも、値はちょうど1回だけ手放される
+        let shared = Rc::new(());
+        let tagged = TaggedBox::<_, 2>::new(Rc::clone(&shared), 3);
+        assert_eq!(Rc::strong_count(&shared), 2);
+        drop(tagged);
+        assert_eq!(Rc::strong_count(&shared), 1);
+        let tagged = TaggedBox::<_, 2>::from_box(Box::new(Rc::clone(&shared)), 1);
+        let inner = tagged.into_inner();
+        assert_eq!(Rc::strong_count(&shared), 2);
+        drop(inner);
+        assert_eq!(Rc::strong_count(&shared), 1);
+    }
+
+    {
+        use ref_with_flag::AtomicTaggedPtr;
+        use std::sync::atomic::Ordering;
+
+        let mut first = 1u32;
+        let mut second = 2u32;
+        let next = AtomicTaggedPtr::new(&mut first as *mut u32, false);
+        assert_eq!(next.load(Ordering::Relaxed), (&mut first as *mut u32, false));
+        next.store(&mut second, false, Ordering::Release);
+        assert_eq!(next.load(Ordering::Acquire), (&mut second as *mut u32, false));
+
+        // 期待した組と違えば置き換えず、今の組を返す
+        let stale = (&mut first as *mut u32, false);
+        assert_eq!(next.compare_exchange(stale, (&mut first, true), Ordering::AcqRel, Ordering::Acquire),
+                   Err((&mut second as *mut u32, false)));
+
+        // 論理削除: ポインタを変えずにフラグだけを立てる競争には、ちょうど1つのスレッドだけが勝つ
+        let winners = std::sync::atomic::AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let (ptr, deleted) = next.load(Ordering::Acquire);
+                    if !deleted && next.compare_exchange((ptr, false), (ptr, true),
+                                                         Ordering::AcqRel, Ordering::Acquire).is_ok() {
+                        winners.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            }
+        });
+        assert_eq!(winners.load(Ordering::Relaxed), 1);
+        let (ptr, deleted) = next.load(Ordering::Acquire);
+        assert!(deleted);
+        assert_eq!(unsafe { *ptr }, 2);
+        // u16は2バイト単位なので、その1バイト目は必ず奇数番地になる
+        let mut half = 0u16;
+        let odd = (&mut half as *mut u16 as *mut u8).wrapping_add(1);
+        assert!(catch_panic(|| AtomicTaggedPtr::new(odd, false)).is_err());
+    }
+
+    {
+        use ref_with_flag::PackedOption;
+
+        let value = 12u32;
+        let mut some = PackedOption::new(Some(&value), false);
+        assert!(some.is_some());
+        assert_eq!(some.get(), Some(&12));
+        some.set_flag(true);
+        assert_eq!((some.get(), some.get_flag()), (Some(&12), true));
+        // option_to_rawの逆向き: 取り出したOptionをrawポインタにすれば元の番地に戻る
+        assert_eq!(option_to_raw(some.get()), &value as *const u32);
+
+        // フラグが立っていても、ヌルの番地はNoneのまま
+        let none = PackedOption::<u32>::new(None, true);
+        assert!(!none.is_some());
+        assert_eq!((none.get(), none.get_flag()), (None, true));
+        assert_eq!(option_to_raw(none.get()), std::ptr::null());
+        assert_eq!(std::mem::size_of::<PackedOption<u32>>(), std::mem::size_of::<Option<&u32>>());
+        // PackedOption::new(Some(&0u8), false); // evaluation panicked: alignment of T is too small for a flag bit
+    }
+
+    {
+        use ref_with_flag::PtrEither;
+
+        // 整数か文字列のどちらかを指す値
+        let number = 42i64;
+        let text = String::from("forty-two");
+        let values: Vec<PtrEither<i64, String>> = vec![PtrEither::new_a(&number), PtrEither::new_b(&text)];
+        let shown: Vec<String> = values.iter()
+            .map(|value| value.fold(|n| format!("int {}", n), |s| format!("str {}", s)))
+            .collect();
+        assert_eq!(shown, ["int 42", "str forty-two"]);
+        assert!(values[0].is_a() && values[1].is_b());
+        assert_eq!((values[0].as_a(), values[0].as_b()), (Some(&42), None));
+        assert_eq!((values[1].as_a(), values[1].as_b()), (None, Some(&text)));
+        assert_eq!(std::mem::size_of::<PtrEither<i64, String>>(), std::mem::size_of::<&i64>());
+        // PtrEither::<u8, u32>::new_b(&0); // evaluation panicked: alignment of A and B must be at least 2
+    }
+
+    {
+        use ref_with_flag::RefWithFlag;
+
+        // マークアンドスイープの印のように、作った後でフラグだけを付け外しする
+        let objects = [String::from("root"), String::from("leaf"), String::from("garbage")];
+        let mut heap: Vec<RefWithFlag<String>> = objects.iter().map(|object| RefWithFlag::new(object, false)).collect();
+        heap[0].set_flag(true);
+        heap[1].toggle_flag();
+        let live: Vec<&str> = heap.iter().filter(|object| object.get_flag()).map(|object| object.get_ref().as_str()).collect();
+        assert_eq!(live, ["root", "leaf"]);
+        heap[1].toggle_flag();
+        assert!(!heap[1].get_flag());
+
+        // 参照先は一切書き換わっていない
+        assert!(heap.iter().zip(&objects).all(|(object, original)| std::ptr::eq(object.get_ref(), original)));
+        assert_eq!(objects, ["root", "leaf", "garbage"]);
+
+        let unmarked = RefWithFlag::new(&objects[2], true).with_flag(false);
+        assert_eq!((unmarked.get_ref().as_str(), unmarked.get_flag()), ("garbage", false));
+        assert!(unmarked.with_flag(true).get_flag());
+    }
+
+    {
+        use arena32::{Arena, Handle};
+
+        // 添字でつないだ単方向リスト
+        struct Node {
+            value: i32,
+            next: Option<Handle<Node>>
+        }
+        let mut arena = Arena::new();
+        assert!(arena.is_empty());
+        let mut head = None;
+        for value in [3, 2, 1] {
+            head = Some(arena.alloc(Node { value, next: head }));
+        }
+        let mut values = Vec::new();
+        let mut cursor = head;
+        while let Some(handle) = cursor {
+            values.push(arena.get(handle).value);
+            cursor = arena.get(handle).next;
+        }
+        assert_eq!(values, [1, 2, 3]);
+        assert_eq!(arena.len(), 3);
 
-    // &vec![42_u8] as *const String; // casting `&std::vec::Vec<u8>` as `*const std::string::String` is invalid
-    &vec![42_u8] as *const Vec<u8> as *const String; // この変換は許される
+        // リンク1本がポインタの半分の大きさになる
+        assert_eq!(std::mem::size_of::<Option<Handle<Node>>>(), 4);
+        assert_eq!(std::mem::size_of::<Option<Box<Node>>>(), std::mem::size_of::<usize>());
 
-    let vec = vec![10, 20, 30];
-    let flagged = ref_with_flag::RefWithFlag::new(&vec, true);
-    assert_eq!(flagged.get_ref()[1], 20); // ラップしたvec参照の要素を取り出す
-    assert_eq!(flagged.get_flag(), true); // ラップしたvecのメモリに保存した値boolを取り出す
+        // 2つのノードを同時に書き換える
+        let first = head.unwrap();
+        let second = arena.get(first).next.unwrap();
+        let (a, b) = arena.get_pair_mut(second, first);
+        std::mem::swap(&mut a.value, &mut b.value);
+        assert_eq!((arena.get(first).value, arena.get(second).value), (2, 1));
+        assert!(catch_panic(std::panic::AssertUnwindSafe(|| { arena.get_pair_mut(first, first); })).is_err());
+        arena.get_mut(second).value = 20;
+        assert_eq!(arena.get(second).value, 20);
 
-    // 計算機プロセッサによって型のサイズとアラインメントが決定される
-    assert_eq!(std::mem::size_of::<i64>(), 8);
-    assert_eq!(std::mem::align_of::<(i32, i32)>(), 4);
+        // タグを付けても同じオブジェクトを指す
+        let marked = second.with_tag(3);
+        assert_eq!((marked.index(), marked.tag()), (second.index(), 3));
+        assert_ne!(marked, second);
+        assert_eq!(marked.with_tag(0), second);
+        assert_eq!(arena.get(marked).value, 20);
+        assert_eq!(format!("{:?}", marked), "Handle(1, tag 3)");
+        assert!(catch_panic(|| second.with_tag(4)).is_err());
+        assert_eq!(arena32::MAX_LEN, (1 << 30) - 1);
+    }
 
-    let slice: &[i32] = &[1, 3, 9, 27, 81];
-    assert_eq!(std::mem::size_of_val(slice), 20);
-    let text: &str = "alligator";
-    assert_eq!(std::mem::size_of_val(text), 9);
+    {
+        use nanbox::{Unpacked, Value};
+
+        // どの値も8バイトの語1つに収まる
+        assert_eq!(std::mem::size_of::<Value>(), 8);
+
+        // 浮動小数点数は特殊な値も含めて、ビット列ごとそのまま戻る
+        let floats = [0.0, -0.0, 1.5, -2.25, f64::MIN_POSITIVE, 5e-324, f64::MAX, f64::MIN,
+                      f64::INFINITY, f64::NEG_INFINITY, std::f64::consts::PI];
+        for &x in &floats {
+            let value = Value::from_f64(x);
+            assert_eq!(value.as_f64().map(f64::to_bits), Some(x.to_bits()));
+            assert_eq!(value.unpack(), Unpacked::Float(x));
+            assert_eq!((value.as_i32(), value.as_bool(), value.is_nil(), value.as_ptr::<u8>()), (None, None, false, None));
+        }
+        // NaNは符号や仮数部に関わらず1つのNaNにそろう
+        for bits in [0x7ff8_0000_0000_0000u64, 0x7ff0_0000_0000_0001, 0xfff8_0000_0000_0000, 0xffff_ffff_ffff_ffff] {
+            let value = Value::from_f64(f64::from_bits(bits));
+            assert!(value.as_f64().unwrap().is_nan());
+            assert_eq!(value, Value::from_f64(f64::NAN));
+        }
+        // ランダムなビット列の浮動小数点数も、ボックスの値と取り違えない
+        let mut state = 0x853c_49e6_748f_ea9bu64;
+        for _ in 0..10_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let x = f64::from_bits(state);
+            match Value::from_f64(x).unpack() {
+                Unpacked::Float(y) => assert!(x.to_bits() == y.to_bits() || x.is_nan() && y.is_nan()),
+                other => panic!("{} unpacked as {:?}", x, other)
+            }
+        }
+
+        for &n in &[0, 1, -1, 42, i32::MIN, i32::MAX] {
+            let value = Value::from_i32(n);
+            assert_eq!((value.as_i32(), value.unpack()), (Some(n), Unpacked::Int(n)));
+            assert_eq!((value.as_f64(), value.as_bool(), value.is_nil()), (None, None, false));
+        }
+        for &b in &[false, true] {
+            let value = Value::from_bool(b);
+            assert_eq!((value.as_bool(), value.unpack()), (Some(b), Unpacked::Bool(b)));
+            assert_eq!((value.as_f64(), value.as_i32()), (None, None));
+        }
+        assert!(Value::nil().is_nil());
+        assert_eq!((Value::nil().unpack(), Value::nil().as_bool()), (Unpacked::Nil, None));
+        assert_ne!(Value::nil(), Value::from_bool(false));
+        assert_ne!(Value::from_i32(0), Value::from_bool(false));
+
+        // ヒープ上の値を指すポインタ
+        let heap = Box::new(String::from("boxed"));
+        let value = Value::from_ptr(&*heap as *const String);
+        let ptr = value.as_ptr::<String>().unwrap();
+        assert_eq!(unsafe { &*ptr }, "boxed");
+        assert_eq!(value.unpack(), Unpacked::Ptr(ptr as *const ()));
+        assert_eq!((value.as_f64(), value.as_i32(), value.is_nil()), (None, None, false));
+        assert_eq!(Value::from_ptr(std::ptr::null::<u8>()).as_ptr::<u8>(), Some(std::ptr::null()));
+        assert_eq!(format!("{:?}", Value::from_i32(-7)), "Int(-7)");
+        assert!(Value::from_bool(true).to_bits() >> 51 == 0x1fff);
+    }
 
-    // トレイトオブジェクトそのものではなく、トレイトオブジェクトが指す値のサイズ・アラインメントを返す
-    use std::fmt::Display;
-    let unremarkable: &dyn Display = &193_u8;
-    let remarkable: &dyn Display = &0.0072973525664;
-    assert_eq!(std::mem::size_of_val(unremarkable), 1);
-    assert_eq!(std::mem::align_of_val(remarkable), 8);
     {
-        let pot = "pasta".to_string();
-        let _plate;
-        _plate = pot; // 変数potのメモリアドレスは未初期化状態になる
+        use ref_with_flag::RefWithFlag;
+
+        let words = vec!["alpha", "beta"];
+        let flagged = RefWithFlag::new(&words, true);
+        assert_eq!(flagged.len(), 2);
+        assert_eq!(flagged[1], "beta");
+        assert_eq!(flagged.iter().map(|word| word.len()).sum::<usize>(), 9);
+        fn total_len<S: AsRef<Vec<&'static str>>>(words: S) -> usize {
+            words.as_ref().len()
+        }
+        assert_eq!(total_len(flagged), 2);
     }
+
     {
-        let mut noodles = vec!["udon".to_string()]; // noodles[0]のみメモリ確保された状態
-        let soba = "soba".to_string();
-        let _last; // 最終的に変数lastだけが所有権を持つ
-        noodles.push(soba); // noodles[1]にメモリ確保され、変数sobaは未初期化状態になる
-        _last = noodles.pop().unwrap(); // noodles[1]は未初期化状態になる
+        use ref_with_flag::RefWithFlag;
+        use std::collections::HashSet;
+
+        let (left, right) = (String::from("node"), String::from("node"));
+        let flagged = RefWithFlag::new(&left, false);
+        let copied = flagged;
+        assert_eq!(flagged, copied); // Copyなのでムーブした後もflaggedを使える
+        assert_eq!(flagged.with_flag(true), RefWithFlag::new(&left, true));
+        #[allow(clippy::clone_on_copy)]
+        let cloned = flagged.clone();
+        assert_eq!(cloned, flagged);
+        assert_ne!(flagged, flagged.with_flag(true));
+
+        // 値が等しくても、別の場所を指していれば等しくない
+        let other = RefWithFlag::new(&right, false);
+        assert_ne!(flagged, other);
+        assert_eq!(*flagged, *other);
+
+        let set: HashSet<RefWithFlag<String>> = [flagged, copied, other, other.with_flag(true)].iter().copied().collect();
+        assert_eq!(set.len(), 3);
+        assert_eq!(format!("{:?}", flagged.with_flag(true)), r#"RefWithFlag { ref: "node", flag: true }"#);
     }
 
+    #[cfg(all(target_pointer_width = "64", any(target_arch = "x86_64", target_arch = "aarch64")))]
     {
-        use gap::GapBuffer;
-        // type GapBufferを使ったコード
-        let mut buf = GapBuffer::new();
-        buf.insert_iter("Lord of the Rings".chars());
-        buf.set_position(12);
-        buf.insert_iter("Onion ".chars());
+        use ref_with_flag::HighTagPtr;
 
-        assert_eq!('R', buf.remove().unwrap());
-        assert_eq!('i', buf.remove().unwrap());
-        assert_eq!('n', buf.remove().unwrap());
-        assert_eq!('g', buf.remove().unwrap());
-        assert_eq!('s', buf.remove().unwrap());
+        // 1バイト単位のu8の、奇数番地を指すポインタにもタグを付けられる
+        let mut bytes = [10u8, 20, 30];
+        let odd = if (&bytes[0] as *const u8).addr().is_multiple_of(2) { &mut bytes[1] } else { &mut bytes[0] } as *mut u8;
+        for &tag in &[0, 1, 0x7fff, 0x8000, u16::MAX] {
+            let mut tagged = HighTagPtr::new(odd, tag);
+            assert_eq!((tagged.ptr(), tagged.tag()), (odd, tag));
+            tagged.set_tag(!tag);
+            assert_eq!((tagged.ptr(), tagged.tag()), (odd, !tag));
+            unsafe { *tagged.ptr() += 1 };
+        }
+        assert_eq!(bytes.iter().map(|&b| b as u32).sum::<u32>(), 60 + 5);
 
-        let n = buf.get(buf.position());
-        assert_eq!(None, n);
-        let m = buf.get(buf.len());
-        assert_eq!(None, m);
+        // ヒープやスタックのアドレスも、上位16ビットを使っても元に戻る
+        let mut boxed = Box::new(7u64);
+        let copied = HighTagPtr::new(&mut *boxed as *mut u64, 0xabcd);
+        let tagged = copied;
+        assert_eq!(unsafe { *tagged.ptr() }, 7);
+        assert_eq!(copied.tag(), 0xabcd); // Copyなのでtaggedに移した後も使える
+        assert_eq!(HighTagPtr::new(std::ptr::null_mut::<u8>(), 1).ptr(), std::ptr::null_mut());
+        // カーネル空間のような上位ビットが全部1の正準形アドレスも、符号拡張で戻す
+        let kernel = std::ptr::without_provenance_mut::<u8>(0xffff_8000_0000_1000);
+        assert_eq!(HighTagPtr::new(kernel, 0x1234).ptr(), kernel);
+        assert!(catch_panic(|| HighTagPtr::new(std::ptr::without_provenance_mut::<u8>(0x0001_0000_0000_0000), 0)).is_err());
+    }
+
+    {
+        use tagged_list::List;
+
+        let mut list = List::new();
+        assert!(list.is_empty());
+        for n in (1..=10).rev() {
+            list.push_front(n);
+        }
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), (1..=10).collect::<Vec<_>>());
+
+        // 偶数に印を付けると、取り除く前から見えなくなる
+        assert_eq!(list.mark(|n| n % 2 == 0), 5);
+        assert_eq!(list.mark(|&n| n == 4), 0); // 印の付いたノードには付け直さない
+        assert_eq!(list.len(), 5);
+        assert_eq!((&list).into_iter().copied().collect::<Vec<_>>(), [1, 3, 5, 7, 9]);
+
+        // 印の付いたノードを飛ばして、生きているノードだけを取り除く
+        assert_eq!(list.remove_first(|&n| n > 4), Some(5));
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.remove_first(|&n| n == 2), None);
+        assert_eq!(list.sweep(), 5);
+        assert_eq!(list.sweep(), 0);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), [3, 7, 9]);
+        assert_eq!(list.len(), 3);
+
+        exercise_tagged_list();
+
+        // 長いリストでもドロップでスタックを使い果たさない
+        let mut long = List::default();
+        for n in 0..100_000 {
+            long.push_front(n);
+        }
+        long.mark(|n| n % 3 == 0);
+        assert_eq!(long.sweep(), 33_334);
+        assert_eq!(long.len(), 66_666);
+    }
+
+    exercise_tagged_pointers();
+
+    {
+        use ref_with_flag::FatRefWithFlag;
+        use std::fmt::Display;
+
+        // スライスの長さはメタデータの語に残る
+        let numbers = [1u32, 2, 3, 4];
+        let mut slice = FatRefWithFlag::new(&numbers[1..], true);
+        assert_eq!((slice.len(), &slice[..], slice.get_flag()), (3, &[2, 3, 4][..], true));
+        slice.set_flag(false);
+        assert_eq!((slice.get_ref(), slice.get_flag()), (&numbers[1..], false));
+        let empty = FatRefWithFlag::new(&numbers[..0], true);
+        assert!(empty.is_empty() && empty.get_flag());
+        assert_eq!(std::mem::size_of::<FatRefWithFlag<[u32]>>(), std::mem::size_of::<&[u32]>());
+
+        // トレイトオブジェクトのvtableもそのまま使える
+        let values: [&dyn Display; 2] = [&42u64, &"text"];
+        let flagged: Vec<FatRefWithFlag<dyn Display>> = values.iter().map(|&value| FatRefWithFlag::new(value, true)).collect();
+        assert_eq!(flagged.iter().map(|value| value.to_string()).collect::<Vec<_>>(), ["42", "text"]);
+        let copied = flagged[0];
+        assert!(copied.get_flag() && flagged[0].get_flag());
+
+        // 1バイト単位の参照先には下位ビットの余りがない
+        assert!(catch_panic(|| FatRefWithFlag::new(&b"bytes"[..], false)).is_err());
+        assert!(catch_panic(|| FatRefWithFlag::<dyn Display>::new(&1u8, false)).is_err());
+    }
+
+    {
+        let numbers = [10u32, 20, 30, 40];
+        let found: Vec<Option<usize>> = numbers.iter().map(|n| element_offset(&numbers, n)).collect();
+        assert_eq!(found, [Some(0), Some(1), Some(2), Some(3)]);
+        // 部分スライスの中での添字になる
+        assert_eq!(element_offset(&numbers[1..], &numbers[3]), Some(2));
+        assert_eq!(element_offset(&numbers[1..], &numbers[0]), None);
+        // 終端の1つ先の要素は、すぐ隣に並んでいても含まれない
+        assert_eq!(element_offset(&numbers[..2], &numbers[2]), None);
+        // 空のスライスにはどの要素も含まれない
+        assert_eq!(element_offset(&numbers[2..2], &numbers[2]), None);
+        assert_eq!(element_offset(&[], &numbers[0]), None);
+        // 同じ値でも別の場所にあれば含まれない
+        let copy = numbers[1];
+        assert_eq!(element_offset(&numbers, &copy), None);
+        // 要素の中を指す参照や、要素の境目をまたぐ参照
+        let pairs = [(1u32, 2u32), (3, 4)];
+        let inner = unsafe { &*pairs.as_ptr().cast::<u32>().add(1).cast::<(u32, u32)>() };
+        assert_eq!(element_offset(&pairs, inner), None);
+        assert_eq!(element_offset(&pairs, &pairs[1]), Some(1));
+        // サイズが0の型は区別できない
+        let units = [(), (), ()];
+        assert_eq!(element_offset(&units, &units[1]), None);
+        assert_eq!(checked_distance(&numbers, &numbers[3], &numbers[1]), Some(2));
+    }
+
+    {
+        use ref_with_flag::RefWithFlag;
+        use std::mem::size_of;
+        use std::ptr::NonNull;
+
+        // Noneはヌルで表されるので、Option<NonNull<T>>も*const Tと同じ大きさ
+        assert_eq!(size_of::<Option<NonNull<u64>>>(), size_of::<*const u64>());
+        assert_eq!(size_of::<Option<RefWithFlag<u64>>>(), size_of::<usize>());
+
+        let mut value = 5u64;
+        let ptr = option_to_nonnull(Some(&value)).unwrap();
+        assert_eq!(unsafe { *nonnull_to_ref(ptr) }, 5);
+        assert_eq!(nonnull_to_raw(Some(ptr)), &value as *const u64);
+        assert_eq!(option_to_nonnull::<u64>(None), None);
+        assert_eq!(nonnull_to_raw::<u64>(None), option_to_raw::<u64>(None));
+        let ptr = NonNull::from(&mut value);
+        unsafe { *nonnull_to_mut(ptr) += 1 };
+        assert_eq!(value, 6);
+
+        // NonNull<T>は*const Tと同じくTについて共変なので、長い生存期間を短い生存期間として扱える
+        // *mut Tは不変なので、同じ変換はコンパイルできない
+        fn shorten<'a>(ptr: NonNull<&'static str>) -> NonNull<&'a str> {
+            ptr
+        }
+        // fn shorten_mut<'a>(ptr: *mut &'static str) -> *mut &'a str { ptr } // lifetime may not live long enough
+        let text: &'static str = "covariant";
+        let short = shorten(NonNull::from(&text));
+        assert_eq!(unsafe { *nonnull_to_ref(short) }, "covariant");
+    }
+
+    {
+        use std::convert::TryInto;
+        use unaligned::{read_unaligned_at, write_unaligned_at, OutOfBounds};
+
+        // 1バイトの種別の直後に、4バイトの長さと2バイトのポートが詰めて並ぶヘッダ
+        let mut header = vec![0u8; 7];
+        header[0] = 0x2a;
+        write_unaligned_at(&mut header, 1, 0x0102_0304u32).unwrap();
+        write_unaligned_at(&mut header, 5, 8080u16).unwrap();
+        assert_eq!(&header[1..5], &0x0102_0304u32.to_ne_bytes());
+        assert_eq!(read_unaligned_at::<u8>(&header, 0), Ok(0x2a));
+        assert_eq!(read_unaligned_at::<u32>(&header, 1), Ok(0x0102_0304));
+        assert_eq!(read_unaligned_at::<u16>(&header, 5), Ok(8080));
+        // 奇数の位置でも、どの位置からでも読める
+        let bytes: Vec<u8> = (0..16).collect();
+        for offset in 0..=8 {
+            let expected = u64::from_ne_bytes(bytes[offset..offset + 8].try_into().unwrap());
+            assert_eq!(read_unaligned_at::<u64>(&bytes, offset), Ok(expected));
+        }
+        let mut floats = [0u8; 9];
+        write_unaligned_at(&mut floats, 1, [1.5f32, -2.0]).unwrap();
+        assert_eq!(read_unaligned_at::<[f32; 2]>(&floats, 1), Ok([1.5, -2.0]));
+
+        // 範囲外は読み書きせずにエラーを返す
+        let error = OutOfBounds { offset: 5, size: 4, len: 7 };
+        assert_eq!(read_unaligned_at::<u32>(&header, 5), Err(error));
+        assert_eq!(write_unaligned_at(&mut header, 5, 0u32), Err(error));
+        assert_eq!(error.to_string(), "4 bytes at offset 5 out of range for slice of length 7");
+        assert!(read_unaligned_at::<u16>(&header, usize::MAX).is_err()); // offset + sizeが溢れる
+        assert_eq!(read_unaligned_at::<[u8; 0]>(&header, 7), Ok([])); // 0バイトなら終端も読める
+        assert!(read_unaligned_at::<u8>(&[], 0).is_err());
+        assert_eq!(read_unaligned_at::<u16>(&header, 5), Ok(8080)); // 失敗した書き込みは何も変えない
+        // read_unaligned_at::<bool>(&header, 0); // the trait bound `bool: Pod` is not satisfied
+    }
+
+    {
+        use volatile::VolatileCell;
+
+        // 3つの32ビットレジスタが並んだ、シリアルポートに見立てた装置
+        #[repr(C)]
+        struct Uart {
+            status: VolatileCell<u32>,
+            data: VolatileCell<u32>,
+            control: VolatileCell<u32>
+        }
+        const TX_READY: u32 = 1;
+        const ENABLE: u32 = 1 << 0;
+        const LOOPBACK: u32 = 1 << 3;
+
+        fn send(uart: &Uart, bytes: &[u8]) -> usize {
+            let mut sent = 0;
+            for &byte in bytes {
+                // 状態レジスタは読むたびに装置が書き換え得るので、毎回実際に読み直す必要がある
+                if uart.status.read() & TX_READY == 0 {
+                    break;
+                }
+                uart.data.write(byte as u32);
+                sent += 1;
+            }
+            sent
+        }
+
+        // 本物の装置の代わりに、普通のメモリをレジスタの番地に見立てる
+        let mut registers = [TX_READY, 0, 0];
+        {
+            // VolatileCell<u32>はu32と同じレイアウトなので、repr(C)のUartは[u32; 3]と同じ並びになる
+            let uart = unsafe { &*(registers.as_mut_ptr() as *const Uart) };
+            uart.control.write(ENABLE);
+            uart.control.update(|control| control | LOOPBACK);
+            assert_eq!(send(uart, b"hi"), 2);
+            assert_eq!(uart.data.read(), b'i' as u32);
+            uart.status.write(0);
+            assert_eq!(send(uart, b"!"), 0);
+        }
+        assert_eq!(registers, [0, b'i' as u32, ENABLE | LOOPBACK]);
+        assert_eq!(std::mem::size_of::<Uart>(), 12);
+
+        // 1つのレジスタだけを見せる
+        let mut status = TX_READY;
+        let register = unsafe { VolatileCell::from_ptr(&mut status) };
+        register.update(|bits| bits & !TX_READY);
+        assert_eq!(register.read(), 0);
+
+        let cell = VolatileCell::new(1.5f64);
+        cell.write(cell.read() * 2.0);
+        assert_eq!(cell.read(), 3.0);
+    }
+
+    {
+        use align::{align_down, align_up, is_aligned_to, is_ptr_aligned_to, padding_needed_for};
+        use std::convert::TryFrom;
+
+        // u128で計算した答えと、すべてのアライメントについて比べる
+        let values = (0..=130).chain(usize::MAX - 130..=usize::MAX);
+        for value in values {
+            for shift in 0..usize::BITS {
+                let align = 1usize << shift;
+                let (value_wide, align_wide) = (value as u128, align as u128);
+                let up = value_wide.div_ceil(align_wide) * align_wide;
+                let down = value_wide / align_wide * align_wide;
+                assert_eq!(align_up(value, align), usize::try_from(up).ok(), "align_up({}, {})", value, align);
+                assert_eq!(align_down(value, align) as u128, down);
+                assert_eq!(is_aligned_to(value, align), value_wide.is_multiple_of(align_wide));
+                assert_eq!(padding_needed_for(value, align) as u128, up - value_wide);
+            }
+        }
+
+        // 上限付近で溢れる場合
+        assert_eq!(align_up(usize::MAX, 1), Some(usize::MAX));
+        assert_eq!(align_up(usize::MAX, 2), None);
+        assert_eq!(align_up(usize::MAX - 7, 8), Some(usize::MAX - 7));
+        assert_eq!(align_up(usize::MAX - 6, 8), None);
+        assert_eq!(align_up(1, 1 << (usize::BITS - 1)), Some(1 << (usize::BITS - 1)));
+        assert_eq!(align_up((1 << (usize::BITS - 1)) + 1, 1 << (usize::BITS - 1)), None);
+        assert_eq!(padding_needed_for(usize::MAX, 8), 1);
+
+        // 定数の文脈でも使える
+        const PAGE: usize = 4096;
+        const HEADER_END: usize = match align_up(100, PAGE) {
+            Some(end) => end,
+            None => panic!()
+        };
+        assert_eq!(HEADER_END, PAGE);
+        // const BAD: usize = align_down(100, 3); // evaluation panicked: align must be a power of two
+
+        // 2の累乗でないアライメントはpanicを起こす
+        for align in [0, 3, 6, 12, usize::MAX] {
+            assert!(catch_panic(|| align_up(1, align)).is_err());
+            assert!(catch_panic(|| is_aligned_to(1, align)).is_err());
+        }
+
+        let words = [0u64; 2];
+        assert!(is_ptr_aligned_to(words.as_ptr(), 8));
+        assert!(!is_ptr_aligned_to((words.as_ptr() as *const u8).wrapping_add(4), 8));
+        assert!(is_ptr_aligned_to(&words[..] as *const [u64], 8));
+    }
+
+    {
+        use fatptr::{is_fat, join_dyn, join_slice, split_dyn, split_slice};
+        use std::fmt::Display;
+
+        assert!(is_fat::<[i32]>() && is_fat::<str>() && is_fat::<dyn Display>());
+        assert!(!is_fat::<i32>() && !is_fat::<Vec<i32>>());
+
+        // スライスは先頭の要素へのポインタと要素数
+        let numbers = [1, 3, 9, 27, 81];
+        let slice: &[i32] = &numbers[1..4];
+        let (data, len) = split_slice(slice);
+        assert_eq!((data, len), (&numbers[1] as *const i32, 3));
+        let rebuilt = unsafe { &*join_slice(data, len) };
+        assert_eq!(rebuilt, slice);
+        assert!(std::ptr::eq(rebuilt, slice));
+        // 要素数を変えれば、同じ先頭から別の長さのスライスになる
+        assert_eq!(unsafe { &*join_slice(data, 2) }, &[3, 9]);
+
+        // トレイトオブジェクトはデータへのポインタとvtableへのポインタ
+        let small: &dyn Display = &193_u8;
+        let float: &dyn Display = &0.0072973525664;
+        // dyn Displayはトレイトオブジェクトなので、split_dynに渡してよい
+        let parts = unsafe { split_dyn(small) };
+        assert_eq!(parts.data, small as *const dyn Display as *const ());
+        let rebuilt: &dyn Display = unsafe { &*join_dyn(parts) };
+        assert!(std::ptr::eq(rebuilt, small));
+        assert_eq!(rebuilt.to_string(), "193");
+        assert_eq!((std::mem::size_of_val(rebuilt), std::mem::align_of_val(rebuilt)), (1, 1));
+        // 型が違えばvtableも違う
+        assert_ne!(unsafe { split_dyn(float) }.vtable, parts.vtable);
+        // 同じvtableなら、別のデータと組み合わせても正しく呼び出せる
+        let other = 7_u8;
+        let moved: &dyn Display = unsafe {
+            &*join_dyn(fatptr::TraitObject { data: &other as *const u8 as *const (), vtable: parts.vtable })
+        };
+        assert_eq!(moved.to_string(), "7");
+
+        assert!(catch_panic(|| unsafe { split_dyn(&1u32 as *const u32) }).is_err());
+    }
+
+    {
+        use fatptr::vtable_header;
+        use std::alloc::{dealloc, Layout};
+        use std::any::Any;
+        use std::fmt::Debug;
+        use std::rc::Rc;
+
+        // ここで渡すのはどれもdyn Debugかdyn Anyなので、vtable_headerを呼んでよい
+        // サイズとアライメントは、size_of_val・align_of_valが返すものと同じ値がvtableに入っている
+        let objects: [&dyn Debug; 4] = [&1u8, &2.5f64, &"text", &[1u16, 2, 3]];
+        for &object in &objects {
+            let header = unsafe { vtable_header(object) };
+            assert_eq!((header.size, header.align), (std::mem::size_of_val(object), std::mem::align_of_val(object)));
+        }
+        let array = unsafe { vtable_header(objects[3]) };
+        assert_eq!((array.size, array.align), (6, 2));
+
+        // ドロップ処理が要る型だけ、ドロップ処理の欄が埋まっている
+        assert!(unsafe { vtable_header(&1u8 as &dyn Debug) }.drop_in_place.is_none());
+        assert!(unsafe { vtable_header(&String::new() as &dyn Debug) }.drop_in_place.is_some());
+
+        // Box<dyn Any>のドロップを、vtableの欄だけを使って手で行う
+        let shared = Rc::new(());
+        let boxed: Box<dyn Any> = Box::new((Rc::clone(&shared), 42u64));
+        let header = unsafe { vtable_header(&*boxed) };
+        let drop_fn = header.drop_in_place.unwrap();
+        let layout = Layout::from_size_align(header.size, header.align).unwrap();
+        let raw = Box::into_raw(boxed);
+        assert_eq!(Rc::strong_count(&shared), 2);
+        unsafe {
+            drop_fn(raw as *mut ());
+            dealloc(raw as *mut u8, layout);
+        }
+        assert_eq!(Rc::strong_count(&shared), 1);
+    }
+
+    {
+        #[repr(C)]
+        struct Header {
+            kind: u8,
+            length: u32,
+            flags: u16
+        }
+        // offset_of!とMaybeUninitを使ったやり方は同じ値になる
+        assert_eq!(offset_of_uninit!(Header, kind), std::mem::offset_of!(Header, kind));
+        assert_eq!(offset_of_uninit!(Header, length), 4);
+        assert_eq!(offset_of_uninit!(Header, flags), std::mem::offset_of!(Header, flags));
+
+        let mut header = Header { kind: 1, length: 0, flags: 0 };
+        let base = &mut header as *mut Header;
+        let length: *mut u32 = project_field_raw!(base, Header, length);
+        unsafe { *length = 512 };
+        unsafe { *project_field_raw!(base, Header, flags) |= 0x8000 };
+        assert_eq!((header.kind, header.length, header.flags), (1, 512, 0x8000));
+
+        // 侵入型の単方向リスト: リンクをノードに埋め込み、リンクからノードを求める
+        struct Link {
+            next: *mut Link
+        }
+        struct Task {
+            id: u32,
+            link: Link
+        }
+        let mut tasks: Vec<Task> = (0..3).map(|id| Task { id, link: Link { next: std::ptr::null_mut() } }).collect();
+        let base = tasks.as_mut_ptr();
+        // 各リンクはVecの領域全体を指すbaseから作るので、そこからTaskに戻して読める
+        let links: Vec<*mut Link> = (0..3).map(|i| project_field_raw!(base.wrapping_add(i), Task, link)).collect();
+        let (first, second, third) = (links[0], links[1], links[2]);
+        unsafe {
+            (*third).next = first;
+            (*first).next = second;
+        }
+        let mut ids = Vec::new();
+        let mut link = third;
+        while !link.is_null() {
+            let task = container_of!(link, Task, link);
+            unsafe {
+                ids.push((*task).id);
+                link = (*link).next;
+            }
+        }
+        assert_eq!(ids, [2, 0, 1]);
+        assert_eq!(container_of!(second, Task, link), &mut tasks[1] as *mut Task);
+        // project_field_raw!(base, Task, missing); // no field `missing` on type `Task`
+    }
+
+    {
+        use bounded_ptr::{Access, BoundedPtr, OutOfBounds};
+        use std::cell::RefCell;
+
+        let mut numbers = [10, 20, 30, 40];
+        {
+            let accesses = RefCell::new(Vec::new());
+            let log = |access| accesses.borrow_mut().push(access);
+            let first = BoundedPtr::new(&mut numbers).with_logger(&log);
+            let third = first.offset(2).unwrap();
+            assert_eq!((third.index(), third.read()), (2, Ok(30)));
+            third.write(33).unwrap();
+            // 複製したポインタで同じ要素を読んでも構わない
+            let copy = third;
+            assert_eq!(copy.read(), Ok(33));
+            assert_eq!(third.as_ptr(), first.as_ptr().wrapping_add(2));
+
+            // 終端の1つ先までは動けるが、そこは読み書きできない
+            let end = first.offset(4).unwrap();
+            assert_eq!(end.read(), Err(OutOfBounds { index: 4, len: 4 }));
+            assert_eq!(end.write(0), Err(OutOfBounds { index: 4, len: 4 }));
+            assert_eq!(end.offset(-1).and_then(|last| last.read()), Ok(40));
+            // 範囲の外へは動けない
+            assert_eq!(first.offset(5).err(), Some(OutOfBounds { index: 5, len: 4 }));
+            assert_eq!(first.offset(-1).err(), Some(OutOfBounds { index: -1, len: 4 }));
+            assert!(third.offset(isize::MAX).is_err());
+            assert_eq!(first.offset(-1).unwrap_err().to_string(), "index -1 out of bounds for allocation of length 4");
+
+            // 範囲外の操作は記録されない
+            assert_eq!(*accesses.borrow(), [Access::Read(2), Access::Write(2), Access::Read(2), Access::Read(3)]);
+        }
+        assert_eq!(numbers, [10, 20, 33, 40]);
+
+        // 空の割り当ては、先頭が終端の1つ先でもある
+        let empty = BoundedPtr::<u8>::new(&mut []);
+        assert!(empty.read().is_err());
+        assert_eq!(empty.offset(0).map(|ptr| ptr.index()), Ok(0));
+    }
+
+    {
+        // 同じフィールドを、表現を変えて並べる
+        #[allow(dead_code)]
+        struct Plain {
+            flag: u8,
+            value: u32,
+            tag: u8
+        }
+        #[allow(dead_code)]
+        #[repr(C)]
+        struct C {
+            flag: u8,
+            value: u32,
+            tag: u8
+        }
+        #[allow(dead_code)]
+        #[repr(C, packed)]
+        struct Packed {
+            flag: u8,
+            value: u32,
+            tag: u8
+        }
+
+        // repr(C)は宣言の順に並べ、各フィールドをアライメントに揃えるためにパディングが入る
+        let c = layout_of!(C { flag, value, tag });
+        assert_eq!((c.size(), c.align()), (12, 4));
+        assert_eq!(c.padding_ranges(), [1..4, 9..12]);
+        assert_eq!(c.to_string(), "C: size 12, align 4, padding 6\n  0..1 flag (1)\n  1..4 padding (3)\n  4..8 value (4)\n  8..9 tag (1)\n  9..12 padding (3)\n");
+
+        // 既定の表現では、コンパイラがフィールドを並べ替えてパディングを減らす
+        let plain = layout_of!(Plain { flag, value, tag });
+        assert_eq!((plain.size(), plain.align(), plain.padding()), (8, 4, 2));
+        assert_eq!(plain.fields()[0].name, "value");
+
+        // repr(packed)はパディングを入れない代わりに、valueが4の倍数の位置に来ない
+        let packed = layout_of!(Packed { flag, value, tag });
+        assert_eq!((packed.size(), packed.align(), packed.padding()), (6, 1, 0));
+        assert_eq!(packed.field("value"), Some(&layout::FieldLayout { name: "value", offset: 1, size: 4 }));
+        assert_eq!(packed.field("missing"), None);
+    }
+
+    {
+        use unaligned::{read_unaligned_at, write_unaligned_at, Pod};
+
+        // ネットワーク越しに届く、詰めて並んだ7バイトのヘッダ。数値はビッグエンディアンで届く
+        #[derive(Clone, Copy)]
+        #[repr(C, packed)]
+        struct WireHeader {
+            version: u8,
+            length: u32,
+            port: u16
+        }
+        // どのフィールドもPodで、packedなのでパディングもない
+        unsafe impl Pod for WireHeader {}
+
+        packed_accessors!(WireHeader {
+            version, set_version: u8;
+            length, set_length: u32;
+            port, set_port: u16;
+        });
+
+        impl WireHeader {
+            fn parse(bytes: &[u8]) -> Option<WireHeader> {
+                read_unaligned_at(bytes, 0).ok()
+            }
+        }
+
+        let bytes = [2, 0, 0, 0x01, 0x00, 0x1f, 0x90, 0xff];
+        let mut header = WireHeader::parse(&bytes).unwrap();
+        assert_eq!(std::mem::size_of::<WireHeader>(), 7);
+        assert_eq!(header.version(), 2);
+        assert_eq!(u32::from_be(header.length()), 256);
+        assert_eq!(u16::from_be(header.port()), 8080);
+        // let length = &header.length; // reference to field of packed struct is unaligned
+
+        header.set_version(3);
+        header.set_port(443u16.to_be());
+        header.set_length(header.length().to_be().wrapping_add(1).to_be());
+        let mut out = [0u8; 7];
+        write_unaligned_at(&mut out, 0, header).unwrap();
+        assert_eq!(out, [3, 0, 0, 0x01, 0x01, 0x01, 0xbb]);
+        assert!(WireHeader::parse(&bytes[..6]).is_none());
+
+        // 値をコピーして読むだけなら、フィールドの値を直接読んでもよい。コンパイラが揃っていない番地からの読み込みを生成する
+        let version = header.version;
+        assert_eq!(version, 3);
+    }
+
+    {
+        use endian::*;
+
+        let bytes = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0xff];
+        assert_eq!(read_u16_le(&bytes, 0), Some(0x0201));
+        assert_eq!(read_u16_be(&bytes, 0), Some(0x0102));
+        assert_eq!(read_u32_be(&bytes, 1), Some(0x0203_0405));
+        assert_eq!(read_u64_le(&bytes, 0), Some(0x0807_0605_0403_0201));
+        assert_eq!(read_i16_be(&bytes, 7), Some(0x08ff));
+        assert_eq!(read_i16_le(&bytes, 7), Some(-248));
+        assert_eq!(read_u64_be(&bytes, 2), None);
+        assert_eq!(read_u32_le(&bytes, usize::MAX), None);
+        assert_eq!(unsafe { read_u32_be_unchecked(&bytes, 5) }, 0x0607_08ff);
+
+        // 安全な版とunsafeな版を、あらゆる位置と両方のバイト順で突き合わせる
+        let mut seed = 7u32;
+        let data: Vec<u8> = (0..67).map(|_| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) as u8
+        }).collect();
+        // ByteReaderも、読み進めた位置ごとに安全な関数で読んだ値と比べる
+        let mut reader = ByteReader::new(&data);
+        for offset in 0..data.len() + 1 {
+            macro_rules! check {
+                ($($t:ty => $checked:ident, $unchecked:ident;)*) => {
+                    $(
+                        match $checked(&data, offset) {
+                            Some(value) => assert_eq!(value, unsafe { $unchecked(&data, offset) }),
+                            None => assert!(offset + std::mem::size_of::<$t>() > data.len())
+                        }
+                        assert_eq!(reader.clone().$checked(), $checked(&data, offset));
+                    )*
+                };
+            }
+            check! {
+                u16 => read_u16_le, read_u16_le_unchecked; u16 => read_u16_be, read_u16_be_unchecked;
+                u32 => read_u32_le, read_u32_le_unchecked; u32 => read_u32_be, read_u32_be_unchecked;
+                u64 => read_u64_le, read_u64_le_unchecked; u64 => read_u64_be, read_u64_be_unchecked;
+                i16 => read_i16_le, read_i16_le_unchecked; i16 => read_i16_be, read_i16_be_unchecked;
+                i32 => read_i32_le, read_i32_le_unchecked; i32 => read_i32_be, read_i32_be_unchecked;
+                i64 => read_i64_le, read_i64_le_unchecked; i64 => read_i64_be, read_i64_be_unchecked;
+            }
+            assert_eq!(reader.position(), offset);
+            let _ = reader.skip(1);
+        }
+
+        // 長さ付きのレコードを読むカーソル
+        let record = [0xca, 0xfe, 3, 0, b'a', b'b', b'c', 0xff, 0xff, 0xff, 0xfe, 0x2a];
+        let mut reader = ByteReader::new(&record);
+        assert_eq!(reader.read_u16_be(), Some(0xcafe));
+        let len = reader.read_u16_le().unwrap() as usize;
+        assert_eq!(reader.read_bytes(len), Some(&b"abc"[..]));
+        assert_eq!(reader.read_i32_be(), Some(-2));
+        assert_eq!(reader.position(), 11);
+        assert_eq!(reader.read_u16_le(), None);
+        assert_eq!(reader.remaining(), 1);
+        assert_eq!(reader.read_u8(), Some(0x2a));
+        assert_eq!(reader.skip(1), None);
+    }
+
+    {
+        use any_box::AnyBox;
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct CountDrop(Rc<Cell<usize>>);
+
+        impl Drop for CountDrop {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Rc::new(Cell::new(0));
+        let mut values = vec![AnyBox::new(42i32),
+                              AnyBox::new(String::from("erased")),
+                              AnyBox::new(()),
+                              AnyBox::new(CountDrop(drops.clone()))];
+        assert!(values[0].is::<i32>());
+        assert!(!values[0].is::<u32>());
+        assert_eq!(values[1].downcast_ref::<String>().map(|s| s.as_str()), Some("erased"));
+        assert_eq!(values[1].downcast_ref::<&str>(), None);
+        assert_eq!(values[2].downcast_ref::<()>(), Some(&()));
+        assert_eq!(format!("{:?}", values[0]), "AnyBox<i32>");
+        assert!(values[1].type_name().ends_with("String"));
+
+        *values[0].downcast_mut::<i32>().unwrap() += 1;
+        values[1].downcast_mut::<String>().unwrap().push('!');
+
+        // 型が違えば、中身を失わずにそのまま返ってくる
+        let first = values.remove(0);
+        let first = first.downcast::<String>().unwrap_err();
+        assert_eq!(*first.downcast::<i32>().unwrap(), 43);
+        assert_eq!(*values.remove(0).downcast::<String>().unwrap(), "erased!");
+
+        // 型を忘れたままでも、Dropは元の型のdropを呼ぶ
+        assert_eq!(drops.get(), 0);
+        drop(values);
+        assert_eq!(drops.get(), 1);
+
+        let counted = AnyBox::new(CountDrop(drops.clone()));
+        let counted = counted.downcast::<CountDrop>().unwrap();
+        assert_eq!(drops.get(), 1);
+        drop(counted);
+        assert_eq!(drops.get(), 2);
+    }
+
+    {
+        use manual_vtable::{vtable_for, DynWriter};
+        use std::io::Write;
+
+        // 書き込みを小分けにしか受け付けず、flushの回数を数える書き込み先
+        struct Chunked<'a> {
+            out: &'a mut Vec<u8>,
+            flushes: &'a mut usize
+        }
+
+        impl<'a> Write for Chunked<'a> {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                let len = std::cmp::min(buf.len(), 3);
+                self.out.extend_from_slice(&buf[..len]);
+                Ok(len)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                *self.flushes += 1;
+                Ok(())
+            }
+        }
+
+        // 同じ書き込みを、本物のdyn Writeと手で作ったDynWriterの両方で行う
+        fn write_all_to(writer: &mut dyn Write) {
+            writer.write_all(b"hello, ").unwrap();
+            write!(writer, "vtable {}", 42).unwrap();
+            writer.flush().unwrap();
+        }
+
+        let (mut real, mut manual) = (Vec::new(), Vec::new());
+        let mut boxed: Box<dyn Write> = Box::new(&mut real);
+        write_all_to(&mut boxed);
+        drop(boxed);
+        write_all_to(&mut DynWriter::new(&mut manual));
+        assert_eq!(real, manual);
+        assert_eq!(manual, b"hello, vtable 42");
+
+        let (mut real, mut manual) = (Vec::new(), Vec::new());
+        let (mut real_flushes, mut manual_flushes) = (0, 0);
+        let mut boxed: Box<dyn Write> = Box::new(Chunked { out: &mut real, flushes: &mut real_flushes });
+        let mut erased = DynWriter::new(Chunked { out: &mut manual, flushes: &mut manual_flushes });
+        write_all_to(&mut boxed);
+        write_all_to(&mut erased);
+        assert_eq!(erased.write(b"abcdef").unwrap(), boxed.write(b"abcdef").unwrap());
+
+        // 表の先頭は、rustcが作ったvtableの先頭と同じサイズ・アライメントを持つ
+        let header = unsafe { fatptr::vtable_header(&*boxed) };
+        assert_eq!(erased.size_of_val(), header.size);
+        assert_eq!(erased.vtable().align, header.align);
+        drop((boxed, erased));
+        assert_eq!((real_flushes, manual_flushes), (1, 1));
+        assert_eq!(real, manual);
+
+        // 表には型ごとのサイズとアライメントが入る
+        assert_eq!(vtable_for::<Vec<u8>>().size, std::mem::size_of::<Vec<u8>>());
+        assert_eq!(vtable_for::<std::io::Sink>().size, 0);
+        assert_eq!(vtable_for::<std::io::Sink>().align, 1);
+
+        // Dropも表を通して元の型のdropを呼ぶ
+        // BufWriterはドロップ時に溜めた分を書き出す
+        let (mut out, mut flushes) = (Vec::new(), 0);
+        let mut erased = DynWriter::new(std::io::BufWriter::new(Chunked { out: &mut out, flushes: &mut flushes }));
+        erased.write_all(b"buffered").unwrap();
+        drop(erased);
+        assert_eq!(out, b"buffered");
+
+        let shared = std::rc::Rc::new(());
+        struct Holder(#[allow(dead_code)] std::rc::Rc<()>);
+
+        impl Write for Holder {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let erased = DynWriter::new(Holder(shared.clone()));
+        assert_eq!(std::rc::Rc::strong_count(&shared), 2);
+        drop(erased);
+        assert_eq!(std::rc::Rc::strong_count(&shared), 1);
+    }
+
+    #[cfg(unix)]
+    {
+        use guarded::{faults, page_size, GuardedBuf};
+
+        let mut buf = GuardedBuf::new(10).unwrap();
+        assert_eq!(buf.len(), 10);
+        assert!(buf.as_slice().iter().all(|&b| b == 0));
+        buf.as_mut_slice().copy_from_slice(b"0123456789");
+        assert_eq!(buf.as_slice(), b"0123456789");
+        // 末尾はページの境目にある
+        assert!(align::is_ptr_aligned_to(buf.as_ptr().wrapping_add(buf.len()), page_size()));
+        assert!(GuardedBuf::new(usize::MAX).is_err());
+
+        // 範囲内の読み書きは落ちないが、末尾を1バイト越えると必ず落ちる
+        let end = unsafe { buf.as_mut_ptr().add(buf.len()) };
+        assert!(!faults(|| unsafe { end.sub(1).write_volatile(b'!') }));
+        assert!(faults(|| unsafe { end.write_volatile(b'!') }));
+        assert!(faults(|| unsafe {
+            end.read_volatile();
+        }));
+        // 子プロセスでの書き込みは、親のバッファには届かない
+        assert_eq!(buf.as_slice(), b"0123456789");
+
+        // ByteReaderが範囲の確認を済ませてからunsafeな読み込みをしていることも、ガードページの前で確かめられる
+        // 末尾に1バイト足りないu32を、確かめずにそのまま読めば落ちる
+        let mut odd = GuardedBuf::new(7).unwrap();
+        odd.as_mut_slice().copy_from_slice(&[1, 0, 0, 0, 2, 0, 0]);
+        assert!(!faults(|| {
+            let mut reader = endian::ByteReader::new(odd.as_slice());
+            assert_eq!(reader.read_u32_le(), Some(1));
+            assert_eq!(reader.read_u32_le(), None);
+        }));
+        // 読んだ値を使わない普通の読み込みは最適化で消されてしまうので、volatileで読む
+        // [u8; 4]は1バイト単位なので、揃っていない番地からでもread_volatileで読める
+        assert!(faults(|| unsafe {
+            odd.as_ptr().add(4).cast::<[u8; 4]>().read_volatile();
+        }));
+
+        let empty = GuardedBuf::new(0).unwrap();
+        assert!(empty.is_empty());
+        assert!(faults(|| unsafe {
+            empty.as_ptr().read_volatile();
+        }));
+    }
+
+    exercise_my_vec();
+    {
+        use myvec::MyVec;
+
+        let mut words: MyVec<String> = MyVec::with_capacity(2);
+        assert_eq!(words.capacity(), 2);
+        words.extend(["b", "d"].iter().map(|s| s.to_string()));
+        words.insert(0, "a".to_string());
+        words.insert(2, "c".to_string());
+        // Derefでスライスのメソッドがそのまま使える
+        words.sort_by(|a, b| b.cmp(a));
+        words[0].push('!');
+        assert_eq!(format!("{:?}", words), r#"["d!", "c", "b", "a"]"#);
+        assert!(words.capacity() >= 4);
+        assert_eq!(words.iter().map(|s| s.len()).sum::<usize>(), 5);
+        for word in &mut words {
+            word.make_ascii_uppercase();
+        }
+        assert_eq!(words, words.clone());
+        assert_eq!(words.into_iter().collect::<Vec<_>>(), ["D!", "C", "B", "A"]);
+
+        let mut empty = MyVec::<u64>::default();
+        assert_eq!(empty.pop(), None);
+        assert!(catch_panic(move || empty.remove(0)).is_err());
+    }
+
+    exercise_array_vec();
+    {
+        use arrayvec::ArrayVec;
+
+        let mut digits: ArrayVec<u8, 8> = ArrayVec::new();
+        for &b in b"31415926" {
+            digits.push(b - b'0');
+        }
+        assert!(digits.try_push(5).is_err());
+        let mut full = digits.clone();
+        assert!(catch_panic(move || full.push(5)).is_err());
+        // Derefでスライスのメソッドがそのまま使える
+        digits.sort_unstable();
+        digits[0] = 0;
+        assert_eq!(digits.iter().sum::<u8>(), 30);
+        assert_eq!(format!("{:?}", digits), "[0, 1, 2, 3, 4, 5, 6, 9]");
+        digits.clear();
+        assert!(digits.is_empty());
+        // ヒープを使わず、要素の配列と長さだけの大きさになる
+        assert_eq!(std::mem::size_of::<ArrayVec<u32, 4>>(), 16 + std::mem::size_of::<usize>());
+    }
+
+    exercise_small_vec();
+    {
+        use smallvec::SmallVec;
+
+        // 自分の中に置いている間は、ムーブするとポインタも変わる
+        let mut words: SmallVec<String, 2> = SmallVec::default();
+        words.push("inline".to_string());
+        let before = words.as_ptr();
+        let words_moved = std::mem::take(&mut words);
+        assert_ne!(before, words_moved.as_ptr());
+        assert_eq!(words_moved.capacity(), 2);
+
+        // ヒープに移した後は、ムーブしてもヒープの要素は動かない
+        let mut words = words_moved;
+        words.extend(["on", "heap"].iter().map(|s| s.to_string()));
+        assert!(words.spilled() && words.capacity() >= 3);
+        let before = words.as_ptr();
+        let words_moved = std::mem::take(&mut words);
+        assert_eq!(before, words_moved.as_ptr());
+        assert_eq!(format!("{:?}", words_moved), r#"["inline", "on", "heap"]"#);
+
+        let mut words = words_moved;
+        words[0].make_ascii_uppercase();
+        assert_eq!(words.into_iter().rev().collect::<Vec<_>>(), ["heap", "on", "INLINE"]);
+
+        // 共用体の分だけ、自分の中の配列とヒープの3つ組の大きい方の場所を取る
+        assert!(std::mem::size_of::<SmallVec<u8, 16>>() <= std::mem::size_of::<usize>() * 4);
+        assert!(std::mem::size_of::<SmallVec<u64, 8>>() >= 64);
+    }
+
+    exercise_sso_string();
+    {
+        use sso::{SsoString, INLINE_CAP};
+        use std::collections::HashMap;
+
+        // Stringと同じ3語の大きさで、23バイトまではヒープを使わない
+        assert_eq!(std::mem::size_of::<SsoString>(), std::mem::size_of::<String>());
+        assert_eq!(INLINE_CAP, std::mem::size_of::<usize>() * 3 - 1);
+        let name = SsoString::from("a".repeat(INLINE_CAP).as_str());
+        assert!(name.is_inline());
+        let mut longer = name.clone();
+        longer.push('!');
+        assert!(!longer.is_inline());
+        assert!(longer > name);
+
+        // Borrow<str>を実装しているので、HashMapのキーにして&strで引ける
+        let mut counts: HashMap<SsoString, usize> = HashMap::new();
+        for word in "the quick brown fox jumps over the lazy dog the end".split(' ') {
+            *counts.entry(SsoString::from(word)).or_insert(0) += 1;
+        }
+        assert_eq!(counts["the"], 3);
+        assert_eq!(counts.get("fox"), Some(&1));
+        // Derefでstrのメソッドがそのまま使える
+        let key = SsoString::from("Key");
+        assert!(key.starts_with('K') && key.to_lowercase() == "key");
+        assert_eq!(format!("{} {:?}", key, key), r#"Key "Key""#);
+        assert_eq!(key, *"Key");
+    }
+
+    exercise_my_rc();
+    {
+        use myrc::MyRc;
+
+        // 同じ値を複数の持ち主で共有する。値は最後の持ち主と一緒にドロップされる
+        let config = MyRc::new(vec!["a", "b"]);
+        let owners: Vec<MyRc<Vec<&str>>> = (0..3).map(|_| config.clone()).collect();
+        assert_eq!(MyRc::strong_count(&config), 4);
+        assert_eq!(owners[2].len(), 2);
+        assert_eq!(format!("{:?}", owners[0]), r#"["a", "b"]"#);
+        drop(owners);
+        assert_eq!(MyRc::strong_count(&config), 1);
+        // MyRc自体はstdのRcと同じく、箱へのポインタ1つ分の大きさになる
+        assert_eq!(std::mem::size_of::<MyRc<u64>>(), std::mem::size_of::<usize>());
+        // let other_thread = std::thread::spawn(move || config.len()); // `NonNull<RcBox<std::vec::Vec<&str>>>` cannot be sent between threads safely
     }
 }
@@ -0,0 +1,444 @@
+// Vecを一から書き直したもの
+// GapBufferと同じく、確保と解放をRawVecに集め、MyVecは先頭からlen個が初期化済みであることだけを管理する
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, realloc};
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::ops::{Bound, Deref, DerefMut, RangeBounds};
+use core::ptr::{self, NonNull};
+
+// 初期化されていないかもしれないTの並び
+// ゼロサイズの型では何も確保せず、容量をusize::MAXにする
+struct RawVec<T> {
+    ptr: NonNull<T>,
+    cap: usize
+}
+
+unsafe impl<T: Send> Send for RawVec<T> {}
+unsafe impl<T: Sync> Sync for RawVec<T> {}
+
+impl<T> RawVec<T> {
+    const IS_ZST: bool = core::mem::size_of::<T>() == 0;
+
+    fn new() -> RawVec<T> {
+        RawVec { ptr: NonNull::dangling(), cap: if Self::IS_ZST { usize::MAX } else { 0 } }
+    }
+
+    fn with_capacity(capacity: usize) -> RawVec<T> {
+        let mut raw = RawVec::new();
+        if capacity > raw.cap {
+            raw.resize(capacity);
+        }
+        raw
+    }
+
+    // 容量がlen + additional以上になるまで倍々に増やす
+    fn reserve(&mut self, len: usize, additional: usize) {
+        let required = len.checked_add(additional).expect("capacity overflow");
+        if required <= self.cap {
+            return;
+        }
+        // 小さな要素では何度も確保し直さないよう、最初から4個分は確保する
+        let doubled = self.cap.saturating_mul(2);
+        self.resize(core::cmp::max(core::cmp::max(required, doubled), 4));
+    }
+
+    // 容量をnew_capにする。ゼロサイズの型では呼ばない
+    // 中身はreallocが移すので、0..min(cap, new_cap)の初期化済みの要素はそのまま残る
+    fn resize(&mut self, new_cap: usize) {
+        debug_assert!(!Self::IS_ZST);
+        // Layout::arrayはバイト数がisize::MAXを超える時もエラーにする
+        let new_layout = Layout::array::<T>(new_cap).expect("capacity overflow");
+        let new_ptr = if new_cap == 0 {
+            unsafe { dealloc(self.ptr.as_ptr().cast(), Layout::array::<T>(self.cap).unwrap()) };
+            NonNull::dangling()
+        } else {
+            let raw = if self.cap == 0 {
+                unsafe { alloc(new_layout) }
+            } else {
+                let old_layout = Layout::array::<T>(self.cap).unwrap();
+                unsafe { realloc(self.ptr.as_ptr().cast(), old_layout, new_layout.size()) }
+            };
+            match NonNull::new(raw.cast::<T>()) {
+                Some(ptr) => {
+                    // アロケータはLayoutのアライメントに揃えた領域を返さなければならない
+                    debug_assert!(super::align::is_ptr_aligned_to(ptr.as_ptr(), new_layout.align()));
+                    ptr
+                }
+                None => handle_alloc_error(new_layout)
+            }
+        };
+        self.ptr = new_ptr;
+        self.cap = new_cap;
+    }
+}
+
+// 領域を解放するだけで、中身はドロップしない
+impl<T> Drop for RawVec<T> {
+    fn drop(&mut self) {
+        if !Self::IS_ZST && self.cap != 0 {
+            unsafe { dealloc(self.ptr.as_ptr().cast(), Layout::array::<T>(self.cap).unwrap()) };
+        }
+    }
+}
+
+// 不変条件: buf.ptrの先頭からlen個の要素が初期化済みで、len <= buf.cap
+// 要素へのポインタは全てbuf.ptrから作り、初期化されていない部分を含む参照は作らない
+pub struct MyVec<T> {
+    buf: RawVec<T>,
+    len: usize,
+    // Tの値を所有していることをドロップチェッカーに教える
+    owns: PhantomData<T>
+}
+
+impl<T> MyVec<T> {
+    pub fn new() -> MyVec<T> {
+        MyVec { buf: RawVec::new(), len: 0, owns: PhantomData }
+    }
+
+    pub fn with_capacity(capacity: usize) -> MyVec<T> {
+        MyVec { buf: RawVec::with_capacity(capacity), len: 0, owns: PhantomData }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.cap
+    }
+
+    pub fn as_ptr(&self) -> *const T {
+        self.buf.ptr.as_ptr()
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.buf.ptr.as_ptr()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.buf.reserve(self.len, additional);
+    }
+
+    // 容量を要素数まで縮める
+    pub fn shrink_to_fit(&mut self) {
+        if !RawVec::<T>::IS_ZST && self.buf.cap > self.len {
+            self.buf.resize(self.len);
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.len == self.buf.cap {
+            self.buf.reserve(self.len, 1);
+        }
+        unsafe { self.as_mut_ptr().add(self.len).write(value) };
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        // 先にlenを減らしてから読み出すので、読み出した要素はもうMyVecの持ち物ではない
+        self.len -= 1;
+        Some(unsafe { self.as_ptr().add(self.len).read() })
+    }
+
+    // index以降の要素を1つずつ後ろにずらしてvalueを入れる
+    // indexが要素数を超えていればpanicを起こす
+    pub fn insert(&mut self, index: usize, value: T) {
+        assert!(index <= self.len, "insertion index {} is out of bounds for length {}", index, self.len);
+        if self.len == self.buf.cap {
+            self.buf.reserve(self.len, 1);
+        }
+        unsafe {
+            let at = self.as_mut_ptr().add(index);
+            ptr::copy(at, at.add(1), self.len - index);
+            at.write(value);
+        }
+        self.len += 1;
+    }
+
+    // indexの要素を取り出し、後ろの要素を1つずつ前に詰める
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "removal index {} is out of bounds for length {}", index, self.len);
+        unsafe {
+            self.len -= 1;
+            let at = self.as_mut_ptr().add(index);
+            let value = at.read();
+            ptr::copy(at.add(1), at, self.len - index);
+            value
+        }
+    }
+
+    // indexの要素を取り出し、空いた場所に最後の要素を移す。順序は保たないがO(1)で済む
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "swap_remove index {} is out of bounds for length {}", index, self.len);
+        unsafe {
+            self.len -= 1;
+            let base = self.as_mut_ptr();
+            let value = base.add(index).read();
+            ptr::copy(base.add(self.len), base.add(index), 1);
+            value
+        }
+    }
+
+    // 先頭からlen個だけ残し、残りをドロップする
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail = ptr::slice_from_raw_parts_mut(unsafe { self.as_mut_ptr().add(len) }, self.len - len);
+        // 要素のDropがpanicしても二重にドロップしないよう、先にlenを縮めておく
+        self.len = len;
+        unsafe { ptr::drop_in_place(tail) };
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    // rangeの要素を取り除き、それを先頭から順に返すイテレータを返す
+    // Drainがドロップされる時に、取り出されなかった要素をドロップし、後ろの要素を前に詰める
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, T> {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n + 1,
+            Bound::Unbounded => 0
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n + 1,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.len
+        };
+        assert!(start <= end && end <= self.len, "drain range {}..{} is out of bounds for length {}", start, end, self.len);
+        let tail_len = self.len - end;
+        // Drainをforgetされても範囲の要素を二重にドロップしないよう、先頭の要素だけを持っていることにしておく
+        // その場合は範囲と後ろの要素がリークするだけで済む
+        self.len = start;
+        Drain { next: start, end, tail_start: end, tail_len, vec: self }
+    }
+}
+
+impl<T> Default for MyVec<T> {
+    fn default() -> MyVec<T> {
+        MyVec::new()
+    }
+}
+
+impl<T> Drop for MyVec<T> {
+    fn drop(&mut self) {
+        // 領域の解放はこの後でbufのDropが行う
+        unsafe { ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.as_mut_ptr(), self.len)) };
+    }
+}
+
+impl<T> Deref for MyVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { core::slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for MyVec<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { core::slice::from_raw_parts_mut(self.as_mut_ptr(), self.len) }
+    }
+}
+
+impl<T: Clone> Clone for MyVec<T> {
+    fn clone(&self) -> MyVec<T> {
+        let mut clone = MyVec::with_capacity(self.len);
+        // cloneがpanicしても、それまでに複製した要素はcloneのDropがドロップする
+        for element in self.iter() {
+            clone.push(element.clone());
+        }
+        clone
+    }
+}
+
+impl<T: core::fmt::Debug> core::fmt::Debug for MyVec<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for MyVec<T> {
+    fn eq(&self, other: &MyVec<T>) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq> Eq for MyVec<T> {}
+
+impl<T: PartialEq> PartialEq<[T]> for MyVec<T> {
+    fn eq(&self, other: &[T]) -> bool {
+        **self == *other
+    }
+}
+
+impl<T> Extend<T> for MyVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for element in iter {
+            self.push(element);
+        }
+    }
+}
+
+impl<T> core::iter::FromIterator<T> for MyVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> MyVec<T> {
+        let mut vec = MyVec::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+// 要素を先頭から順にムーブして返すイテレータ
+// bufのnext..endにまだ返していない要素が残っている
+pub struct IntoIter<T> {
+    buf: RawVec<T>,
+    next: usize,
+    end: usize,
+    owns: PhantomData<T>
+}
+
+impl<T> IntoIterator for MyVec<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        let vec = core::mem::ManuallyDrop::new(self);
+        // vecのDropは呼ばれないので、bufを読み出して持ち主を移してよい
+        let buf = unsafe { ptr::read(&vec.buf) };
+        IntoIter { buf, next: 0, end: vec.len, owns: PhantomData }
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.next == self.end {
+            return None;
+        }
+        let element = unsafe { self.buf.ptr.as_ptr().add(self.next).read() };
+        self.next += 1;
+        Some(element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.next;
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.next == self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(unsafe { self.buf.ptr.as_ptr().add(self.end).read() })
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+// まだ返していない要素をドロップする。領域はこの後bufのDropが解放する
+impl<T> Drop for IntoIter<T> {
+    fn drop(&mut self) {
+        let remaining = ptr::slice_from_raw_parts_mut(unsafe { self.buf.ptr.as_ptr().add(self.next) },
+                                                      self.end - self.next);
+        self.next = self.end;
+        unsafe { ptr::drop_in_place(remaining) };
+    }
+}
+
+impl<'a, T> IntoIterator for &'a MyVec<T> {
+    type Item = &'a T;
+    type IntoIter = core::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> core::slice::Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut MyVec<T> {
+    type Item = &'a mut T;
+    type IntoIter = core::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> core::slice::IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+// MyVec::drainが返すイテレータ
+// vecのnext..endにまだ返していない要素が、tail_start..tail_start + tail_lenに後ろの要素が残っている
+pub struct Drain<'a, T> {
+    next: usize,
+    end: usize,
+    tail_start: usize,
+    tail_len: usize,
+    vec: &'a mut MyVec<T>
+}
+
+impl<'a, T> Iterator for Drain<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.next == self.end {
+            return None;
+        }
+        let element = unsafe { self.vec.as_ptr().add(self.next).read() };
+        self.next += 1;
+        Some(element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.next;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Drain<'a, T> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.next == self.end {
+            return None;
+        }
+        self.end -= 1;
+        Some(unsafe { self.vec.as_ptr().add(self.end).read() })
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Drain<'a, T> {}
+
+impl<'a, T> Drop for Drain<'a, T> {
+    fn drop(&mut self) {
+        // 要素のDropがpanicしても後ろの要素を詰め直せるよう、詰め直しはガードのDropで行う
+        struct MoveTail<'b, 'a, T>(&'b mut Drain<'a, T>);
+
+        impl<'b, 'a, T> Drop for MoveTail<'b, 'a, T> {
+            fn drop(&mut self) {
+                let drain = &mut *self.0;
+                let start = drain.vec.len;
+                unsafe {
+                    let base = drain.vec.as_mut_ptr();
+                    ptr::copy(base.add(drain.tail_start), base.add(start), drain.tail_len);
+                }
+                drain.vec.len = start + drain.tail_len;
+            }
+        }
+
+        let remaining = ptr::slice_from_raw_parts_mut(unsafe { self.vec.as_mut_ptr().add(self.next) },
+                                                      self.end - self.next);
+        self.next = self.end;
+        let _guard = MoveTail(self);
+        unsafe { ptr::drop_in_place(remaining) };
+    }
+}
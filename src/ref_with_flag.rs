@@ -0,0 +1,565 @@
+// 参照やポインタの使われていないビットにフラグやタグを詰める型
+// tests/ui/のコンパイルできないことを確かめるテストからも読み込むので、main.rsの他のモジュールには依存しない
+use std::marker::PhantomData;
+use std::mem::{align_of, align_of_val};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+// NonNullのアドレスをfで書き換える。参照から作ったポインタのタグを付け外しするのに使う
+// fがゼロを返すとpanicを起こす
+fn map_nonnull_addr<T: ?Sized, F: FnOnce(usize) -> usize>(ptr: NonNull<T>, f: F) -> NonNull<T> {
+    NonNull::new(ptr.as_ptr().map_addr(f)).unwrap()
+}
+
+// 古典的なbit操作をRustで安全にラップした型
+// 型Tは少なくとも2バイト単位でアライメントされているものでなければならない
+//
+// ポインタをusizeにして持つと、整数からポインタに戻す時にどの割り当てを指していたかという出自（provenance）が失われる
+// そこでフラグを付けたポインタのまま持ち、map_addrでアドレスだけを書き換えて出自を保つ（strict provenance）
+// 参照はヌルにならず、フラグを付けてもヌルにならないのでNonNullで持つ。Option<RefWithFlag<T>>も1語に収まる
+pub struct RefWithFlag<'a, T:'a> {
+    ptr_and_bit: NonNull<T>,
+    behaves_like: PhantomData<&'a T>
+}
+
+// ptr_and_bitはNonNullなので、自動実装に任せるとSendもSyncも実装されない
+// 中身は&'a Tそのものなので&'a Tと同じ境界が正しく、ここでは明示的に書いておく
+// &'a Tを別のスレッドに送ったり共有したりすると、複数のスレッドから同時にTを読めるのでT: Syncが必要
+unsafe impl<'a, T: Sync> Send for RefWithFlag<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for RefWithFlag<'a, T> {}
+
+impl<'a, T:'a> RefWithFlag<'a, T> {
+    // 最下位ビットが必ずゼロになるアライメントかを、型ごとにコンパイル時に検証する
+    // newで参照するので、1バイト単位の型でnewを呼ぶコードはビルドできない
+    const ALIGNED: () = assert!(align_of::<T>().is_multiple_of(2), "alignment of T is too small for a flag bit");
+
+    pub fn new(ptr: &'a T, flag: bool) -> RefWithFlag<'a, T> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::ALIGNED; // 最下位ビットがゼロであるか検証してからrawポインタに変換
+        RefWithFlag {
+            // 参照->rawポインタに変換し、アドレスの最下位ビットにフラグを入れる
+            ptr_and_bit: map_nonnull_addr(NonNull::from(ptr), |addr| addr | flag as usize),
+            // メモリを消費しないゼロサイズの型（生存期間をどう扱うかRustコンパイラに教えるために必要なフィールドで、これが無いとコンパイルできない）
+            behaves_like: PhantomData
+        }
+    }
+
+    pub fn get_ref(&self) -> &'a T {
+        unsafe {
+            let ptr = self.ptr_and_bit.as_ptr().map_addr(|addr| addr & !1);
+            &*ptr
+        }
+    }
+
+    pub fn get_flag(&self) -> bool {
+        // 最下位ビットをマスクしてゼロかを返す
+        self.ptr_and_bit.as_ptr().addr() & 1 != 0
+    }
+
+    // 書き換えるのは最下位ビットだけで、参照先のTには触れない
+    pub fn set_flag(&mut self, flag: bool) {
+        self.ptr_and_bit = map_nonnull_addr(self.ptr_and_bit, |addr| (addr & !1) | flag as usize);
+    }
+
+    pub fn toggle_flag(&mut self) {
+        self.ptr_and_bit = map_nonnull_addr(self.ptr_and_bit, |addr| addr ^ 1);
+    }
+
+    // フラグだけを差し替えた値を返す
+    pub fn with_flag(mut self, flag: bool) -> RefWithFlag<'a, T> {
+        self.set_flag(flag);
+        self
+    }
+}
+
+// get_refと同じくフラグを落として参照に戻すので、flagged.len()のようにTのメソッドを直接呼べる
+impl<'a, T: 'a> std::ops::Deref for RefWithFlag<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.get_ref()
+    }
+}
+
+impl<'a, T: 'a> AsRef<T> for RefWithFlag<'a, T> {
+    fn as_ref(&self) -> &T {
+        self.get_ref()
+    }
+}
+
+// 中身は&'a Tとboolなので自由に複製してよい
+// deriveするとT: Cloneなどの境界が付いてしまうので、手で実装する
+impl<'a, T: 'a> Clone for RefWithFlag<'a, T> {
+    fn clone(&self) -> RefWithFlag<'a, T> {
+        *self
+    }
+}
+
+impl<'a, T: 'a> Copy for RefWithFlag<'a, T> {}
+
+// 詰めた整数ではなく、参照先とフラグを表示する
+impl<'a, T: std::fmt::Debug + 'a> std::fmt::Debug for RefWithFlag<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("RefWithFlag")
+            .field("ref", self.get_ref())
+            .field("flag", &self.get_flag())
+            .finish()
+    }
+}
+
+// フラグ付きのアドレスを比べるので、同じ場所を指していてフラグも同じ時だけ等しい
+// 参照先の値が等しいかどうかは、*a == *bのように参照外ししてから比べる
+impl<'a, T: 'a> PartialEq for RefWithFlag<'a, T> {
+    fn eq(&self, other: &RefWithFlag<'a, T>) -> bool {
+        self.ptr_and_bit == other.ptr_and_bit
+    }
+}
+
+impl<'a, T: 'a> Eq for RefWithFlag<'a, T> {}
+
+impl<'a, T: 'a> std::hash::Hash for RefWithFlag<'a, T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.ptr_and_bit.hash(state);
+    }
+}
+
+// RefWithFlagを&[T]や&dyn Traitのようなファットポインタに広げた型
+// ファットポインタはデータのアドレスとメタデータ（スライスなら長さ、トレイトオブジェクトならvtable）の2語でできている
+// map_addrはデータのアドレスの語だけを書き換えてメタデータの語はそのまま残すので、
+// データの語の最下位ビットにフラグを入れ、フラグを落とせば元の参照を組み立て直せる
+// 参照先のアライメントは型だけでは決まらないので、コンパイル時ではなくnewで検証する
+pub struct FatRefWithFlag<'a, T: ?Sized + 'a> {
+    ptr_and_bit: NonNull<T>,
+    behaves_like: PhantomData<&'a T>
+}
+
+unsafe impl<'a, T: ?Sized + Sync> Send for FatRefWithFlag<'a, T> {}
+unsafe impl<'a, T: ?Sized + Sync> Sync for FatRefWithFlag<'a, T> {}
+
+impl<'a, T: ?Sized + 'a> Clone for FatRefWithFlag<'a, T> {
+    fn clone(&self) -> FatRefWithFlag<'a, T> {
+        *self
+    }
+}
+
+impl<'a, T: ?Sized + 'a> Copy for FatRefWithFlag<'a, T> {}
+
+impl<'a, T: ?Sized + 'a> FatRefWithFlag<'a, T> {
+    // 参照先が2バイト単位でなければpanicを起こす
+    // [u8]や、1バイト単位の型を指すdyn Traitにはフラグを付けられない
+    pub fn new(ptr: &'a T, flag: bool) -> FatRefWithFlag<'a, T> {
+        assert!(align_of_val(ptr) >= 2, "alignment of the referent is too small for a flag bit");
+        FatRefWithFlag {
+            ptr_and_bit: map_nonnull_addr(NonNull::from(ptr), |addr| addr | flag as usize),
+            behaves_like: PhantomData
+        }
+    }
+
+    pub fn get_ref(&self) -> &'a T {
+        unsafe { &*self.ptr_and_bit.as_ptr().map_addr(|addr| addr & !1) }
+    }
+
+    pub fn get_flag(&self) -> bool {
+        self.ptr_and_bit.as_ptr().addr() & 1 != 0
+    }
+
+    pub fn set_flag(&mut self, flag: bool) {
+        self.ptr_and_bit = map_nonnull_addr(self.ptr_and_bit, |addr| (addr & !1) | flag as usize);
+    }
+}
+
+impl<'a, T: ?Sized + 'a> std::ops::Deref for FatRefWithFlag<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.get_ref()
+    }
+}
+
+// RefWithFlagの&'a mut T版
+// 型Tは少なくとも2バイト単位でアライメントされているものでなければならない
+//
+// フラグを落としたポインタを&mut Tにしてよいのは、次の理由による
+// ・newで受け取った&'a mut Tの借用を'aの間ずっと持ち続けるので、その間Tに触れられるのはこの値だけ
+// ・PhantomData<&'a mut T>を持つのでCopyにもCloneにもならず、同じポインタを持つ値が2つになることはない
+// ・get_mutは&mut selfを借用するので、返した&mut Tが生きている間はget_refもget_mutも呼べない
+// ・map_addrはアドレスを変えても出自を引き継ぐので、フラグを落としたポインタで元の借用の範囲を読み書きできる
+pub struct RefWithFlagMut<'a, T: 'a> {
+    ptr_and_bit: NonNull<T>,
+    behaves_like: PhantomData<&'a mut T>
+}
+
+// 中身は&'a mut Tそのものなので、&'a mut Tと同じ境界にする
+// &'a mut Tを送るとTを別のスレッドで書き換えられるのでT: Send、共有すると&Tが読めるのでT: Syncが必要
+unsafe impl<'a, T: Send> Send for RefWithFlagMut<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for RefWithFlagMut<'a, T> {}
+
+impl<'a, T: 'a> RefWithFlagMut<'a, T> {
+    const ALIGNED: () = assert!(align_of::<T>().is_multiple_of(2), "alignment of T is too small for a flag bit");
+
+    pub fn new(ptr: &'a mut T, flag: bool) -> RefWithFlagMut<'a, T> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::ALIGNED;
+        RefWithFlagMut {
+            ptr_and_bit: map_nonnull_addr(NonNull::from(ptr), |addr| addr | flag as usize),
+            behaves_like: PhantomData
+        }
+    }
+
+    fn ptr(&self) -> *mut T {
+        self.ptr_and_bit.as_ptr().map_addr(|addr| addr & !1)
+    }
+
+    pub fn get_ref(&self) -> &T {
+        unsafe { &*self.ptr() }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.ptr() }
+    }
+
+    // 借用を'aの間まるごと返す。selfは消費されるので、以降は他に&mut Tを作れる値が残らない
+    pub fn into_mut(self) -> &'a mut T {
+        unsafe { &mut *self.ptr() }
+    }
+
+    pub fn get_flag(&self) -> bool {
+        self.ptr_and_bit.as_ptr().addr() & 1 != 0
+    }
+
+    // ポインタの部分はそのままで、最下位ビットだけを書き換える
+    pub fn set_flag(&mut self, flag: bool) {
+        self.ptr_and_bit = map_nonnull_addr(self.ptr_and_bit, |addr| (addr & !1) | flag as usize);
+    }
+}
+
+// RefWithFlagの1ビットのフラグを、下位BITSビットのタグに広げた型
+// 4バイト単位なら2ビット、8バイト単位なら3ビットまで使える
+pub struct RefWithTag<'a, T: 'a, const BITS: u32> {
+    ptr_and_tag: NonNull<T>,
+    behaves_like: PhantomData<&'a T>
+}
+
+unsafe impl<'a, T: Sync, const BITS: u32> Send for RefWithTag<'a, T, BITS> {}
+unsafe impl<'a, T: Sync, const BITS: u32> Sync for RefWithTag<'a, T, BITS> {}
+
+impl<'a, T: 'a, const BITS: u32> RefWithTag<'a, T, BITS> {
+    // タグに使う下位ビット
+    const MASK: usize = (1 << BITS) - 1;
+
+    // アライメントが足りなければ、この定数を評価する時にコンパイルエラーになる
+    // newで参照するので、足りない型とBITSの組でnewを呼ぶコードはコンパイルできない
+    const ALIGNED: () = assert!(align_of::<T>() >= 1 << BITS, "alignment of T is too small for BITS tag bits");
+
+    // タグがBITSビットに収まらなければpanicを起こす
+    pub fn new(ptr: &'a T, tag: usize) -> RefWithTag<'a, T, BITS> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::ALIGNED;
+        assert!(tag <= Self::MASK, "tag {} does not fit in {} bits", tag, BITS);
+        RefWithTag {
+            ptr_and_tag: map_nonnull_addr(NonNull::from(ptr), |addr| addr | tag),
+            behaves_like: PhantomData
+        }
+    }
+
+    pub fn get_ref(&self) -> &'a T {
+        unsafe { &*self.ptr_and_tag.as_ptr().map_addr(|addr| addr & !Self::MASK) }
+    }
+
+    pub fn tag(&self) -> usize {
+        self.ptr_and_tag.as_ptr().addr() & Self::MASK
+    }
+
+    // タグがBITSビットに収まらなければpanicを起こす
+    pub fn set_tag(&mut self, tag: usize) {
+        assert!(tag <= Self::MASK, "tag {} does not fit in {} bits", tag, BITS);
+        self.ptr_and_tag = map_nonnull_addr(self.ptr_and_tag, |addr| (addr & !Self::MASK) | tag);
+    }
+
+    // 今のタグをfで変換したタグを持つ値を返す
+    pub fn map_tag<F: FnOnce(usize) -> usize>(mut self, f: F) -> RefWithTag<'a, T, BITS> {
+        let tag = f(self.tag());
+        self.set_tag(tag);
+        self
+    }
+}
+
+// Boxのヒープポインタの下位BITSビットにタグを持たせた、値を所有する型
+// RefWithFlagと違ってドロップ時に値を解放する責任があるので、元のBoxを組み立て直して手放す
+// Boxのポインタはヌルにならないのでタグを付けてもヌルにならず、Option<TaggedBox<T>>も1語に収まる
+pub struct TaggedBox<T, const BITS: u32 = 1> {
+    ptr_and_tag: NonNull<T>,
+    // Box<T>を持っているのと同じく、Tの値を所有していることをドロップチェッカーに教える
+    owns: PhantomData<Box<T>>
+}
+
+unsafe impl<T: Send, const BITS: u32> Send for TaggedBox<T, BITS> {}
+unsafe impl<T: Sync, const BITS: u32> Sync for TaggedBox<T, BITS> {}
+
+impl<T, const BITS: u32> TaggedBox<T, BITS> {
+    const MASK: usize = (1 << BITS) - 1;
+
+    const ALIGNED: () = assert!(align_of::<T>() >= 1 << BITS, "alignment of T is too small for BITS tag bits");
+
+    // タグがBITSビットに収まらなければpanicを起こす
+    pub fn new(value: T, tag: usize) -> TaggedBox<T, BITS> {
+        TaggedBox::from_box(Box::new(value), tag)
+    }
+
+    pub fn from_box(boxed: Box<T>, tag: usize) -> TaggedBox<T, BITS> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::ALIGNED;
+        assert!(tag <= Self::MASK, "tag {} does not fit in {} bits", tag, BITS);
+        // Tのサイズが0でも、Box::into_rawはアライメントの揃ったポインタを返す
+        TaggedBox {
+            ptr_and_tag: map_nonnull_addr(NonNull::from(Box::leak(boxed)), |addr| addr | tag),
+            owns: PhantomData
+        }
+    }
+
+    fn ptr(&self) -> *mut T {
+        self.ptr_and_tag.as_ptr().map_addr(|addr| addr & !Self::MASK)
+    }
+
+    pub fn get_ref(&self) -> &T {
+        unsafe { &*self.ptr() }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.ptr() }
+    }
+
+    pub fn tag(&self) -> usize {
+        self.ptr_and_tag.as_ptr().addr() & Self::MASK
+    }
+
+    // タグがBITSビットに収まらなければpanicを起こす
+    pub fn set_tag(&mut self, tag: usize) {
+        assert!(tag <= Self::MASK, "tag {} does not fit in {} bits", tag, BITS);
+        self.ptr_and_tag = map_nonnull_addr(self.ptr_and_tag, |addr| (addr & !Self::MASK) | tag);
+    }
+
+    // タグを外して元のBoxとタグに分ける
+    // 値の所有権はBoxに移るので、selfのDropは走らせない
+    pub fn into_box(self) -> (Box<T>, usize) {
+        let this = std::mem::ManuallyDrop::new(self);
+        (unsafe { Box::from_raw(this.ptr()) }, this.tag())
+    }
+
+    pub fn into_inner(self) -> T {
+        *self.into_box().0
+    }
+}
+
+impl<T, const BITS: u32> Drop for TaggedBox<T, BITS> {
+    fn drop(&mut self) {
+        // タグを落としたポインタはBox::into_rawが返したものそのものなので、Boxに戻して解放できる
+        unsafe {
+            drop(Box::from_raw(self.ptr()));
+        }
+    }
+}
+
+// Option<&T>とboolを1語に収めた型
+// &Tは決してヌルにならないので、ヌルの番地をNoneに使える。Option<&T>が1語に収まるのと同じ理屈
+// option_to_rawはNoneをヌルに変換したが、こちらはヌルからNoneに戻す向きを扱う
+pub struct PackedOption<'a, T: 'a> {
+    ptr_and_bit: *const T,
+    behaves_like: PhantomData<Option<&'a T>>
+}
+
+unsafe impl<'a, T: Sync> Send for PackedOption<'a, T> {}
+unsafe impl<'a, T: Sync> Sync for PackedOption<'a, T> {}
+
+impl<'a, T: 'a> PackedOption<'a, T> {
+    const ALIGNED: () = assert!(align_of::<T>() >= 2, "alignment of T is too small for a flag bit");
+
+    pub fn new(opt: Option<&'a T>, flag: bool) -> PackedOption<'a, T> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::ALIGNED;
+        let ptr = match opt {
+            None => std::ptr::null(),
+            Some(r) => r as *const T
+        };
+        PackedOption {
+            ptr_and_bit: ptr.map_addr(|addr| addr | flag as usize),
+            behaves_like: PhantomData
+        }
+    }
+
+    // フラグを落としてヌルならNone、そうでなければnewで受け取った参照そのもの
+    pub fn get(&self) -> Option<&'a T> {
+        unsafe { self.ptr_and_bit.map_addr(|addr| addr & !1).as_ref() }
+    }
+
+    pub fn is_some(&self) -> bool {
+        self.ptr_and_bit.addr() & !1 != 0
+    }
+
+    pub fn get_flag(&self) -> bool {
+        self.ptr_and_bit.addr() & 1 != 0
+    }
+
+    pub fn set_flag(&mut self, flag: bool) {
+        self.ptr_and_bit = self.ptr_and_bit.map_addr(|addr| (addr & !1) | flag as usize);
+    }
+}
+
+// &'a Aか&'a Bのどちらかを1語で持つ型。最下位ビットが0ならA、1ならB
+// enumで書くと判別子の分だけ大きくなるが、こちらは参照1つ分で済む
+// どちらの型のポインタもcastでNonNull<u8>にして持つ。castはアドレスも出自も変えない
+pub struct PtrEither<'a, A: 'a, B: 'a> {
+    ptr_and_bit: NonNull<u8>,
+    behaves_like: PhantomData<(&'a A, &'a B)>
+}
+
+unsafe impl<'a, A: Sync, B: Sync> Send for PtrEither<'a, A, B> {}
+unsafe impl<'a, A: Sync, B: Sync> Sync for PtrEither<'a, A, B> {}
+
+impl<'a, A: 'a, B: 'a> PtrEither<'a, A, B> {
+    // どちらを持つ時も最下位ビットを使うので、両方の型が2バイト単位でなければならない
+    const ALIGNED: () = assert!(align_of::<A>() >= 2 && align_of::<B>() >= 2,
+                                "alignment of A and B must be at least 2");
+
+    pub fn new_a(a: &'a A) -> PtrEither<'a, A, B> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::ALIGNED;
+        PtrEither { ptr_and_bit: NonNull::from(a).cast(), behaves_like: PhantomData }
+    }
+
+    pub fn new_b(b: &'a B) -> PtrEither<'a, A, B> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::ALIGNED;
+        PtrEither { ptr_and_bit: map_nonnull_addr(NonNull::from(b).cast(), |addr| addr | 1), behaves_like: PhantomData }
+    }
+
+    pub fn is_a(&self) -> bool {
+        self.ptr_and_bit.as_ptr().addr() & 1 == 0
+    }
+
+    pub fn is_b(&self) -> bool {
+        !self.is_a()
+    }
+
+    pub fn as_a(&self) -> Option<&'a A> {
+        self.fold(Some, |_| None)
+    }
+
+    pub fn as_b(&self) -> Option<&'a B> {
+        self.fold(|_| None, Some)
+    }
+
+    // matchの代わりに、持っている方の参照を対応する関数に渡して結果を返す
+    pub fn fold<R, FA, FB>(&self, fa: FA, fb: FB) -> R
+        where FA: FnOnce(&'a A) -> R, FB: FnOnce(&'a B) -> R
+    {
+        let ptr = self.ptr_and_bit.as_ptr().map_addr(|addr| addr & !1);
+        if self.is_a() {
+            fa(unsafe { &*ptr.cast::<A>() })
+        } else {
+            fb(unsafe { &*ptr.cast::<B>() })
+        }
+    }
+}
+
+// アドレスの上位16ビットに16ビットのタグを持たせた生ポインタ
+// x86-64とAArch64の仮想アドレスは下位48ビットだけが意味を持ち、上位16ビットは47ビット目の符号拡張（正準形）になる
+// 下位ビットを使う型と違ってアライメントに頼らないので、u8を指すポインタにもタグを付けられる
+// ハードウェアがそのまま参照外しできる形ではないので、使う前にptrで正準形に戻す
+#[cfg(all(target_pointer_width = "64", any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub struct HighTagPtr<T> {
+    ptr_and_tag: *mut T,
+    behaves_like: PhantomData<*mut T>
+}
+
+#[cfg(all(target_pointer_width = "64", any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl<T> Clone for HighTagPtr<T> {
+    fn clone(&self) -> HighTagPtr<T> {
+        *self
+    }
+}
+
+#[cfg(all(target_pointer_width = "64", any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl<T> Copy for HighTagPtr<T> {}
+
+#[cfg(all(target_pointer_width = "64", any(target_arch = "x86_64", target_arch = "aarch64")))]
+impl<T> HighTagPtr<T> {
+    const TAG_SHIFT: u32 = 48;
+    const ADDRESS_MASK: usize = (1 << Self::TAG_SHIFT) - 1;
+
+    // 下位48ビットを符号拡張して正準形のアドレスに戻す
+    fn canonical(address: usize) -> usize {
+        (((address << 16) as isize) >> 16) as usize
+    }
+
+    // ptrが正準形でなければ（上位16ビットに既に何か入っていれば）panicを起こす
+    pub fn new(ptr: *mut T, tag: u16) -> HighTagPtr<T> {
+        assert!(Self::canonical(ptr.addr()) == ptr.addr(), "pointer {:p} is not a canonical address", ptr);
+        HighTagPtr {
+            ptr_and_tag: ptr.map_addr(|addr| (addr & Self::ADDRESS_MASK) | (tag as usize) << Self::TAG_SHIFT),
+            behaves_like: PhantomData
+        }
+    }
+
+    pub fn ptr(self) -> *mut T {
+        self.ptr_and_tag.map_addr(|addr| Self::canonical(addr & Self::ADDRESS_MASK))
+    }
+
+    pub fn tag(self) -> u16 {
+        (self.ptr_and_tag.addr() >> Self::TAG_SHIFT) as u16
+    }
+
+    pub fn set_tag(&mut self, tag: u16) {
+        self.ptr_and_tag = self.ptr_and_tag.map_addr(|addr| (addr & Self::ADDRESS_MASK) | (tag as usize) << Self::TAG_SHIFT);
+    }
+}
+
+// ポインタと最下位ビットのフラグの組を1語のAtomicPtrに収め、組ごと不可分に読み書きする型
+// ロックフリーのリストで、ノードを論理的に削除した印をnextポインタに付けるのに使う
+// 中身は生ポインタなので、指す先が生きているかどうかは使う側が保証する
+pub struct AtomicTaggedPtr<T> {
+    ptr_and_bit: AtomicPtr<T>,
+    behaves_like: PhantomData<*mut T>
+}
+
+// 持っているのはアドレスの値だけで、このモジュールの中では参照外しをしない
+unsafe impl<T> Send for AtomicTaggedPtr<T> {}
+unsafe impl<T> Sync for AtomicTaggedPtr<T> {}
+
+impl<T> AtomicTaggedPtr<T> {
+    // ptrが2バイト単位に揃っていなければpanicを起こす
+    pub fn new(ptr: *mut T, flag: bool) -> AtomicTaggedPtr<T> {
+        AtomicTaggedPtr {
+            ptr_and_bit: AtomicPtr::new(Self::pack(ptr, flag)),
+            behaves_like: PhantomData
+        }
+    }
+
+    fn pack(ptr: *mut T, flag: bool) -> *mut T {
+        assert!(ptr.addr() & 1 == 0, "pointer {:p} is not aligned to 2 bytes", ptr);
+        ptr.map_addr(|addr| addr | flag as usize)
+    }
+
+    fn unpack(ptr_and_bit: *mut T) -> (*mut T, bool) {
+        (ptr_and_bit.map_addr(|addr| addr & !1), ptr_and_bit.addr() & 1 != 0)
+    }
+
+    pub fn load(&self, order: Ordering) -> (*mut T, bool) {
+        Self::unpack(self.ptr_and_bit.load(order))
+    }
+
+    pub fn store(&self, ptr: *mut T, flag: bool, order: Ordering) {
+        self.ptr_and_bit.store(Self::pack(ptr, flag), order);
+    }
+
+    // 今の組がcurrentと等しければnewに置き換える
+    // 成功すれば元の組をOkで、失敗すれば今の組をErrで返す
+    pub fn compare_exchange(&self, current: (*mut T, bool), new: (*mut T, bool),
+                            success: Ordering, failure: Ordering)
+                            -> Result<(*mut T, bool), (*mut T, bool)> {
+        self.ptr_and_bit
+            .compare_exchange(Self::pack(current.0, current.1), Self::pack(new.0, new.1), success, failure)
+            .map(Self::unpack)
+            .map_err(Self::unpack)
+    }
+}
@@ -0,0 +1,251 @@
+// 短い文字列をヒープを使わずに自分の中に置くString
+// 3語（64ビットなら24バイト）のうち最後の1バイトを長さとタグに使い、残りの23バイトに文字列を置く
+// それより長くなったら、Stringと同じポインタ・長さ・容量の3つ組でヒープに置く
+use alloc::string::String;
+use core::fmt;
+use core::mem::{size_of, ManuallyDrop};
+use core::ptr::NonNull;
+
+const SIZE: usize = size_of::<usize>() * 3;
+
+// 自分の中に置ける最大のバイト数
+pub const INLINE_CAP: usize = SIZE - 1;
+
+// 自分の中に置いている時、最後のバイトは最上位ビットを立てて下位ビットに長さを入れる
+const INLINE_TAG: u8 = 0x80;
+
+// String::from_raw_partsで組み立て直せる3つ組
+// capはリトルエンディアンのバイト列で持つので、どのターゲットでも最後のバイトが容量の最上位バイトになる
+// 容量はisize::MAXを超えないので、このバイトの最上位ビットは必ず0になり、INLINE_TAGと見分けられる
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Heap {
+    ptr: NonNull<u8>,
+    len: usize,
+    cap: [u8; size_of::<usize>()]
+}
+
+// どちらが有効かは最後のバイトで見分ける
+#[repr(C)]
+union Repr {
+    inline: [u8; SIZE],
+    heap: Heap
+}
+
+// 不変条件: 最後のバイトの最上位ビットが立っていればinlineの先頭から下位ビットの長さ分が、
+// 立っていなければheapの3つ組が、それぞれUTF-8として正しい文字列になっている
+pub struct SsoString {
+    repr: Repr
+}
+
+// Stringと同じく、ヒープの領域を1つ所有するだけなので他のスレッドに渡せる
+unsafe impl Send for SsoString {}
+unsafe impl Sync for SsoString {}
+
+impl SsoString {
+    pub const fn new() -> SsoString {
+        let mut inline = [0; SIZE];
+        inline[SIZE - 1] = INLINE_TAG;
+        SsoString { repr: Repr { inline } }
+    }
+
+    pub fn is_inline(&self) -> bool {
+        // 最後のバイトはどちらのフィールドでも初期化済みで、ポインタのバイトでもない
+        unsafe { self.repr.inline[SIZE - 1] & INLINE_TAG != 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        unsafe {
+            if self.is_inline() {
+                (self.repr.inline[SIZE - 1] & !INLINE_TAG) as usize
+            } else {
+                self.repr.heap.len
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        if self.is_inline() {
+            INLINE_CAP
+        } else {
+            usize::from_le_bytes(unsafe { self.repr.heap.cap })
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        unsafe {
+            let bytes = if self.is_inline() {
+                &self.repr.inline[..self.len()]
+            } else {
+                core::slice::from_raw_parts(self.repr.heap.ptr.as_ptr(), self.repr.heap.len)
+            };
+            core::str::from_utf8_unchecked(bytes)
+        }
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        let len = self.len();
+        if self.is_inline() {
+            let new_len = len + s.len();
+            if new_len <= INLINE_CAP {
+                // 末尾に書き足してから長さを更新する。タグのバイトはINLINE_CAPより後ろなので書き換えない
+                unsafe {
+                    self.repr.inline[len..new_len].copy_from_slice(s.as_bytes());
+                    self.repr.inline[SIZE - 1] = INLINE_TAG | new_len as u8;
+                }
+                return;
+            }
+            // ヒープへ移す。長い文字列は伸び続けることが多いので、2回分の余裕を取っておく
+            let mut heap = String::with_capacity(core::cmp::max(new_len, INLINE_CAP * 2));
+            heap.push_str(self.as_str());
+            heap.push_str(s);
+            self.repr = Repr { heap: into_heap(heap) };
+            return;
+        }
+        let heap = unsafe { self.repr.heap };
+        let mut string = ManuallyDrop::new(unsafe { String::from_raw_parts(heap.ptr.as_ptr(), heap.len, usize::from_le_bytes(heap.cap)) });
+        // push_strが確保し直しに失敗してpanicしても、それは確保し直す前なのでheapはそのまま有効
+        string.push_str(s);
+        self.repr = Repr { heap: into_heap(ManuallyDrop::into_inner(string)) };
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    pub fn clear(&mut self) {
+        *self = SsoString::new();
+    }
+
+    // ヒープに置いている時は、確保し直さずにStringに渡す
+    pub fn into_string(self) -> String {
+        if self.is_inline() {
+            return String::from(self.as_str());
+        }
+        let heap = unsafe { self.repr.heap };
+        // 領域の持ち主はStringに移るので、selfのDropで解放しないようにする
+        core::mem::forget(self);
+        unsafe { String::from_raw_parts(heap.ptr.as_ptr(), heap.len, usize::from_le_bytes(heap.cap)) }
+    }
+}
+
+// Stringを解放せずに3つ組にする
+fn into_heap(string: String) -> Heap {
+    let mut string = ManuallyDrop::new(string);
+    let cap = string.capacity();
+    Heap {
+        // as_mut_vecのas_mut_ptrは、バッファへの参照を作らずにポインタを返す
+        ptr: unsafe { NonNull::new_unchecked(string.as_mut_vec().as_mut_ptr()) },
+        len: string.len(),
+        cap: cap.to_le_bytes()
+    }
+}
+
+impl Drop for SsoString {
+    fn drop(&mut self) {
+        if !self.is_inline() {
+            let heap = unsafe { self.repr.heap };
+            drop(unsafe { String::from_raw_parts(heap.ptr.as_ptr(), heap.len, usize::from_le_bytes(heap.cap)) });
+        }
+    }
+}
+
+impl Default for SsoString {
+    fn default() -> SsoString {
+        SsoString::new()
+    }
+}
+
+impl core::ops::Deref for SsoString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl core::borrow::Borrow<str> for SsoString {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for SsoString {
+    fn from(s: &str) -> SsoString {
+        let mut string = SsoString::new();
+        string.push_str(s);
+        string
+    }
+}
+
+// 短ければ自分の中に写し、長ければStringのヒープの領域をそのまま引き継ぐ
+impl From<String> for SsoString {
+    fn from(s: String) -> SsoString {
+        if s.len() <= INLINE_CAP {
+            SsoString::from(s.as_str())
+        } else {
+            SsoString { repr: Repr { heap: into_heap(s) } }
+        }
+    }
+}
+
+impl From<SsoString> for String {
+    fn from(s: SsoString) -> String {
+        s.into_string()
+    }
+}
+
+impl Clone for SsoString {
+    fn clone(&self) -> SsoString {
+        SsoString::from(self.as_str())
+    }
+}
+
+impl fmt::Debug for SsoString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+impl fmt::Display for SsoString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+// Borrow<str>を実装するので、比較とハッシュはstrと同じ結果にしなければならない
+impl PartialEq for SsoString {
+    fn eq(&self, other: &SsoString) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for SsoString {}
+
+impl PartialEq<str> for SsoString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialOrd for SsoString {
+    fn partial_cmp(&self, other: &SsoString) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SsoString {
+    fn cmp(&self, other: &SsoString) -> core::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl core::hash::Hash for SsoString {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
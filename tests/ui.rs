@@ -0,0 +1,14 @@
+// コンパイルできてはいけないコードが、期待どおりのエラーでコンパイルに失敗することを確かめる
+// エラーメッセージはtests/ui/*.stderrに残してあり、変わった時はTRYBUILD=overwriteで書き直す
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    // ビルドできるはずのコードが1つでもあると、trybuildはcargo checkの代わりにcargo buildを使う
+    // RefWithFlag::newのアライメントの検査は単相化の後に評価されるので、checkだけでは失敗しない
+    t.pass("tests/ui/pass/*.rs");
+    // エラーメッセージはコンパイラの版ごとに変わるので、*.stderrはREADMEに書いた版で作ってある
+    // それ以外の版では、コンパイルに失敗するはずのコードの確認を飛ばす
+    if rustversion::cfg!(stable(1.95)) {
+        t.compile_fail("tests/ui/*.rs");
+    }
+}
@@ -0,0 +1,26 @@
+// 要素がSendやSyncでなければ、GapBufferやRefWithFlagもSendやSyncにならない
+extern crate alloc;
+
+#[allow(dead_code)]
+#[path = "../../src/gap.rs"]
+mod gap;
+
+#[allow(dead_code)]
+#[path = "../../src/ref_with_flag.rs"]
+mod ref_with_flag;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gap::GapBuffer;
+use ref_with_flag::RefWithFlag;
+
+fn assert_send<T: Send>() {}
+fn assert_sync<T: Sync>() {}
+
+fn main() {
+    assert_send::<GapBuffer<Rc<i32>>>();
+    assert_sync::<GapBuffer<Cell<i32>>>();
+    assert_send::<gap::Iter<'static, Cell<i32>>>();
+    assert_send::<RefWithFlag<'static, Cell<i32>>>();
+}
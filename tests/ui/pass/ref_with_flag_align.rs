@@ -0,0 +1,11 @@
+// 2バイト単位以上の型にはフラグを付けられる
+#[allow(dead_code)]
+#[path = "../../../src/ref_with_flag.rs"]
+mod ref_with_flag;
+
+use ref_with_flag::{RefWithFlag, RefWithFlagMut};
+
+fn main() {
+    assert!(RefWithFlag::<u16>::new(&1, true).get_flag());
+    assert!(!RefWithFlagMut::<[u16; 2]>::new(&mut [0; 2], false).get_flag());
+}
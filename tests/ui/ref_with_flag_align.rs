@@ -0,0 +1,11 @@
+// 1バイト単位の型には最下位ビットにフラグを置く余地が無いので、newを呼ぶコードはビルドできない
+#[allow(dead_code)]
+#[path = "../../src/ref_with_flag.rs"]
+mod ref_with_flag;
+
+use ref_with_flag::{RefWithFlag, RefWithFlagMut};
+
+fn main() {
+    RefWithFlag::<u8>::new(&1, true);
+    RefWithFlagMut::<[u8; 2]>::new(&mut [0; 2], true);
+}